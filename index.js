@@ -2,6 +2,47 @@
 const API_BASE_URL = 'http://localhost:8000/api';
 let currentEditingId = null;
 let currentEditingType = null;
+// ETag of the record as loaded into the edit form, so saving it cannot overwrite a newer version
+let currentEditingEtag = null;
+
+// Authentication: attach the session token to every API call, logging in on 401
+const rawFetch = window.fetch.bind(window);
+
+async function login() {
+  const username = prompt('Username');
+  const password = username && prompt('Password');
+  if (!password) return false;
+
+  const response = await rawFetch(`${API_BASE_URL}/auth/login`, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify({ username, password })
+  });
+  if (!response.ok) return false;
+
+  const { token } = await response.json();
+  localStorage.setItem('token', token);
+  return true;
+}
+
+window.fetch = async function(url, options = {}) {
+  // One key per logical POST, so a retried submission cannot create the record twice
+  const idempotency = options.method === 'POST' ? { 'Idempotency-Key': crypto.randomUUID() } : {};
+  const withToken = () => rawFetch(url, {
+    ...options,
+    headers: {
+      ...options.headers,
+      ...idempotency,
+      Authorization: `Bearer ${localStorage.getItem('token') || ''}`
+    }
+  });
+
+  let response = await withToken();
+  while (response.status === 401 && await login()) {
+    response = await withToken();
+  }
+  return response;
+};
 
 // DOM Elements
 const tabs = document.querySelectorAll('.tab');
@@ -33,6 +74,13 @@ document.addEventListener('DOMContentLoaded', function() {
   document.getElementById('product-update-btn').addEventListener('click', updateProduct);
   document.getElementById('product-cancel-btn').addEventListener('click', cancelEdit);
 
+  // Search on the server once typing pauses
+  let productSearchTimer;
+  document.getElementById('product-search').addEventListener('input', () => {
+    clearTimeout(productSearchTimer);
+    productSearchTimer = setTimeout(loadProducts, 300);
+  });
+
   // Set up category form handlers
   document.getElementById('category-create-btn').addEventListener('click', createCategory);
   document.getElementById('category-update-btn').addEventListener('click', updateCategory);
@@ -65,7 +113,7 @@ document.addEventListener('DOMContentLoaded', function() {
   document.getElementById('shipment-expected-date').valueAsDate = expectedDate;
 
   // Load initial data for the active tab (products)
-  loadTabData('products');
+  loadTabData('products').then(listenForChanges);
   
   // Set up event delegation for order items
   const orderItemsContainer = document.getElementById('order-items-container');
@@ -134,8 +182,11 @@ async function loadTabData(tabId) {
   async function loadProducts() {
     try {
       showLoading('products-table-body');
-      const response = await fetch(`${API_BASE_URL}/products`);
-      const products = await response.json();
+      const query = document.getElementById('product-search').value.trim();
+      const response = /\w/.test(query)
+        ? await fetch(`${API_BASE_URL}/products/search?per_page=1000&q=${encodeURIComponent(query)}`)
+        : await fetch(`${API_BASE_URL}/products?per_page=1000`);
+      const { items: products } = await response.json();
 
       const tableBody = document.getElementById('products-table-body');
       tableBody.innerHTML = '';
@@ -153,21 +204,11 @@ async function loadTabData(tabId) {
       }
 
       products.forEach(product => {
-        const row = document.createElement('tr');
-        row.innerHTML = `
-          <td>${product.product_id}</td>
-          <td>${product.name}</td>
-          <td>$${parseFloat(product.price).toFixed(2)}</td>
-          <td>${product.stock_quantity}</td>
-          <td>${product.category_name || 'N/A'}</td>
-          <td>${product.supplier_name || 'N/A'}</td>
-          <td>
-          <div class="action-buttons">
-          <button class="btn btn-warning btn-sm" onclick="editProduct(${product.product_id})">Edit</button>
-          <button class="btn btn-danger btn-sm" onclick="deleteProduct(${product.product_id})">Delete</button>
-          </div>
-          </td>
-          `;
+        const row = productRow(product);
+        // The server escapes the name, leaving <mark> as the only markup
+        if (product.name_highlight) {
+          row.children[1].innerHTML = product.name_highlight;
+        }
         tableBody.appendChild(row);
       });
     } catch (error) {
@@ -176,6 +217,34 @@ async function loadTabData(tabId) {
     }
   }
 
+// Text made safe to interpolate into HTML
+function escapeHtml(text) {
+  const element = document.createElement('div');
+  element.textContent = text;
+  return element.innerHTML;
+}
+
+// One row of the products table, also used to patch single rows on live updates
+function productRow(product) {
+  const row = document.createElement('tr');
+  row.dataset.id = product.product_id;
+  row.innerHTML = `
+    <td>${product.product_id}</td>
+    <td>${escapeHtml(product.name)}</td>
+    <td>$${parseFloat(product.price).toFixed(2)}</td>
+    <td>${product.stock_quantity}</td>
+    <td>${escapeHtml(product.category_name || 'N/A')}</td>
+    <td>${escapeHtml(product.supplier_name || 'N/A')}</td>
+    <td>
+    <div class="action-buttons">
+    <button class="btn btn-warning btn-sm" onclick="editProduct(${product.product_id})">Edit</button>
+    <button class="btn btn-danger btn-sm" onclick="deleteProduct(${product.product_id})">Delete</button>
+    </div>
+    </td>
+    `;
+  return row;
+}
+
 async function createProduct() {
   const name = document.getElementById('product-name').value;
   const description = document.getElementById('product-description').value;
@@ -208,7 +277,7 @@ async function createProduct() {
     if (response.ok) {
       showNotification('Product created successfully', 'success');
       resetProductForm();
-      await loadProducts();
+      await refreshUnlessLive(loadProducts);
     } else {
       showNotification('Failed to create product', 'error');
     }
@@ -236,6 +305,7 @@ async function editProduct(id) {
 
     currentEditingId = id;
     currentEditingType = 'product';
+    currentEditingEtag = response.headers.get('ETag');
   } catch (error) {
     console.error('Error loading product for edit:', error);
     showNotification('Failed to load product for editing', 'error');
@@ -262,6 +332,7 @@ async function updateProduct() {
       method: 'PUT',
       headers: {
         'Content-Type': 'application/json',
+        'If-Match': currentEditingEtag,
       },
       body: JSON.stringify({
         name,
@@ -276,9 +347,9 @@ async function updateProduct() {
     if (response.ok) {
       showNotification('Product updated successfully', 'success');
       resetProductForm();
-      await loadProducts();
+      await refreshUnlessLive(loadProducts);
     } else {
-      showNotification('Failed to update product', 'error');
+      showNotification(failureMessage(response, 'Failed to update product'), 'error');
     }
   } catch (error) {
     console.error('Error updating product:', error);
@@ -292,15 +363,17 @@ async function deleteProduct(id) {
   }
 
   try {
-    const response = await fetch(`${API_BASE_URL}/products/${id}`, {
-      method: 'DELETE'
+    const url = `${API_BASE_URL}/products/${id}`;
+    const response = await fetch(url, {
+      method: 'DELETE',
+      headers: { 'If-Match': await currentEtag(url) }
     });
 
     if (response.ok) {
       showNotification('Product deleted successfully', 'success');
-      await loadProducts();
+      await refreshUnlessLive(loadProducts);
     } else {
-      showNotification('Failed to delete product', 'error');
+      showNotification(failureMessage(response, 'Failed to delete product'), 'error');
     }
   } catch (error) {
     console.error('Error deleting product:', error);
@@ -326,14 +399,15 @@ function resetProductForm() {
 
   currentEditingId = null;
   currentEditingType = null;
+  currentEditingEtag = null;
 }
 
 // ========== CATEGORY FUNCTIONS ==========
   async function loadCategories() {
     try {
       showLoading('categories-table-body');
-      const response = await fetch(`${API_BASE_URL}/categories`);
-      const categories = await response.json();
+      const response = await fetch(`${API_BASE_URL}/categories?per_page=1000`);
+      const { items: categories } = await response.json();
 
       const tableBody = document.getElementById('categories-table-body');
       tableBody.innerHTML = '';
@@ -350,31 +424,35 @@ function resetProductForm() {
         return;
       }
 
-      categories.forEach(category => {
-        const row = document.createElement('tr');
-        row.innerHTML = `
-          <td>${category.category_id}</td>
-          <td>${category.name}</td>
-          <td>${category.description || 'N/A'}</td>
-          <td>
-          <div class="action-buttons">
-          <button class="btn btn-warning btn-sm" onclick="editCategory(${category.category_id})">Edit</button>
-          <button class="btn btn-danger btn-sm" onclick="deleteCategory(${category.category_id})">Delete</button>
-          </div>
-          </td>
-          `;
-        tableBody.appendChild(row);
-      });
+      categories.forEach(category => tableBody.appendChild(categoryRow(category)));
     } catch (error) {
       console.error('Error loading categories:', error);
       showNotification('Failed to load categories', 'error');
     }
   }
 
+// One row of the categories table, also used to patch single rows on live updates
+function categoryRow(category) {
+  const row = document.createElement('tr');
+  row.dataset.id = category.category_id;
+  row.innerHTML = `
+    <td>${category.category_id}</td>
+    <td>${category.name}</td>
+    <td>${category.description || 'N/A'}</td>
+    <td>
+    <div class="action-buttons">
+    <button class="btn btn-warning btn-sm" onclick="editCategory(${category.category_id})">Edit</button>
+    <button class="btn btn-danger btn-sm" onclick="deleteCategory(${category.category_id})">Delete</button>
+    </div>
+    </td>
+    `;
+  return row;
+}
+
 async function loadCategoriesForDropdown() {
   try {
-    const response = await fetch(`${API_BASE_URL}/categories`);
-    const categories = await response.json();
+    const response = await fetch(`${API_BASE_URL}/categories?per_page=1000`);
+    const { items: categories } = await response.json();
 
     const dropdown = document.getElementById('product-category');
     dropdown.innerHTML = '<option value="">Select a category</option>';
@@ -414,7 +492,7 @@ async function createCategory() {
     if (response.ok) {
       showNotification('Category created successfully', 'success');
       resetCategoryForm();
-      await loadCategories();
+      await refreshUnlessLive(loadCategories);
       await loadCategoriesForDropdown(); // Refresh dropdown in products tab
     } else {
       showNotification('Failed to create category', 'error');
@@ -439,6 +517,7 @@ async function editCategory(id) {
 
     currentEditingId = id;
     currentEditingType = 'category';
+    currentEditingEtag = response.headers.get('ETag');
   } catch (error) {
     console.error('Error loading category for edit:', error);
     showNotification('Failed to load category for editing', 'error');
@@ -461,6 +540,7 @@ async function updateCategory() {
       method: 'PUT',
       headers: {
         'Content-Type': 'application/json',
+        'If-Match': currentEditingEtag,
       },
       body: JSON.stringify({
         name,
@@ -471,10 +551,10 @@ async function updateCategory() {
     if (response.ok) {
       showNotification('Category updated successfully', 'success');
       resetCategoryForm();
-      await loadCategories();
+      await refreshUnlessLive(loadCategories);
       await loadCategoriesForDropdown(); // Refresh dropdown in products tab
     } else {
-      showNotification('Failed to update category', 'error');
+      showNotification(failureMessage(response, 'Failed to update category'), 'error');
     }
   } catch (error) {
     console.error('Error updating category:', error);
@@ -488,16 +568,18 @@ async function deleteCategory(id) {
   }
 
   try {
-    const response = await fetch(`${API_BASE_URL}/categories/${id}`, {
-      method: 'DELETE'
+    const url = `${API_BASE_URL}/categories/${id}`;
+    const response = await fetch(url, {
+      method: 'DELETE',
+      headers: { 'If-Match': await currentEtag(url) }
     });
 
     if (response.ok) {
       showNotification('Category deleted successfully', 'success');
-      await loadCategories();
+      await refreshUnlessLive(loadCategories);
       await loadCategoriesForDropdown(); // Refresh dropdown in products tab
     } else {
-      showNotification('Failed to delete category', 'error');
+      showNotification(failureMessage(response, 'Failed to delete category'), 'error');
     }
   } catch (error) {
     console.error('Error deleting category:', error);
@@ -519,14 +601,15 @@ function resetCategoryForm() {
 
   currentEditingId = null;
   currentEditingType = null;
+  currentEditingEtag = null;
 }
 
 // ========== CUSTOMER FUNCTIONS ==========
   async function loadCustomers() {
     try {
       showLoading('customers-table-body');
-      const response = await fetch(`${API_BASE_URL}/customers`);
-      const customers = await response.json();
+      const response = await fetch(`${API_BASE_URL}/customers?per_page=1000`);
+      const { items: customers } = await response.json();
 
       const tableBody = document.getElementById('customers-table-body');
       tableBody.innerHTML = '';
@@ -543,35 +626,39 @@ function resetCategoryForm() {
         return;
       }
 
-      customers.forEach(customer => {
-        const row = document.createElement('tr');
-        const registrationDate = new Date(customer.registration_date).toLocaleDateString();
-        row.innerHTML = `
-          <td>${customer.customer_id}</td>
-          <td>${customer.first_name} ${customer.last_name}</td>
-          <td>${customer.email}</td>
-          <td>${customer.phone || 'N/A'}</td>
-          <td>${registrationDate}</td>
-          <td>
-          <div class="action-buttons">
-          <button class="btn btn-warning btn-sm" onclick="editCustomer(${customer.customer_id})">Edit</button>
-          <button class="btn btn-danger btn-sm" onclick="deleteCustomer(${customer.customer_id})">Delete</button>
-          <button class="btn btn-primary btn-sm" onclick="viewCustomerDetails(${customer.customer_id})">Details</button>
-          </div>
-          </td>
-          `;
-        tableBody.appendChild(row);
-      });
+      customers.forEach(customer => tableBody.appendChild(customerRow(customer)));
     } catch (error) {
       console.error('Error loading customers:', error);
       showNotification('Failed to load customers', 'error');
     }
   }
 
+// One row of the customers table, also used to patch single rows on live updates
+function customerRow(customer) {
+  const row = document.createElement('tr');
+  row.dataset.id = customer.customer_id;
+  const registrationDate = new Date(customer.registration_date).toLocaleDateString();
+  row.innerHTML = `
+    <td>${customer.customer_id}</td>
+    <td>${customer.first_name} ${customer.last_name}</td>
+    <td>${customer.email}</td>
+    <td>${customer.phone || 'N/A'}</td>
+    <td>${registrationDate}</td>
+    <td>
+    <div class="action-buttons">
+    <button class="btn btn-warning btn-sm" onclick="editCustomer(${customer.customer_id})">Edit</button>
+    <button class="btn btn-danger btn-sm" onclick="deleteCustomer(${customer.customer_id})">Delete</button>
+    <button class="btn btn-primary btn-sm" onclick="viewCustomerDetails(${customer.customer_id})">Details</button>
+    </div>
+    </td>
+    `;
+  return row;
+}
+
 async function loadCustomersForDropdown() {
   try {
-    const response = await fetch(`${API_BASE_URL}/customers`);
-    const customers = await response.json();
+    const response = await fetch(`${API_BASE_URL}/customers?per_page=1000`);
+    const { items: customers } = await response.json();
 
     const dropdown = document.getElementById('order-customer');
     dropdown.innerHTML = '<option value="">Select a customer</option>';
@@ -617,7 +704,7 @@ async function createCustomer() {
     if (response.ok) {
       showNotification('Customer created successfully', 'success');
       resetCustomerForm();
-      await loadCustomers();
+      await refreshUnlessLive(loadCustomers);
       await loadCustomersForDropdown(); // Refresh dropdown in orders tab
     } else {
       showNotification('Failed to create customer', 'error');
@@ -645,6 +732,7 @@ async function editCustomer(id) {
 
     currentEditingId = id;
     currentEditingType = 'customer';
+    currentEditingEtag = response.headers.get('ETag');
   } catch (error) {
     console.error('Error loading customer for edit:', error);
     showNotification('Failed to load customer for editing', 'error');
@@ -670,6 +758,7 @@ async function updateCustomer() {
       method: 'PUT',
       headers: {
         'Content-Type': 'application/json',
+        'If-Match': currentEditingEtag,
       },
       body: JSON.stringify({
         first_name: firstName,
@@ -683,10 +772,10 @@ async function updateCustomer() {
     if (response.ok) {
       showNotification('Customer updated successfully', 'success');
       resetCustomerForm();
-      await loadCustomers();
+      await refreshUnlessLive(loadCustomers);
       await loadCustomersForDropdown(); // Refresh dropdown in orders tab
     } else {
-      showNotification('Failed to update customer', 'error');
+      showNotification(failureMessage(response, 'Failed to update customer'), 'error');
     }
   } catch (error) {
     console.error('Error updating customer:', error);
@@ -700,16 +789,18 @@ async function deleteCustomer(id) {
   }
 
   try {
-    const response = await fetch(`${API_BASE_URL}/customers/${id}`, {
-      method: 'DELETE'
+    const url = `${API_BASE_URL}/customers/${id}`;
+    const response = await fetch(url, {
+      method: 'DELETE',
+      headers: { 'If-Match': await currentEtag(url) }
     });
 
     if (response.ok) {
       showNotification('Customer deleted successfully', 'success');
-      await loadCustomers();
+      await refreshUnlessLive(loadCustomers);
       await loadCustomersForDropdown(); // Refresh dropdown in orders tab
     } else {
-      showNotification('Failed to delete customer', 'error');
+      showNotification(failureMessage(response, 'Failed to delete customer'), 'error');
     }
   } catch (error) {
     console.error('Error deleting customer:', error);
@@ -747,14 +838,15 @@ function resetCustomerForm() {
 
   currentEditingId = null;
   currentEditingType = null;
+  currentEditingEtag = null;
 }
 
 // ========== SUPPLIER FUNCTIONS ==========
   async function loadSuppliers() {
     try {
       showLoading('suppliers-table-body');
-      const response = await fetch(`${API_BASE_URL}/suppliers`);
-      const suppliers = await response.json();
+      const response = await fetch(`${API_BASE_URL}/suppliers?per_page=1000`);
+      const { items: suppliers } = await response.json();
 
       const tableBody = document.getElementById('suppliers-table-body');
       tableBody.innerHTML = '';
@@ -771,34 +863,38 @@ function resetCustomerForm() {
         return;
       }
 
-      suppliers.forEach(supplier => {
-        const row = document.createElement('tr');
-        row.innerHTML = `
-          <td>${supplier.supplier_id}</td>
-          <td>${supplier.company_name}</td>
-          <td>${supplier.contact_name || 'N/A'}</td>
-          <td>${supplier.email || 'N/A'}</td>
-          <td>${supplier.phone || 'N/A'}</td>
-          <td>
-          <div class="action-buttons">
-          <button class="btn btn-warning btn-sm" onclick="editSupplier(${supplier.supplier_id})">Edit</button>
-          <button class="btn btn-danger btn-sm" onclick="deleteSupplier(${supplier.supplier_id})">Delete</button>
-          <button class="btn btn-primary btn-sm" onclick="viewSupplierDetails(${supplier.supplier_id})">Details</button>
-          </div>
-          </td>
-          `;
-        tableBody.appendChild(row);
-      });
+      suppliers.forEach(supplier => tableBody.appendChild(supplierRow(supplier)));
     } catch (error) {
       console.error('Error loading suppliers:', error);
       showNotification('Failed to load suppliers', 'error');
     }
   }
 
+// One row of the suppliers table, also used to patch single rows on live updates
+function supplierRow(supplier) {
+  const row = document.createElement('tr');
+  row.dataset.id = supplier.supplier_id;
+  row.innerHTML = `
+    <td>${supplier.supplier_id}</td>
+    <td>${supplier.company_name}</td>
+    <td>${supplier.contact_name || 'N/A'}</td>
+    <td>${supplier.email || 'N/A'}</td>
+    <td>${supplier.phone || 'N/A'}</td>
+    <td>
+    <div class="action-buttons">
+    <button class="btn btn-warning btn-sm" onclick="editSupplier(${supplier.supplier_id})">Edit</button>
+    <button class="btn btn-danger btn-sm" onclick="deleteSupplier(${supplier.supplier_id})">Delete</button>
+    <button class="btn btn-primary btn-sm" onclick="viewSupplierDetails(${supplier.supplier_id})">Details</button>
+    </div>
+    </td>
+    `;
+  return row;
+}
+
 async function loadSuppliersForDropdown() {
   try {
-    const response = await fetch(`${API_BASE_URL}/suppliers`);
-    const suppliers = await response.json();
+    const response = await fetch(`${API_BASE_URL}/suppliers?per_page=1000`);
+    const { items: suppliers } = await response.json();
 
     // For product form
     const productDropdown = document.getElementById('product-supplier');
@@ -854,7 +950,7 @@ async function createSupplier() {
     if (response.ok) {
       showNotification('Supplier created successfully', 'success');
       resetSupplierForm();
-      await loadSuppliers();
+      await refreshUnlessLive(loadSuppliers);
       await loadSuppliersForDropdown(); // Refresh dropdowns
     } else {
       showNotification('Failed to create supplier', 'error');
@@ -882,6 +978,7 @@ async function editSupplier(id) {
 
     currentEditingId = id;
     currentEditingType = 'supplier';
+    currentEditingEtag = response.headers.get('ETag');
   } catch (error) {
     console.error('Error loading supplier for edit:', error);
     showNotification('Failed to load supplier for editing', 'error');
@@ -907,6 +1004,7 @@ async function updateSupplier() {
       method: 'PUT',
       headers: {
         'Content-Type': 'application/json',
+        'If-Match': currentEditingEtag,
       },
       body: JSON.stringify({
         company_name: companyName,
@@ -920,10 +1018,10 @@ async function updateSupplier() {
     if (response.ok) {
       showNotification('Supplier updated successfully', 'success');
       resetSupplierForm();
-      await loadSuppliers();
+      await refreshUnlessLive(loadSuppliers);
       await loadSuppliersForDropdown(); // Refresh dropdowns
     } else {
-      showNotification('Failed to update supplier', 'error');
+      showNotification(failureMessage(response, 'Failed to update supplier'), 'error');
     }
   } catch (error) {
     console.error('Error updating supplier:', error);
@@ -937,16 +1035,18 @@ async function deleteSupplier(id) {
   }
 
   try {
-    const response = await fetch(`${API_BASE_URL}/suppliers/${id}`, {
-      method: 'DELETE'
+    const url = `${API_BASE_URL}/suppliers/${id}`;
+    const response = await fetch(url, {
+      method: 'DELETE',
+      headers: { 'If-Match': await currentEtag(url) }
     });
 
     if (response.ok) {
       showNotification('Supplier deleted successfully', 'success');
-      await loadSuppliers();
+      await refreshUnlessLive(loadSuppliers);
       await loadSuppliersForDropdown(); // Refresh dropdowns
     } else {
-      showNotification('Failed to delete supplier', 'error');
+      showNotification(failureMessage(response, 'Failed to delete supplier'), 'error');
     }
   } catch (error) {
     console.error('Error deleting supplier:', error);
@@ -984,14 +1084,15 @@ function resetSupplierForm() {
 
   currentEditingId = null;
   currentEditingType = null;
+  currentEditingEtag = null;
 }
 
 // ========== ORDER FUNCTIONS ==========
   async function loadOrders() {
     try {
       showLoading('orders-table-body');
-      const response = await fetch(`${API_BASE_URL}/orders`);
-      const orders = await response.json();
+      const response = await fetch(`${API_BASE_URL}/orders?per_page=1000`);
+      const { items: orders } = await response.json();
 
       const tableBody = document.getElementById('orders-table-body');
       tableBody.innerHTML = '';
@@ -1008,36 +1109,40 @@ function resetSupplierForm() {
         return;
       }
 
-      orders.forEach(order => {
-        const row = document.createElement('tr');
-        const orderDate = new Date(order.order_date).toLocaleDateString();
-        const statusClass = `status-${order.status}`;
-        row.innerHTML = `
-          <td>${order.order_id}</td>
-          <td>${order.customer_name || 'N/A'}</td>
-          <td>${orderDate}</td>
-          <td><span class="status-badge ${statusClass}">${order.status}</span></td>
-          <td>$${parseFloat(order.total_amount).toFixed(2)}</td>
-          <td>
-          <div class="action-buttons">
-          <button class="btn btn-warning btn-sm" onclick="editOrder(${order.order_id})">Edit</button>
-          <button class="btn btn-danger btn-sm" onclick="deleteOrder(${order.order_id})">Delete</button>
-          <button class="btn btn-primary btn-sm" onclick="viewOrderDetails(${order.order_id})">Details</button>
-          </div>
-          </td>
-          `;
-        tableBody.appendChild(row);
-      });
+      orders.forEach(order => tableBody.appendChild(orderRow(order)));
     } catch (error) {
       console.error('Error loading orders:', error);
       showNotification('Failed to load orders', 'error');
     }
   }
 
+// One row of the orders table, also used to patch single rows on live updates
+function orderRow(order) {
+  const row = document.createElement('tr');
+  row.dataset.id = order.order_id;
+  const orderDate = new Date(order.order_date).toLocaleDateString();
+  const statusClass = `status-${order.status}`;
+  row.innerHTML = `
+    <td>${order.order_id}</td>
+    <td>${order.customer_name || 'N/A'}</td>
+    <td>${orderDate}</td>
+    <td><span class="status-badge ${statusClass}">${order.status}</span></td>
+    <td>$${parseFloat(order.total_amount).toFixed(2)}</td>
+    <td>
+    <div class="action-buttons">
+    <button class="btn btn-warning btn-sm" onclick="editOrder(${order.order_id})">Edit</button>
+    <button class="btn btn-danger btn-sm" onclick="deleteOrder(${order.order_id})">Delete</button>
+    <button class="btn btn-primary btn-sm" onclick="viewOrderDetails(${order.order_id})">Details</button>
+    </div>
+    </td>
+    `;
+  return row;
+}
+
 async function loadProductsForOrderItems() {
   try {
-    const response = await fetch(`${API_BASE_URL}/products`);
-    const products = await response.json();
+    const response = await fetch(`${API_BASE_URL}/products?per_page=1000`);
+    const { items: products } = await response.json();
 
     const dropdowns = document.querySelectorAll('#order-items-container .order-item-product');
     
@@ -1154,8 +1259,7 @@ async function createOrder() {
     if (productId && quantity && unitPrice) {
       orderItems.push({
         product_id: parseInt(productId),
-        quantity: parseInt(quantity),
-        unit_price: parseFloat(unitPrice)
+        quantity: parseInt(quantity)
       });
     }
   }
@@ -1174,7 +1278,6 @@ async function createOrder() {
       body: JSON.stringify({
         customer_id: parseInt(customerId),
         status,
-        total_amount: parseFloat(totalAmount),
         shipping_address: shippingAddress,
         items: orderItems
       })
@@ -1183,7 +1286,7 @@ async function createOrder() {
     if (response.ok) {
       showNotification('Order created successfully', 'success');
       resetOrderForm();
-      await loadOrders();
+      await refreshUnlessLive(loadOrders);
     } else {
       showNotification('Failed to create order', 'error');
     }
@@ -1254,6 +1357,7 @@ async function editOrder(id) {
 
     currentEditingId = id;
     currentEditingType = 'order';
+    currentEditingEtag = response.headers.get('ETag');
 
     // Load products for dropdowns
     await loadProductsForOrderItems();
@@ -1286,8 +1390,7 @@ async function updateOrder() {
     if (productId && quantity && unitPrice) {
       orderItems.push({
         product_id: parseInt(productId),
-        quantity: parseInt(quantity),
-        unit_price: parseFloat(unitPrice)
+        quantity: parseInt(quantity)
       });
     }
   });
@@ -1302,11 +1405,11 @@ async function updateOrder() {
       method: 'PUT',
       headers: {
         'Content-Type': 'application/json',
+        'If-Match': currentEditingEtag,
       },
       body: JSON.stringify({
         customer_id: parseInt(customerId),
         status,
-        total_amount: parseFloat(totalAmount),
         shipping_address: shippingAddress,
         items: orderItems
       })
@@ -1315,9 +1418,9 @@ async function updateOrder() {
     if (response.ok) {
       showNotification('Order updated successfully', 'success');
       resetOrderForm();
-      await loadOrders();
+      await refreshUnlessLive(loadOrders);
     } else {
-      showNotification('Failed to update order', 'error');
+      showNotification(failureMessage(response, 'Failed to update order'), 'error');
     }
   } catch (error) {
     console.error('Error updating order:', error);
@@ -1331,15 +1434,17 @@ async function deleteOrder(id) {
   }
 
   try {
-    const response = await fetch(`${API_BASE_URL}/orders/${id}`, {
-      method: 'DELETE'
+    const url = `${API_BASE_URL}/orders/${id}`;
+    const response = await fetch(url, {
+      method: 'DELETE',
+      headers: { 'If-Match': await currentEtag(url) }
     });
 
     if (response.ok) {
       showNotification('Order deleted successfully', 'success');
-      await loadOrders();
+      await refreshUnlessLive(loadOrders);
     } else {
-      showNotification('Failed to delete order', 'error');
+      showNotification(failureMessage(response, 'Failed to delete order'), 'error');
     }
   } catch (error) {
     console.error('Error deleting order:', error);
@@ -1387,14 +1492,15 @@ function resetOrderForm() {
 
   currentEditingId = null;
   currentEditingType = null;
+  currentEditingEtag = null;
 }
 
 // ========== SHIPMENT FUNCTIONS ==========
   async function loadShipments() {
     try {
       showLoading('shipments-table-body');
-      const response = await fetch(`${API_BASE_URL}/shipments`);
-      const shipments = await response.json();
+      const response = await fetch(`${API_BASE_URL}/shipments?per_page=1000`);
+      const { items: shipments } = await response.json();
 
       const tableBody = document.getElementById('shipments-table-body');
       tableBody.innerHTML = '';
@@ -1411,38 +1517,42 @@ function resetOrderForm() {
         return;
       }
 
-      shipments.forEach(shipment => {
-        const row = document.createElement('tr');
-        const shipmentDate = new Date(shipment.shipment_date).toLocaleDateString();
-        const expectedDate = new Date(shipment.expected_delivery_date).toLocaleDateString();
-        const statusClass = `status-${shipment.status.replace('_', '-')}`;
-        row.innerHTML = `
-          <td>${shipment.shipment_id}</td>
-          <td>${shipment.supplier_name || 'N/A'}</td>
-          <td>${shipmentDate}</td>
-          <td>${expectedDate}</td>
-          <td><span class="status-badge ${statusClass}">${shipment.status}</span></td>
-          <td>$${parseFloat(shipment.total_cost).toFixed(2)}</td>
-          <td>
-          <div class="action-buttons">
-          <button class="btn btn-warning btn-sm" onclick="editShipment(${shipment.shipment_id})">Edit</button>
-          <button class="btn btn-danger btn-sm" onclick="deleteShipment(${shipment.shipment_id})">Delete</button>
-          <button class="btn btn-primary btn-sm" onclick="viewShipmentDetails(${shipment.shipment_id})">Details</button>
-          </div>
-          </td>
-          `;
-        tableBody.appendChild(row);
-      });
+      shipments.forEach(shipment => tableBody.appendChild(shipmentRow(shipment)));
     } catch (error) {
       console.error('Error loading shipments:', error);
       showNotification('Failed to load shipments', 'error');
     }
   }
 
+// One row of the shipments table, also used to patch single rows on live updates
+function shipmentRow(shipment) {
+  const row = document.createElement('tr');
+  row.dataset.id = shipment.shipment_id;
+  const shipmentDate = new Date(shipment.shipment_date).toLocaleDateString();
+  const expectedDate = new Date(shipment.expected_delivery_date).toLocaleDateString();
+  const statusClass = `status-${shipment.status.replace('_', '-')}`;
+  row.innerHTML = `
+    <td>${shipment.shipment_id}</td>
+    <td>${shipment.supplier_name || 'N/A'}</td>
+    <td>${shipmentDate}</td>
+    <td>${expectedDate}</td>
+    <td><span class="status-badge ${statusClass}">${shipment.status}</span></td>
+    <td>$${parseFloat(shipment.total_cost).toFixed(2)}</td>
+    <td>
+    <div class="action-buttons">
+    <button class="btn btn-warning btn-sm" onclick="editShipment(${shipment.shipment_id})">Edit</button>
+    <button class="btn btn-danger btn-sm" onclick="deleteShipment(${shipment.shipment_id})">Delete</button>
+    <button class="btn btn-primary btn-sm" onclick="viewShipmentDetails(${shipment.shipment_id})">Details</button>
+    </div>
+    </td>
+    `;
+  return row;
+}
+
 async function loadProductsForShipmentItems() {
   try {
-    const response = await fetch(`${API_BASE_URL}/products`);
-    const products = await response.json();
+    const response = await fetch(`${API_BASE_URL}/products?per_page=1000`);
+    const { items: products } = await response.json();
 
     const dropdowns = document.querySelectorAll('#shipment-items-container .shipment-item-product');
     
@@ -1590,7 +1700,7 @@ async function createShipment() {
     if (response.ok) {
       showNotification('Shipment created successfully', 'success');
       resetShipmentForm();
-      await loadShipments();
+      await refreshUnlessLive(loadShipments);
     } else {
       showNotification('Failed to create shipment', 'error');
     }
@@ -1662,6 +1772,7 @@ async function editShipment(id) {
 
     currentEditingId = id;
     currentEditingType = 'shipment';
+    currentEditingEtag = response.headers.get('ETag');
 
     // Load products for dropdowns
     await loadProductsForShipmentItems();
@@ -1716,6 +1827,7 @@ async function updateShipment() {
       method: 'PUT',
       headers: {
         'Content-Type': 'application/json',
+        'If-Match': currentEditingEtag,
       },
       body: JSON.stringify({
         supplier_id: parseInt(supplierId),
@@ -1730,9 +1842,9 @@ async function updateShipment() {
     if (response.ok) {
       showNotification('Shipment updated successfully', 'success');
       resetShipmentForm();
-      await loadShipments();
+      await refreshUnlessLive(loadShipments);
     } else {
-      showNotification('Failed to update shipment', 'error');
+      showNotification(failureMessage(response, 'Failed to update shipment'), 'error');
     }
   } catch (error) {
     console.error('Error updating shipment:', error);
@@ -1746,15 +1858,17 @@ async function deleteShipment(id) {
   }
 
   try {
-    const response = await fetch(`${API_BASE_URL}/shipments/${id}`, {
-      method: 'DELETE'
+    const url = `${API_BASE_URL}/shipments/${id}`;
+    const response = await fetch(url, {
+      method: 'DELETE',
+      headers: { 'If-Match': await currentEtag(url) }
     });
 
     if (response.ok) {
       showNotification('Shipment deleted successfully', 'success');
-      await loadShipments();
+      await refreshUnlessLive(loadShipments);
     } else {
-      showNotification('Failed to delete shipment', 'error');
+      showNotification(failureMessage(response, 'Failed to delete shipment'), 'error');
     }
   } catch (error) {
     console.error('Error deleting shipment:', error);
@@ -1809,6 +1923,7 @@ function resetShipmentForm() {
 
   currentEditingId = null;
   currentEditingType = null;
+  currentEditingEtag = null;
 }
 
 // ========== HELPER FUNCTIONS ==========
@@ -1824,7 +1939,116 @@ function resetShipmentForm() {
       `;
   }
 
+// ========== LIVE UPDATES ==========
+// Tables patched row by row from the /events stream
+const liveTables = {
+  product: { tab: 'products', path: 'products', body: 'products-table-body', row: productRow },
+  category: { tab: 'categories', path: 'categories', body: 'categories-table-body', row: categoryRow },
+  customer: { tab: 'customers', path: 'customers', body: 'customers-table-body', row: customerRow },
+  supplier: { tab: 'suppliers', path: 'suppliers', body: 'suppliers-table-body', row: supplierRow },
+  order: { tab: 'orders', path: 'orders', body: 'orders-table-body', row: orderRow },
+  shipment: { tab: 'shipments', path: 'shipments', body: 'shipments-table-body', row: shipmentRow }
+};
+let liveUpdates = false;
+
+function activeTab() {
+  return document.querySelector('.tab.active')?.getAttribute('data-tab') || 'products';
+}
+
+// After the user's own changes, the table only needs reloading when the live feed is down
+async function refreshUnlessLive(load) {
+  if (!liveUpdates) await load();
+}
+
+async function applyChange({ type, id, action }) {
+  const table = liveTables[type];
+  if (!table) return;
+  if (type === 'order' && action === 'created') {
+    showNotification(`New order #${id} received`, 'success');
+  }
+  // Hidden tables are reloaded in full when their tab is opened
+  if (activeTab() !== table.tab) return;
+
+  const tableBody = document.getElementById(table.body);
+  const existing = tableBody.querySelector(`tr[data-id="${id}"]`);
+  if (action === 'deleted') {
+    existing?.remove();
+    return;
+  }
+
+  const response = await fetch(`${API_BASE_URL}/${table.path}/${id}`);
+  if (!response.ok) {
+    existing?.remove();
+    return;
+  }
+  const row = table.row(await response.json());
+  if (existing) {
+    existing.replaceWith(row);
+  } else {
+    tableBody.querySelector('.empty-state')?.closest('tr')?.remove();
+    tableBody.prepend(row);
+  }
+}
+
+function handleEventFrame(frame) {
+  let event = 'message';
+  let data = '';
+  frame.split('\n').forEach(line => {
+    if (line.startsWith('event:')) event = line.slice(6).trim();
+    else if (line.startsWith('data:')) data += line.slice(5).trim();
+  });
+
+  if (event === 'change') {
+    applyChange(JSON.parse(data)).catch(error => console.error('Error applying change:', error));
+  } else if (event === 'resync') {
+    loadTabData(activeTab());
+  }
+}
+
+// EventSource cannot send the bearer token, so the stream is read through fetch
+async function listenForChanges() {
+  let reconnecting = false;
+  for (;;) {
+    try {
+      const response = await fetch(`${API_BASE_URL}/events`);
+      if (response.ok) {
+        liveUpdates = true;
+        // Changes made while disconnected were missed
+        if (reconnecting) loadTabData(activeTab());
+        const reader = response.body.pipeThrough(new TextDecoderStream()).getReader();
+        let buffer = '';
+        for (;;) {
+          const { value, done } = await reader.read();
+          if (done) break;
+          buffer += value;
+          let end;
+          while ((end = buffer.indexOf('\n\n')) >= 0) {
+            handleEventFrame(buffer.slice(0, end));
+            buffer = buffer.slice(end + 2);
+          }
+        }
+      }
+    } catch (error) {
+      console.error('Live updates disconnected:', error);
+    }
+    liveUpdates = false;
+    reconnecting = true;
+    await new Promise(resolve => setTimeout(resolve, 3000));
+  }
+}
+
 // Notification function
+// The ETag of a record as it is now, for changes made straight from a table row
+async function currentEtag(url) {
+  const response = await fetch(url);
+  return response.headers.get('ETag');
+}
+
+// Explains a failed write, calling out one refused because someone else changed the record first
+function failureMessage(response, fallback) {
+  return response.status === 412 ? 'Someone else changed this record meanwhile, reload it and try again' : fallback;
+}
+
 function showNotification(message, type) {
   notification.textContent = message;
   notification.className = `notification ${type}`;
@@ -0,0 +1,145 @@
+use std::future::{Ready, ready};
+
+use actix_web::{
+    Error, FromRequest, HttpMessage, HttpRequest,
+    body::{BoxBody, MessageBody},
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    http::{Method, header},
+    middleware::Next,
+    web,
+};
+
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::{db::users::Role, error::AppError, services::AuthService, state::AppState};
+
+tokio::task_local! {
+    static ACTOR: String;
+}
+
+/// Username of the user behind the request currently being handled, if any.
+pub fn current_actor() -> Option<String> {
+    ACTOR.try_with(|actor| actor.clone()).ok()
+}
+
+/// The authenticated user, available to handlers as an extractor.
+#[derive(Clone, Debug)]
+pub struct CurrentUser {
+    pub user_id: i32,
+    pub username: String,
+    pub role: Role,
+    pub token: String,
+}
+
+impl FromRequest for CurrentUser {
+    type Error = AppError;
+    type Future = Ready<Result<Self, AppError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(req.extensions().get::<CurrentUser>().cloned().ok_or(AppError::Unauthorized))
+    }
+}
+
+/// `?include_deleted=true`, which also returns soft-deleted records. Only admins may ask for it.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct IncludeDeleted {
+    #[serde(default)]
+    pub include_deleted: bool,
+}
+
+impl FromRequest for IncludeDeleted {
+    type Error = AppError;
+    type Future = Ready<Result<Self, AppError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let params = match web::Query::<IncludeDeleted>::from_query(req.query_string()) {
+            Ok(params) => params.into_inner(),
+            Err(err) => return ready(Err(AppError::validation(err.to_string()))),
+        };
+        let is_admin = req.extensions().get::<CurrentUser>().is_some_and(|user| user.role == Role::Admin);
+        if params.include_deleted && !is_admin {
+            return ready(Err(AppError::Forbidden));
+        }
+        ready(Ok(params))
+    }
+}
+
+/// Which roles may read and which may modify a group of routes. Admins may always do both.
+#[derive(Clone, Copy)]
+pub struct Access {
+    read: Option<&'static [Role]>,
+    write: &'static [Role],
+}
+
+impl Access {
+    fn allows(&self, role: Role, method: &Method) -> bool {
+        if role == Role::Admin {
+            return true;
+        }
+        if matches!(*method, Method::GET | Method::HEAD) {
+            self.read.is_none_or(|roles| roles.contains(&role))
+        } else {
+            self.write.contains(&role)
+        }
+    }
+}
+
+pub const CATALOG: Access = Access { read: None, write: &[Role::Warehouse] };
+pub const CUSTOMERS: Access = Access { read: None, write: &[Role::Sales] };
+pub const ORDERS: Access = Access { read: None, write: &[Role::Sales, Role::Warehouse] };
+pub const SHIPMENTS: Access = Access { read: None, write: &[Role::Warehouse] };
+pub const ADMIN_ONLY: Access = Access { read: Some(&[]), write: &[] };
+
+/// Resolves the bearer token, if any, to a `CurrentUser` for the rest of the request.
+pub async fn authenticate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let Some(token) = bearer_token(&req) else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+
+    let Some(state) = req.app_data::<web::Data<AppState>>().cloned() else {
+        return Ok(req.error_response(AppError::Internal));
+    };
+    let user = match AuthService::authenticate(&state.db, &token).await {
+        Ok(user) => user,
+        Err(err) => return Ok(req.error_response(err)),
+    };
+
+    req.extensions_mut().insert(CurrentUser {
+        user_id: user.user_id,
+        username: user.username.clone(),
+        role: user.role,
+        token,
+    });
+    Ok(ACTOR.scope(user.username, next.call(req)).await?.map_into_boxed_body())
+}
+
+/// Rejects requests without a user (401) or whose role `access` does not allow (403).
+pub async fn authorize(
+    access: Access,
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let role = req.extensions().get::<CurrentUser>().map(|user| user.role);
+
+    match role {
+        None => Ok(req.error_response(AppError::Unauthorized)),
+        Some(role) if !access.allows(role, req.method()) => Ok(req.error_response(AppError::Forbidden)),
+        Some(_) => Ok(next.call(req).await?.map_into_boxed_body()),
+    }
+}
+
+fn bearer_token(req: &ServiceRequest) -> Option<String> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+        .map(str::to_string)
+}
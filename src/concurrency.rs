@@ -0,0 +1,67 @@
+use std::future::{Ready, ready};
+
+use actix_web::{
+    FromRequest, HttpRequest,
+    dev::Payload,
+    http::{Method, header::{self, EntityTag, Header}},
+};
+
+use crate::error::AppError;
+
+/// The `ETag` of a row at the given `version`.
+pub fn etag(version: i32) -> header::ETag {
+    header::ETag(EntityTag::new_strong(version.to_string()))
+}
+
+/// The client's `If-Match` precondition. It is required on `PUT` and `DELETE`, so that
+/// an edit based on a stale read cannot silently overwrite someone else's change; other
+/// requests without one are not checked.
+pub struct IfMatch(Option<header::IfMatch>);
+
+impl IfMatch {
+    /// No precondition, for changes that are not made against a version the client read.
+    pub fn none() -> Self {
+        IfMatch(None)
+    }
+
+    /// A precondition on `version` given in a request body rather than a header. Like the
+    /// header on `PUT` and `DELETE`, it is required.
+    pub fn version(version: Option<i32>) -> Result<Self, AppError> {
+        let version = version.ok_or(AppError::PreconditionRequired)?;
+        Ok(IfMatch(Some(header::IfMatch::Items(vec![etag(version).0]))))
+    }
+
+    /// Fails with 412 unless the row at `version` is one the client has seen.
+    pub fn check(&self, version: i32) -> Result<(), AppError> {
+        match &self.0 {
+            None | Some(header::IfMatch::Any) => Ok(()),
+            Some(header::IfMatch::Items(tags)) => {
+                let current = etag(version).0;
+                if tags.iter().any(|tag| tag.strong_eq(&current)) {
+                    Ok(())
+                } else {
+                    Err(AppError::PreconditionFailed)
+                }
+            }
+        }
+    }
+}
+
+impl FromRequest for IfMatch {
+    type Error = AppError;
+    type Future = Ready<Result<Self, AppError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        if !req.headers().contains_key(header::IF_MATCH) {
+            return ready(match *req.method() {
+                Method::PUT | Method::DELETE => Err(AppError::PreconditionRequired),
+                _ => Ok(IfMatch(None)),
+            });
+        }
+        ready(
+            header::IfMatch::parse(req)
+                .map(|if_match| IfMatch(Some(if_match)))
+                .map_err(|_| AppError::validation("Malformed If-Match header")),
+        )
+    }
+}
@@ -5,6 +5,11 @@ use serde::Deserialize;
 pub struct ServerConfig {
     pub application: ApplicationConfig,
     pub db: DatabaseConfig,
+    pub inventory: InventoryConfig,
+    pub auth: AuthConfig,
+    pub idempotency: IdempotencyConfig,
+    pub webhooks: WebhookConfig,
+    pub outbox: OutboxConfig,
 }
 #[derive(Deserialize)]
 pub struct ApplicationConfig {
@@ -20,6 +25,54 @@ pub struct DatabaseConfig {
     pub name: String,
 }
 
+#[derive(Deserialize)]
+pub struct InventoryConfig {
+    pub reconcile_interval_secs: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AuthConfig {
+    pub session_ttl_hours: i64,
+    pub session_purge_interval_secs: u64,
+    pub admin_username: Option<String>,
+    pub admin_password: Option<String>,
+    /// File holding the admin password, such as a mounted secret; used when
+    /// `admin_password` is not set.
+    pub admin_password_file: Option<String>,
+}
+
+impl AuthConfig {
+    /// The credentials for the first admin account, if configured.
+    pub fn admin_credentials(&self) -> std::io::Result<Option<(String, String)>> {
+        let password = match (&self.admin_password, &self.admin_password_file) {
+            (Some(password), _) => Some(password.clone()),
+            (None, Some(path)) => Some(std::fs::read_to_string(path)?.trim_end_matches(['\r', '\n']).to_string()),
+            (None, None) => None,
+        };
+        Ok(self.admin_username.clone().zip(password))
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct IdempotencyConfig {
+    pub key_ttl_hours: i64,
+    pub purge_interval_secs: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct WebhookConfig {
+    pub poll_interval_secs: u64,
+    pub timeout_secs: u64,
+    pub max_attempts: i32,
+    pub backoff_base_secs: i64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct OutboxConfig {
+    pub poll_interval_secs: u64,
+    pub batch_size: u64,
+}
+
 pub fn read_config() -> Result<ServerConfig, ConfigError> {
     let additional_config_path = std::env::var("SHOP_ENVIROMENT").unwrap_or("local".into());
 
@@ -28,7 +81,11 @@ pub fn read_config() -> Result<ServerConfig, ConfigError> {
         .add_source(config::File::with_name(&format!(
             "configuration/{additional_config_path}.toml"
         )))
-        .add_source(config::Environment::with_prefix("SHOP"))
+        .add_source(
+            config::Environment::with_prefix("SHOP")
+                .prefix_separator("_")
+                .separator("__"),
+        )
         .build()?
         .try_deserialize()
 }
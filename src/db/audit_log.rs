@@ -0,0 +1,64 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use chrono::{DateTime, Utc};
+
+/// One change to one record, written in the transaction that made it.
+#[derive(Default, Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "auditlog")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub audit_id: i64,
+    pub entity: AuditEntity,
+    pub entity_id: i32,
+    pub action: AuditAction,
+    /// Username of whoever made the change; empty for changes made by the system.
+    pub actor: Option<String>,
+    pub request_id: Option<String>,
+    /// JSON object of the changed fields, each with its `old` and `new` value.
+    pub changes: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize, ToSchema)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::N(20))")]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEntity {
+    #[default]
+    #[sea_orm(string_value = "category")]
+    Category,
+    #[sea_orm(string_value = "customer")]
+    Customer,
+    #[sea_orm(string_value = "customer_address")]
+    CustomerAddress,
+    #[sea_orm(string_value = "supplier")]
+    Supplier,
+    #[sea_orm(string_value = "product")]
+    Product,
+    #[sea_orm(string_value = "order")]
+    Order,
+    #[sea_orm(string_value = "shipment")]
+    Shipment,
+    #[sea_orm(string_value = "user")]
+    User,
+    #[sea_orm(string_value = "webhook")]
+    Webhook,
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize, ToSchema)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::N(10))")]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    #[default]
+    #[sea_orm(string_value = "create")]
+    Create,
+    #[sea_orm(string_value = "update")]
+    Update,
+    #[sea_orm(string_value = "delete")]
+    Delete,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,12 +1,16 @@
 use sea_orm::entity::prelude::*;
+use serde::Serialize;
+use chrono::{DateTime, Utc};
 
-#[derive(Default, Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[derive(Default, Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
 #[sea_orm(table_name = "categories")]
 pub struct Model {
     #[sea_orm(primary_key)]
     pub category_id: i32,
     pub name: String,
     pub description: Option<String>,
+    pub version: i32,
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -15,4 +19,12 @@ pub enum Relation {
     Product,
 }
 
-impl ActiveModelBehavior for ActiveModel {}
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C: ConnectionTrait>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr> {
+        if !insert {
+            self.version = super::next_version(&self.version);
+        }
+        Ok(self)
+    }
+}
@@ -0,0 +1,66 @@
+use sea_orm::entity::prelude::*;
+use serde::Serialize;
+
+#[derive(Default, Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
+#[sea_orm(table_name = "customeraddresses")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub address_id: i32,
+    pub customer_id: i32,
+    pub line1: String,
+    pub line2: Option<String>,
+    pub city: String,
+    pub region: Option<String>,
+    pub postal_code: Option<String>,
+    /// ISO 3166-1 alpha-2 country code.
+    pub country: String,
+    pub is_default_shipping: bool,
+    pub is_default_billing: bool,
+    pub version: i32,
+}
+
+impl Model {
+    /// The address on one line, as copied onto an order.
+    pub fn one_line(&self) -> String {
+        let locality = [self.region.as_deref(), self.postal_code.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+        
+        let mut parts = vec![self.line1.as_str()];
+        parts.extend(self.line2.as_deref());
+        parts.push(&self.city);
+        if !locality.is_empty() {
+            parts.push(&locality);
+        }
+        parts.push(&self.country);
+        parts.join(", ")
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::customers::Entity",
+        from = "Column::CustomerId",
+        to = "super::customers::Column::CustomerId"
+    )]
+    Customer,
+}
+
+impl Related<super::customers::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Customer.def()
+    }
+}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C: ConnectionTrait>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr> {
+        if !insert {
+            self.version = super::next_version(&self.version);
+        }
+        Ok(self)
+    }
+}
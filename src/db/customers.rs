@@ -1,7 +1,8 @@
 use sea_orm::entity::prelude::*;
+use serde::Serialize;
 use chrono::{DateTime, Utc};
 
-#[derive(Default, Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[derive(Default, Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
 #[sea_orm(table_name = "customers")]
 pub struct Model {
     #[sea_orm(primary_key)]
@@ -12,12 +13,16 @@ pub struct Model {
     pub phone: Option<String>,
     pub registration_date: DateTime<Utc>,
     pub address: Option<String>,
+    pub version: i32,
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(has_many = "super::orders::Entity")]
     Order,
+    #[sea_orm(has_many = "super::customer_addresses::Entity")]
+    Address,
 }
 
 impl Related<super::orders::Entity> for Entity {
@@ -26,4 +31,18 @@ impl Related<super::orders::Entity> for Entity {
     }
 }
 
-impl ActiveModelBehavior for ActiveModel {}
+impl Related<super::customer_addresses::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Address.def()
+    }
+}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C: ConnectionTrait>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr> {
+        if !insert {
+            self.version = super::next_version(&self.version);
+        }
+        Ok(self)
+    }
+}
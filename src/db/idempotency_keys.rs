@@ -0,0 +1,38 @@
+use sea_orm::entity::prelude::*;
+use chrono::{DateTime, Utc};
+
+/// A client-supplied `Idempotency-Key` and the response first produced for it.
+/// `response_status` is empty while that first request is still running.
+#[derive(Default, Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "idempotencykeys")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub user_id: i32,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub idempotency_key: String,
+    pub request_hash: String,
+    pub response_status: Option<i32>,
+    /// JSON list of the `[name, value]` headers worth replaying, such as `ETag`.
+    pub response_headers: Option<String>,
+    pub response_body: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::UserId",
+        to = "super::users::Column::UserId"
+    )]
+    User,
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
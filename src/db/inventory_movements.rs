@@ -0,0 +1,54 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use chrono::{DateTime, Utc};
+
+#[derive(Default, Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "inventorymovements")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub movement_id: i32,
+    pub product_id: i32,
+    pub delta: i32,
+    pub reason: MovementReason,
+    pub order_id: Option<i32>,
+    pub shipment_id: Option<i32>,
+    pub actor: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize, ToSchema)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::N(20))")]
+#[serde(rename_all = "snake_case")]
+pub enum MovementReason {
+    #[sea_orm(string_value = "sale")]
+    Sale,
+    #[sea_orm(string_value = "receipt")]
+    Receipt,
+    #[default]
+    #[sea_orm(string_value = "adjustment")]
+    Adjustment,
+    /// Stock a customer sent back after delivery. No workflow books returns yet.
+    #[sea_orm(string_value = "return")]
+    Return,
+    #[sea_orm(string_value = "cancel")]
+    Cancel,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::products::Entity",
+        from = "Column::ProductId",
+        to = "super::products::Column::ProductId"
+    )]
+    Product,
+}
+
+impl Related<super::products::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Product.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,22 +1,40 @@
 pub mod categories;
 pub mod suppliers;
 pub mod customers;
+pub mod customer_addresses;
 pub mod products;
 pub mod orders;
 pub mod order_items;
 pub mod shipments;
 pub mod shipments_items;
+pub mod inventory_movements;
+pub mod users;
+pub mod sessions;
+pub mod idempotency_keys;
+pub mod webhooks;
+pub mod webhook_deliveries;
+pub mod outbox;
+pub mod audit_log;
 
 pub use categories::Entity as Categories;
 pub use suppliers::Entity as Suppliers;
 pub use customers::Entity as Customers;
+pub use customer_addresses::Entity as CustomerAddresses;
 pub use products::Entity as Products;
 pub use orders::Entity as Orders;
 pub use order_items::Entity as OrderItems;
 pub use shipments::Entity as Shipments;
 pub use shipments_items::Entity as ShipmentItems;
+pub use inventory_movements::Entity as InventoryMovements;
+pub use users::Entity as Users;
+pub use sessions::Entity as Sessions;
+pub use idempotency_keys::Entity as IdempotencyKeys;
+pub use webhooks::Entity as Webhooks;
+pub use webhook_deliveries::Entity as WebhookDeliveries;
+pub use outbox::Entity as Outbox;
+pub use audit_log::Entity as AuditLog;
 
-use sea_orm::{Database, DatabaseConnection, DbErr};
+use sea_orm::{ActiveValue, Database, DatabaseConnection, DbErr};
 use crate::migration::{Migrator, MigratorTrait}; 
 
 pub async fn connect_database(connection_string: impl AsRef<str>) -> Result<DatabaseConnection, DbErr> {
@@ -28,6 +46,14 @@ pub async fn migrate(connection: &DatabaseConnection) -> Result<(), DbErr> {
     Ok(())
 }
 
+/// The `version` an updated row is saved with, so every save changes its ETag.
+fn next_version(version: &ActiveValue<i32>) -> ActiveValue<i32> {
+    match version {
+        ActiveValue::Set(version) | ActiveValue::Unchanged(version) => ActiveValue::Set(version + 1),
+        ActiveValue::NotSet => ActiveValue::NotSet,
+    }
+}
+
 pub fn connection_string<A: AsRef<str>>(
     user: impl AsRef<str>,
     password: impl AsRef<str>,
@@ -11,6 +11,12 @@ pub struct Model {
     pub unit_price: Decimal,
 }
 
+impl Model {
+    pub fn line_total(&self) -> Decimal {
+        self.unit_price * Decimal::from(self.quantity)
+    }
+}
+
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(
@@ -29,12 +35,12 @@ pub enum Relation {
 
 impl Related<super::products::Entity> for Entity {
     fn to() -> RelationDef {
-        super::products::Relation::OrderItem.def()
+        Relation::Product.def()
     }
 }
 impl Related<super::orders::Entity> for Entity {
     fn to() -> RelationDef {
-        super::orders::Relation::OrderItem.def()
+        Relation::Order.def()
     }
 }
 
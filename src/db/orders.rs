@@ -1,16 +1,66 @@
 use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use chrono::{DateTime, Utc};
 
-#[derive(Default, Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[derive(Default, Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
 #[sea_orm(table_name = "orders")]
 pub struct Model {
     #[sea_orm(primary_key)]
     pub order_id: i32,
     pub customer_id: i32,
     pub order_date: DateTime<Utc>,
-    pub status: String,
+    pub status: OrderStatus,
     pub total_amount: Decimal,
     pub shipping_address: String,
+    pub version: i32,
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize, ToSchema)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::N(20))")]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    #[default]
+    #[sea_orm(string_value = "pending")]
+    Pending,
+    #[sea_orm(string_value = "confirmed")]
+    Confirmed,
+    #[sea_orm(string_value = "shipped")]
+    Shipped,
+    #[sea_orm(string_value = "delivered")]
+    Delivered,
+    #[sea_orm(string_value = "cancelled")]
+    Cancelled,
+}
+
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Pending => "pending",
+            OrderStatus::Confirmed => "confirmed",
+            OrderStatus::Shipped => "shipped",
+            OrderStatus::Delivered => "delivered",
+            OrderStatus::Cancelled => "cancelled",
+        }
+    }
+
+    /// Stock is held for the order but has not left the warehouse yet.
+    pub fn reserves_stock(&self) -> bool {
+        matches!(self, OrderStatus::Pending | OrderStatus::Confirmed)
+    }
+
+    /// Orders move forward one step at a time and can only be cancelled before they ship.
+    pub fn can_transition_to(&self, next: OrderStatus) -> bool {
+        use OrderStatus::*;
+        matches!(
+            (self, next),
+            (Pending, Confirmed)
+                | (Confirmed, Shipped)
+                | (Shipped, Delivered)
+                | (Pending, Cancelled)
+                | (Confirmed, Cancelled)
+        )
+    }
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -36,4 +86,46 @@ impl Related<super::order_items::Entity> for Entity {
     }
 }
 
-impl ActiveModelBehavior for ActiveModel {}
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C: ConnectionTrait>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr> {
+        if !insert {
+            self.version = super::next_version(&self.version);
+        }
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OrderStatus::{self, *};
+    use sea_orm::Iterable;
+
+    #[test]
+    fn orders_move_forward_one_step_and_cancel_only_before_shipping() {
+        let allowed = [
+            (Pending, Confirmed),
+            (Confirmed, Shipped),
+            (Shipped, Delivered),
+            (Pending, Cancelled),
+            (Confirmed, Cancelled),
+        ];
+        for from in OrderStatus::iter() {
+            for to in OrderStatus::iter() {
+                assert_eq!(
+                    from.can_transition_to(to),
+                    allowed.contains(&(from, to)),
+                    "{} -> {}",
+                    from.as_str(),
+                    to.as_str()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn only_unshipped_orders_reserve_stock() {
+        let reserving: Vec<OrderStatus> = OrderStatus::iter().filter(OrderStatus::reserves_stock).collect();
+        assert_eq!(reserving, [Pending, Confirmed]);
+    }
+}
@@ -0,0 +1,67 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use chrono::{DateTime, Utc};
+
+/// A domain event, written in the same transaction as the change it describes and
+/// relayed to consumers in the order those transactions wrote them.
+#[derive(Default, Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "outbox")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub outbox_id: i64,
+    pub event_type: EventType,
+    pub aggregate_type: String,
+    pub aggregate_id: i32,
+    /// JSON describing the change.
+    pub payload: String,
+    pub created_at: DateTime<Utc>,
+    /// When every consumer had been handed the event; empty until then.
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize, ToSchema)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::N(50))")]
+pub enum EventType {
+    #[default]
+    #[sea_orm(string_value = "order.created")]
+    #[serde(rename = "order.created")]
+    OrderCreated,
+    #[sea_orm(string_value = "order.status_changed")]
+    #[serde(rename = "order.status_changed")]
+    OrderStatusChanged,
+    #[sea_orm(string_value = "shipment.created")]
+    #[serde(rename = "shipment.created")]
+    ShipmentCreated,
+    #[sea_orm(string_value = "shipment.status_changed")]
+    #[serde(rename = "shipment.status_changed")]
+    ShipmentStatusChanged,
+    #[sea_orm(string_value = "shipment.received")]
+    #[serde(rename = "shipment.received")]
+    ShipmentReceived,
+    #[sea_orm(string_value = "product.low_stock")]
+    #[serde(rename = "product.low_stock")]
+    ProductLowStock,
+    #[sea_orm(string_value = "customer.created")]
+    #[serde(rename = "customer.created")]
+    CustomerCreated,
+}
+
+impl EventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventType::OrderCreated => "order.created",
+            EventType::OrderStatusChanged => "order.status_changed",
+            EventType::ShipmentCreated => "shipment.created",
+            EventType::ShipmentStatusChanged => "shipment.status_changed",
+            EventType::ShipmentReceived => "shipment.received",
+            EventType::ProductLowStock => "product.low_stock",
+            EventType::CustomerCreated => "customer.created",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
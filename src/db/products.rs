@@ -1,6 +1,8 @@
 use sea_orm::entity::prelude::*;
+use serde::Serialize;
+use chrono::{DateTime, Utc};
 
-#[derive(Default, Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[derive(Default, Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
 #[sea_orm(table_name = "products")]
 pub struct Model {
     #[sea_orm(primary_key)]
@@ -11,6 +13,8 @@ pub struct Model {
     pub stock_quantity: i32,
     pub category_id: i32,
     pub supplier_id: i32,
+    pub version: i32,
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -31,6 +35,8 @@ pub enum Relation {
     OrderItem,
     #[sea_orm(has_many = "super::shipments_items::Entity")]
     ShipmentItem, 
+    #[sea_orm(has_many = "super::inventory_movements::Entity")]
+    InventoryMovement,
 }
 
 impl Related<super::categories::Entity> for Entity {
@@ -45,4 +51,18 @@ impl Related<super::suppliers::Entity> for Entity {
     }
 }
 
-impl ActiveModelBehavior for ActiveModel {}
+impl Related<super::inventory_movements::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::InventoryMovement.def()
+    }
+}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C: ConnectionTrait>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr> {
+        if !insert {
+            self.version = super::next_version(&self.version);
+        }
+        Ok(self)
+    }
+}
@@ -1,7 +1,9 @@
 use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use chrono::NaiveDate;
 
-#[derive(Default, Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[derive(Default, Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
 #[sea_orm(table_name = "shipments")]
 pub struct Model {
     #[sea_orm(primary_key)]
@@ -9,8 +11,47 @@ pub struct Model {
     pub supplier_id: i32,
     pub shipment_date: NaiveDate,
     pub expected_delivery_date: NaiveDate,
-    pub status: String,
+    pub status: ShipmentStatus,
     pub total_cost: Decimal,
+    pub version: i32,
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize, ToSchema)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::N(20))")]
+#[serde(rename_all = "snake_case")]
+pub enum ShipmentStatus {
+    #[default]
+    #[sea_orm(string_value = "in_transit")]
+    InTransit,
+    #[sea_orm(string_value = "partially_received")]
+    PartiallyReceived,
+    #[sea_orm(string_value = "delivered")]
+    Delivered,
+    #[sea_orm(string_value = "cancelled")]
+    Cancelled,
+}
+
+impl ShipmentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShipmentStatus::InTransit => "in_transit",
+            ShipmentStatus::PartiallyReceived => "partially_received",
+            ShipmentStatus::Delivered => "delivered",
+            ShipmentStatus::Cancelled => "cancelled",
+        }
+    }
+
+    /// Status changes that can be asked for directly. A shipment can be delivered until it
+    /// is, and cancelled even afterwards; `partially_received` is only reached by receiving
+    /// part of the goods.
+    pub fn can_transition_to(&self, next: ShipmentStatus) -> bool {
+        use ShipmentStatus::*;
+        matches!(
+            (self, next),
+            (InTransit | PartiallyReceived, Delivered)
+                | (InTransit | PartiallyReceived | Delivered, Cancelled)
+        )
+    }
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -27,13 +68,54 @@ pub enum Relation {
 
 impl Related<super::suppliers::Entity> for Entity {
     fn to() -> RelationDef {
-        super::suppliers::Relation::Shipment.def()
+        Relation::Supplier.def()
     }
 }
 impl Related<super::shipments_items::Entity> for Entity {
     fn to() -> RelationDef {
-        super::shipments_items::Relation::Shipment.def()
+        Relation::ShipmentItem.def()
     }
 }
 
-impl ActiveModelBehavior for ActiveModel {}
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C: ConnectionTrait>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr> {
+        if !insert {
+            self.version = super::next_version(&self.version);
+        }
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShipmentStatus::{self, *};
+    use sea_orm::Iterable;
+
+    #[test]
+    fn shipments_are_delivered_until_they_are_and_cancellable_after() {
+        let allowed = [
+            (InTransit, Delivered),
+            (PartiallyReceived, Delivered),
+            (InTransit, Cancelled),
+            (PartiallyReceived, Cancelled),
+            (Delivered, Cancelled),
+        ];
+        for from in ShipmentStatus::iter() {
+            for to in ShipmentStatus::iter() {
+                assert_eq!(
+                    from.can_transition_to(to),
+                    allowed.contains(&(from, to)),
+                    "{} -> {}",
+                    from.as_str(),
+                    to.as_str()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn partially_received_cannot_be_asked_for() {
+        assert!(ShipmentStatus::iter().all(|from| !from.can_transition_to(PartiallyReceived)));
+    }
+}
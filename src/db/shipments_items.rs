@@ -9,6 +9,7 @@ pub struct Model {
     pub product_id: i32,
     pub quantity: i32,
     pub unit_cost: Decimal,
+    pub received_quantity: i32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -29,13 +30,13 @@ pub enum Relation {
 
 impl Related<super::products::Entity> for Entity {
     fn to() -> RelationDef {
-        super::products::Relation::ShipmentItem.def()
+        Relation::Product.def()
     }
 }
 
 impl Related<super::shipments::Entity> for Entity {
     fn to() -> RelationDef {
-        super::shipments::Relation::ShipmentItem.def()
+        Relation::Shipment.def()
     }
 }
 
@@ -0,0 +1,34 @@
+use sea_orm::entity::prelude::*;
+use serde::Serialize;
+use chrono::{DateTime, Utc};
+use super::outbox::EventType;
+
+/// An endpoint that receives signed POSTs for one type of event.
+#[derive(Default, Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
+#[sea_orm(table_name = "webhooks")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub webhook_id: i32,
+    pub url: String,
+    pub event_type: EventType,
+    /// Key of the HMAC-SHA256 signature sent with every delivery. Never serialized, so it
+    /// stays out of audit snapshots.
+    #[serde(skip)]
+    pub secret: String,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::webhook_deliveries::Entity")]
+    Deliveries,
+}
+
+impl Related<super::webhook_deliveries::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Deliveries.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,44 +1,67 @@
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError, ValidationErrors};
 use chrono::{DateTime, Utc, NaiveDate};
+use crate::db::orders::OrderStatus;
+use crate::db::shipments::ShipmentStatus;
+use crate::db::inventory_movements::MovementReason;
+use crate::db::users::Role;
+use crate::db::outbox::EventType;
+use crate::db::webhook_deliveries::DeliveryStatus;
+use crate::db::audit_log::{AuditAction, AuditEntity};
+use crate::error::{ErrorResponse, FieldError};
+use crate::pagination::Page;
+use crate::validation::{ProductLine, country_code, field_error, non_negative, unique_products};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
 pub struct CategoryCreate {
+    #[validate(length(min = 1, max = 100))]
     pub name: String,
     pub description: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
 pub struct CategoryUpdate {
+    #[validate(length(min = 1, max = 100))]
     pub name: Option<String>,
     pub description: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct CategoryResponse {
     pub category_id: i32,
     pub name: String,
     pub description: Option<String>,
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
 pub struct CustomerCreate {
+    #[validate(length(min = 1, max = 50))]
     pub first_name: String,
+    #[validate(length(min = 1, max = 50))]
     pub last_name: String,
+    #[validate(email, length(max = 100))]
     pub email: String,
+    #[validate(length(max = 20))]
     pub phone: Option<String>,
     pub address: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
 pub struct CustomerUpdate {
+    #[validate(length(min = 1, max = 50))]
     pub first_name: Option<String>,
+    #[validate(length(min = 1, max = 50))]
     pub last_name: Option<String>,
+    #[validate(email, length(max = 100))]
     pub email: Option<String>,
+    #[validate(length(max = 20))]
     pub phone: Option<String>,
     pub address: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct CustomerResponse {
     pub customer_id: i32,
     pub first_name: String,
@@ -47,27 +70,114 @@ pub struct CustomerResponse {
     pub phone: Option<String>,
     pub registration_date: DateTime<Utc>,
     pub address: Option<String>,
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CustomerOrdersResponse {
+    pub customer: CustomerResponse,
+    pub orders: Vec<OrderResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct CustomerAddressCreate {
+    #[validate(length(min = 1, max = 200))]
+    pub line1: String,
+    #[validate(length(max = 200))]
+    pub line2: Option<String>,
+    #[validate(length(min = 1, max = 100))]
+    pub city: String,
+    #[validate(length(max = 100))]
+    pub region: Option<String>,
+    #[validate(length(max = 20))]
+    pub postal_code: Option<String>,
+    /// ISO 3166-1 alpha-2 code, such as `US`.
+    #[validate(custom(function = "country_code"))]
+    pub country: String,
+    /// Makes this the address orders are shipped to when they name none, in place of
+    /// the customer's current default.
+    #[serde(default)]
+    pub is_default_shipping: bool,
+    #[serde(default)]
+    pub is_default_billing: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct CustomerAddressUpdate {
+    #[validate(length(min = 1, max = 200))]
+    pub line1: Option<String>,
+    #[validate(length(max = 200))]
+    pub line2: Option<String>,
+    #[validate(length(min = 1, max = 100))]
+    pub city: Option<String>,
+    #[validate(length(max = 100))]
+    pub region: Option<String>,
+    #[validate(length(max = 20))]
+    pub postal_code: Option<String>,
+    #[validate(custom(function = "country_code"))]
+    pub country: Option<String>,
+    pub is_default_shipping: Option<bool>,
+    pub is_default_billing: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CustomerAddressResponse {
+    pub address_id: i32,
+    pub customer_id: i32,
+    pub line1: String,
+    pub line2: Option<String>,
+    pub city: String,
+    pub region: Option<String>,
+    pub postal_code: Option<String>,
+    pub country: String,
+    pub is_default_shipping: bool,
+    pub is_default_billing: bool,
+}
+
+/// Everything stored about a customer, as returned by `GET /customers/{id}/export`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CustomerExport {
+    pub exported_at: DateTime<Utc>,
+    pub customer: CustomerResponse,
+    pub addresses: Vec<CustomerAddressResponse>,
+    pub orders: Vec<OrderDetailsResponse>,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeleteParams {
+    /// Delete the record even though others still refer to it, keeping them as they are.
+    #[serde(default)]
+    pub archive: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
 pub struct SupplierCreate {
+    #[validate(length(min = 1, max = 150))]
     pub company_name: String,
+    #[validate(length(max = 100))]
     pub contact_name: Option<String>,
+    #[validate(email, length(max = 100))]
     pub email: Option<String>,
+    #[validate(length(max = 20))]
     pub phone: Option<String>,
     pub address: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
 pub struct SupplierUpdate {
+    #[validate(length(min = 1, max = 150))]
     pub company_name: Option<String>,
+    #[validate(length(max = 100))]
     pub contact_name: Option<String>,
+    #[validate(email, length(max = 100))]
     pub email: Option<String>,
+    #[validate(length(max = 20))]
     pub phone: Option<String>,
     pub address: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct SupplierResponse {
     pub supplier_id: i32,
     pub company_name: String,
@@ -75,29 +185,42 @@ pub struct SupplierResponse {
     pub email: Option<String>,
     pub phone: Option<String>,
     pub address: Option<String>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SupplierProductsResponse {
+    pub supplier: SupplierResponse,
+    pub products: Vec<ProductResponse>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
 pub struct ProductCreate {
+    #[validate(length(min = 1, max = 200))]
     pub name: String,
     pub description: Option<String>,
+    #[validate(custom(function = "non_negative"))]
     pub price: rust_decimal::Decimal,
+    #[validate(range(min = 0))]
     pub stock_quantity: i32,
     pub category_id: i32,
     pub supplier_id: i32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
 pub struct ProductUpdate {
+    #[validate(length(min = 1, max = 200))]
     pub name: Option<String>,
     pub description: Option<String>,
+    #[validate(custom(function = "non_negative"))]
     pub price: Option<rust_decimal::Decimal>,
+    #[validate(range(min = 0))]
     pub stock_quantity: Option<i32>,
     pub category_id: Option<i32>,
     pub supplier_id: Option<i32>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ProductResponse {
     pub product_id: i32,
     pub name: String,
@@ -108,117 +231,535 @@ pub struct ProductResponse {
     pub supplier_id: i32,
     pub category_name: Option<String>,
     pub supplier_name: Option<String>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ProductFilter {
+    pub category_id: Option<i32>,
+    pub supplier_id: Option<i32>,
+    pub price_min: Option<rust_decimal::Decimal>,
+    pub price_max: Option<rust_decimal::Decimal>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchParams {
+    /// Words to look for in names and descriptions. Each word also matches as a prefix.
+    pub q: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProductSearchHit {
+    #[serde(flatten)]
+    pub product: ProductResponse,
+    pub rank: f32,
+    /// The HTML-escaped name with matches wrapped in `<mark>` tags, the only markup it contains.
+    pub name_highlight: String,
+    /// Passages of the description around the matches, marked up like `name_highlight`.
+    pub snippet: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FacetCount {
+    pub id: i32,
+    pub name: String,
+    pub count: i64,
+}
+
+/// Matches priced from `min` up to, but not including, `max`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PriceFacet {
+    pub min: rust_decimal::Decimal,
+    pub max: Option<rust_decimal::Decimal>,
+    pub count: i64,
+}
+
+/// Match counts per value of each filter, counted with the other filters applied.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProductFacets {
+    pub categories: Vec<FacetCount>,
+    pub suppliers: Vec<FacetCount>,
+    pub prices: Vec<PriceFacet>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProductSearchResponse {
+    #[serde(flatten)]
+    pub page: Page<ProductSearchHit>,
+    pub facets: ProductFacets,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
 pub struct OrderItemCreate {
     pub product_id: i32,
+    #[validate(range(min = 1))]
     pub quantity: i32,
-    pub unit_price: rust_decimal::Decimal,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl ProductLine for OrderItemCreate {
+    fn product_id(&self) -> i32 {
+        self.product_id
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct OrderItemResponse {
     pub order_id: i32,
     pub product_id: i32,
     pub quantity: i32,
     pub unit_price: rust_decimal::Decimal,
+    pub line_total: rust_decimal::Decimal,
     pub product_name: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+#[allow(clippy::duplicated_attributes)] // two schema rules sharing the same option
+#[validate(
+    schema(function = "distinct_order_products", skip_on_field_errors = false),
+    schema(function = "one_shipping_address", skip_on_field_errors = false)
+)]
 pub struct OrderCreate {
     pub customer_id: i32,
-    pub status: Option<String>,
-    pub total_amount: rust_decimal::Decimal,
-    pub shipping_address: String,
+    pub status: Option<OrderStatus>,
+    /// Where to ship to, as free text. Without it or `shipping_address_id` the order
+    /// ships to the customer's default shipping address.
+    #[validate(length(min = 1))]
+    pub shipping_address: Option<String>,
+    /// One of the customer's addresses, which is copied onto the order.
+    pub shipping_address_id: Option<i32>,
+    #[validate(nested)]
     pub items: Vec<OrderItemCreate>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+fn distinct_order_products(dto: &OrderCreate) -> Result<(), ValidationError> {
+    unique_products(&dto.items)
+}
+
+fn one_shipping_address(dto: &OrderCreate) -> Result<(), ValidationError> {
+    if dto.shipping_address.is_some() && dto.shipping_address_id.is_some() {
+        return Err(field_error("shipping_address_id", "exclusive", "cannot be given together with shipping_address"));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+#[validate(schema(function = "distinct_order_update_products", skip_on_field_errors = false))]
 pub struct OrderUpdate {
-    pub status: Option<String>,
-    pub total_amount: Option<rust_decimal::Decimal>,
+    pub status: Option<OrderStatus>,
+    #[validate(length(min = 1))]
     pub shipping_address: Option<String>,
+    #[validate(nested)]
     pub items: Option<Vec<OrderItemCreate>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+fn distinct_order_update_products(dto: &OrderUpdate) -> Result<(), ValidationError> {
+    dto.items.as_deref().map_or(Ok(()), unique_products)
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct OrderResponse {
     pub order_id: i32,
     pub customer_id: i32,
     pub order_date: DateTime<Utc>,
-    pub status: String,
+    pub status: OrderStatus,
     pub total_amount: rust_decimal::Decimal,
     pub shipping_address: String,
     pub customer_name: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct OrderDetailsResponse {
     pub order_id: i32,
     pub customer_id: i32,
     pub order_date: DateTime<Utc>,
-    pub status: String,
+    pub status: OrderStatus,
     pub total_amount: rust_decimal::Decimal,
     pub shipping_address: String,
     pub customer_name: Option<String>,
     pub items: Vec<OrderItemResponse>,
+    pub subtotal: rust_decimal::Decimal,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OrderFilter {
+    pub status: Option<OrderStatus>,
+    pub customer_id: Option<i32>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
 pub struct ShipmentItemCreate {
     pub product_id: i32,
+    #[validate(range(min = 1))]
     pub quantity: i32,
+    #[validate(custom(function = "non_negative"))]
     pub unit_cost: rust_decimal::Decimal,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl ProductLine for ShipmentItemCreate {
+    fn product_id(&self) -> i32 {
+        self.product_id
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ShipmentItemResponse {
     pub shipment_id: i32,
     pub product_id: i32,
     pub quantity: i32,
     pub unit_cost: rust_decimal::Decimal,
+    pub received_quantity: i32,
     pub product_name: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+#[allow(clippy::duplicated_attributes)] // two schema rules sharing the same option
+#[validate(
+    schema(function = "distinct_shipment_products", skip_on_field_errors = false),
+    schema(function = "delivery_not_before_shipment", skip_on_field_errors = false)
+)]
 pub struct ShipmentCreate {
     pub supplier_id: i32,
     pub shipment_date: NaiveDate,
     pub expected_delivery_date: NaiveDate,
-    pub status: Option<String>,
+    pub status: Option<ShipmentStatus>,
+    #[validate(custom(function = "non_negative"))]
     pub total_cost: rust_decimal::Decimal,
+    #[validate(nested)]
     pub items: Vec<ShipmentItemCreate>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+fn distinct_shipment_products(dto: &ShipmentCreate) -> Result<(), ValidationError> {
+    unique_products(&dto.items)
+}
+
+fn delivery_not_before_shipment(dto: &ShipmentCreate) -> Result<(), ValidationError> {
+    if dto.expected_delivery_date < dto.shipment_date {
+        return Err(field_error("expected_delivery_date", "date_order", "must not be before shipment_date"));
+    }
+    Ok(())
+}
+
+fn distinct_shipment_update_products(dto: &ShipmentUpdate) -> Result<(), ValidationError> {
+    dto.items.as_deref().map_or(Ok(()), unique_products)
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+#[validate(schema(function = "distinct_shipment_update_products", skip_on_field_errors = false))]
 pub struct ShipmentUpdate {
-    pub status: Option<String>,
+    pub status: Option<ShipmentStatus>,
     pub expected_delivery_date: Option<NaiveDate>,
+    #[validate(custom(function = "non_negative"))]
     pub total_cost: Option<rust_decimal::Decimal>,
+    #[validate(nested)]
     pub items: Option<Vec<ShipmentItemCreate>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl ShipmentUpdate {
+    /// The rules that depend on the stored shipment, checked once it has been loaded.
+    pub fn validate_against(&self, shipment_date: NaiveDate) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if self.expected_delivery_date.is_some_and(|date| date < shipment_date) {
+            errors.add(
+                "expected_delivery_date",
+                ValidationError::new("date_order").with_message("must not be before shipment_date".into()),
+            );
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ShipmentFilter {
+    pub status: Option<ShipmentStatus>,
+    pub supplier_id: Option<i32>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct ShipmentItemReceive {
+    pub product_id: i32,
+    #[validate(range(min = 1))]
+    pub quantity: i32,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema, Validate)]
+pub struct ShipmentReceive {
+    #[serde(default)]
+    #[validate(nested)]
+    pub items: Vec<ShipmentItemReceive>,
+    /// Mark the shipment delivered after receiving `items`, even though some lines are
+    /// still short. The shortfall stays in the supplier's discrepancy report.
+    #[serde(default)]
+    pub close: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ShipmentResponse {
     pub shipment_id: i32,
     pub supplier_id: i32,
     pub shipment_date: NaiveDate,
     pub expected_delivery_date: NaiveDate,
-    pub status: String,
+    pub status: ShipmentStatus,
     pub total_cost: rust_decimal::Decimal,
     pub supplier_name: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ShipmentDetailsResponse {
     pub shipment_id: i32,
     pub supplier_id: i32,
     pub shipment_date: NaiveDate,
     pub expected_delivery_date: NaiveDate,
-    pub status: String,
+    pub status: ShipmentStatus,
     pub total_cost: rust_decimal::Decimal,
     pub supplier_name: Option<String>,
     pub items: Vec<ShipmentItemResponse>,
 }
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ShipmentDiscrepancyResponse {
+    pub shipment_id: i32,
+    pub shipment_date: NaiveDate,
+    pub expected_delivery_date: NaiveDate,
+    pub product_id: i32,
+    pub product_name: Option<String>,
+    pub quantity: i32,
+    pub received_quantity: i32,
+    /// Negative when more arrived than was ordered.
+    pub shortfall: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InventoryMovementResponse {
+    pub movement_id: i32,
+    pub product_id: i32,
+    pub delta: i32,
+    pub reason: MovementReason,
+    pub order_id: Option<i32>,
+    pub shipment_id: Option<i32>,
+    pub actor: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StockDiscrepancyResponse {
+    pub product_id: i32,
+    pub stock_quantity: i32,
+    pub ledger_quantity: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LoginResponse {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+    pub user: UserResponse,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct UserCreate {
+    #[validate(length(min = 3, max = 50))]
+    pub username: String,
+    #[validate(length(min = 8))]
+    pub password: String,
+    pub role: Role,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct UserUpdate {
+    #[validate(length(min = 8))]
+    pub password: Option<String>,
+    pub role: Option<Role>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserResponse {
+    pub user_id: i32,
+    pub username: String,
+    pub role: Role,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct WebhookCreate {
+    #[validate(url, length(max = 500))]
+    pub url: String,
+    pub event_type: EventType,
+    pub active: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct WebhookUpdate {
+    #[validate(url, length(max = 500))]
+    pub url: Option<String>,
+    pub event_type: Option<EventType>,
+    pub active: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WebhookResponse {
+    pub webhook_id: i32,
+    pub url: String,
+    pub event_type: EventType,
+    /// Key of the `X-Webhook-Signature` HMAC. Only returned when the webhook is created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeliveryFilter {
+    pub status: Option<DeliveryStatus>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WebhookDeliveryResponse {
+    pub delivery_id: i32,
+    pub webhook_id: i32,
+    pub event_type: EventType,
+    pub payload: serde_json::Value,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+/// Which kind of record an `EntityChange` is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityKind {
+    Category,
+    Customer,
+    CustomerAddress,
+    Supplier,
+    Product,
+    Order,
+    Shipment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeAction {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// Sent as the data of each `change` event on `GET /events`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EntityChange {
+    #[serde(rename = "type")]
+    pub entity: EntityKind,
+    pub id: i32,
+    pub action: ChangeAction,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportParams {
+    /// Validate and report without saving anything.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Renames CSV headers to fields, as `Header:field` pairs separated by commas.
+    #[param(example = "E-mail:email,Phone number:phone")]
+    pub mapping: Option<String>,
+}
+
+/// Outcome of a CSV import. Row errors are reported as `rows[<line>].<field>`,
+/// counting the header as line 1.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub rows: u64,
+    pub created: u64,
+    pub updated: u64,
+    /// Columns that do not match any field and were skipped.
+    pub ignored_columns: Vec<String>,
+    pub errors: Vec<FieldError>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchMode {
+    /// Every operation succeeds or the batch fails at the first one that does not.
+    #[default]
+    Atomic,
+    /// Each operation succeeds or fails on its own and gets its own result.
+    PerItem,
+}
+
+/// One operation of a batch. `version` works like an `If-Match` header on a single request,
+/// so an update or delete without one fails with 428.
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOperation<C, U> {
+    Create { data: C },
+    Update { id: i32, version: Option<i32>, data: U },
+    Delete { id: i32, version: Option<i32> },
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BatchRequest<C, U> {
+    #[serde(default)]
+    pub mode: BatchMode,
+    /// Between 1 and 500 operations, applied in order.
+    pub operations: Vec<BatchOperation<C, U>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchResult {
+    /// Position of the operation in the request.
+    pub index: usize,
+    /// The status the operation would have had as a single request.
+    pub status: u16,
+    pub id: Option<i32>,
+    pub error: Option<ErrorResponse>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchResponse {
+    pub mode: BatchMode,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub results: Vec<BatchResult>,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditFilter {
+    pub entity: Option<AuditEntity>,
+    /// Id of the record, within `entity`.
+    pub id: Option<i32>,
+    /// Username of whoever made the changes.
+    pub actor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntryResponse {
+    pub audit_id: i64,
+    pub entity: AuditEntity,
+    pub entity_id: i32,
+    pub action: AuditAction,
+    pub actor: Option<String>,
+    pub request_id: Option<String>,
+    /// The changed fields, each as `{"old": .., "new": ..}`.
+    pub changes: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
@@ -1,6 +1,10 @@
-use actix_web::{HttpResponse, ResponseError};
-use sea_orm::DbErr;
+use actix_web::{HttpResponse, ResponseError, http::StatusCode};
+use sea_orm::{DbErr, RuntimeErr, SqlxPostgresError, sqlx};
+use serde::Serialize;
 use thiserror::Error;
+use utoipa::ToSchema;
+
+use crate::request_id;
 
 #[derive(Error, Debug)]
 pub enum AppError {
@@ -10,39 +14,317 @@ pub enum AppError {
     #[error("Not found")]
     NotFound,
     
-    #[error("Validation error: {0}")]
-    Validation(String),
+    #[error("Validation error: {message}")]
+    Validation {
+        message: String,
+        details: Vec<FieldError>,
+    },
+    
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    
+    /// A delete refused because the records in `dependents` still refer to this one.
+    #[error("Still referenced: {message}")]
+    StillReferenced {
+        message: String,
+        dependents: Vec<FieldError>,
+    },
+    
+    #[error("Unauthorized")]
+    Unauthorized,
+    
+    #[error("Forbidden")]
+    Forbidden,
+    
+    #[error("Precondition failed")]
+    PreconditionFailed,
+    
+    #[error("Precondition required")]
+    PreconditionRequired,
     
     #[error("Internal server error")]
     Internal,
+    
+    /// Failure of one operation of a batch, which failed the batch as a whole.
+    #[error("Operation {index} failed: {source}")]
+    Operation {
+        index: usize,
+        source: Box<AppError>,
+    },
+}
+
+impl AppError {
+    /// A validation failure that cannot be pinned on individual fields.
+    pub fn validation(message: impl Into<String>) -> Self {
+        AppError::Validation {
+            message: message.into(),
+            details: Vec::new(),
+        }
+    }
+
+    /// The input problems behind this error, or `None` if it is not the input's fault.
+    pub fn field_errors(&self) -> Option<Vec<FieldError>> {
+        let unattributed = |message: String| vec![FieldError { field: String::new(), message }];
+        match self {
+            AppError::Database(err) => ConstraintViolation::from_db_err(err).map(|violation| violation.into_response().details),
+            AppError::NotFound => Some(unattributed("refers to a record that does not exist".to_string())),
+            AppError::Validation { message, details } if details.is_empty() => Some(unattributed(message.clone())),
+            AppError::Validation { details, .. } => Some(details.clone()),
+            AppError::Conflict(msg) => Some(unattributed(msg.clone())),
+            AppError::StillReferenced { dependents, .. } => Some(dependents.clone()),
+            _ => None,
+        }
+    }
 }
 
 impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::Database(err) => match ConstraintViolation::from_db_err(err) {
+                Some(violation) => violation.status_code(),
+                None => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            AppError::NotFound => StatusCode::NOT_FOUND,
+            AppError::Validation { .. } => StatusCode::BAD_REQUEST,
+            AppError::Conflict(_) | AppError::StillReferenced { .. } => StatusCode::CONFLICT,
+            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden => StatusCode::FORBIDDEN,
+            AppError::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            AppError::PreconditionRequired => StatusCode::PRECONDITION_REQUIRED,
+            AppError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Operation { source, .. } => source.status_code(),
+        }
+    }
+
     fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(self.response_body())
+    }
+}
+
+impl AppError {
+    /// The body sent for this error.
+    pub fn response_body(&self) -> ErrorResponse {
         match self {
-            AppError::Database(err) => {
-                HttpResponse::InternalServerError().json(ErrorResponse::new(err.to_string()))
+            AppError::Database(err) => match ConstraintViolation::from_db_err(err) {
+                Some(violation) => violation.into_response(),
+                None => {
+                    eprintln!("[{}] {}", request_id::current().unwrap_or_default(), err);
+                    ErrorResponse::new("database_error", "Internal server error".to_string())
+                }
+            },
+            AppError::NotFound => ErrorResponse::new("not_found", "Resource not found".to_string()),
+            AppError::Validation { message, details } => {
+                ErrorResponse::new("validation_failed", message.to_string()).with_details(details.clone())
             }
-            AppError::NotFound => {
-                HttpResponse::NotFound().json(ErrorResponse::new("Resource not found".to_string()))
+            AppError::Conflict(msg) => ErrorResponse::new("conflict", msg.to_string()),
+            AppError::StillReferenced { message, dependents } => {
+                ErrorResponse::new("still_referenced", message.to_string()).with_details(dependents.clone())
             }
-            AppError::Validation(msg) => {
-                HttpResponse::BadRequest().json(ErrorResponse::new(msg.to_string()))
+            AppError::Unauthorized => {
+                ErrorResponse::new("unauthorized", "Authentication required".to_string())
             }
+            AppError::Forbidden => ErrorResponse::new(
+                "forbidden",
+                "You do not have permission to perform this action".to_string(),
+            ),
+            AppError::PreconditionFailed => ErrorResponse::new(
+                "precondition_failed",
+                "The resource has changed since it was read, fetch it again".to_string(),
+            ),
+            AppError::PreconditionRequired => ErrorResponse::new(
+                "precondition_required",
+                "Send If-Match with the ETag of the version this change is based on".to_string(),
+            ),
             AppError::Internal => {
-                HttpResponse::InternalServerError().json(ErrorResponse::new("Internal server error".to_string()))
+                ErrorResponse::new("internal_error", "Internal server error".to_string())
+            }
+            AppError::Operation { index, source } => {
+                let mut response = source.response_body();
+                let path = format!("operations[{}]", index);
+                if response.details.is_empty() {
+                    response.details.push(FieldError { field: path, message: response.error.clone() });
+                } else {
+                    for detail in &mut response.details {
+                        detail.field = match detail.field.as_str() {
+                            "" => path.clone(),
+                            field => format!("{}.{}", path, field),
+                        };
+                    }
+                }
+                response
             }
         }
     }
 }
 
-#[derive(serde::Serialize)]
+/// Body of every error response.
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ErrorResponse {
+    /// Stable, machine-readable error code such as `not_found` or `unique_violation`.
+    pub code: String,
+    /// Human-readable description of the error.
     pub error: String,
+    /// The fields that caused the error, when it can be attributed to specific fields.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<FieldError>,
+    /// Also sent as the `X-Request-Id` response header.
+    pub request_id: Option<String>,
 }
 
 impl ErrorResponse {
-    pub fn new(error: String) -> Self {
-        Self { error }
+    pub fn new(code: &str, error: String) -> Self {
+        Self {
+            code: code.to_string(),
+            error,
+            details: Vec::new(),
+            request_id: request_id::current(),
+        }
+    }
+
+    pub fn with_details(mut self, details: Vec<FieldError>) -> Self {
+        self.details = details;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+/// Most ids listed by `FieldError::dependents`.
+const MAX_LISTED_DEPENDENTS: usize = 20;
+
+impl FieldError {
+    /// Lists the `ids` of the `field` records that depend on the one being deleted.
+    pub fn dependents(field: &str, ids: &[i32]) -> Self {
+        let mut listed: Vec<String> = ids.iter().take(MAX_LISTED_DEPENDENTS).map(i32::to_string).collect();
+        if ids.len() > MAX_LISTED_DEPENDENTS {
+            listed.push(format!("and {} more", ids.len() - MAX_LISTED_DEPENDENTS));
+        }
+        FieldError {
+            field: field.to_string(),
+            message: format!("referenced by {} {}", field, listed.join(", ")),
+        }
+    }
+}
+
+/// A Postgres integrity constraint violation, attributed to the column that caused it.
+enum ConstraintViolation {
+    Unique { field: String },
+    MissingReference { field: String, table: String },
+    StillReferenced { field: String, referenced_by: String },
+    Check { field: String },
+    NotNull { field: String },
+}
+
+impl ConstraintViolation {
+    fn from_db_err(err: &DbErr) -> Option<Self> {
+        let (DbErr::Query(RuntimeErr::SqlxError(sqlx::Error::Database(err)))
+        | DbErr::Exec(RuntimeErr::SqlxError(sqlx::Error::Database(err)))) = err
+        else {
+            return None;
+        };
+        let err = err.try_downcast_ref::<SqlxPostgresError>()?;
+        
+        match err.code() {
+            "23505" => Some(Self::Unique {
+                field: key_column(err).unwrap_or_else(|| constraint_column(err)),
+            }),
+            "23503" => {
+                let detail = err.detail().unwrap_or_default();
+                match detail.split_once("is still referenced from table ") {
+                    Some((_, referenced_by)) => Some(Self::StillReferenced {
+                        field: key_column(err).unwrap_or_else(|| constraint_column(err)),
+                        referenced_by: unquote_table(referenced_by),
+                    }),
+                    None => Some(Self::MissingReference {
+                        field: key_column(err).unwrap_or_else(|| constraint_column(err)),
+                        table: detail
+                            .split_once("is not present in table ")
+                            .map(|(_, table)| unquote_table(table))
+                            .unwrap_or_default(),
+                    }),
+                }
+            }
+            "23514" => Some(Self::Check { field: constraint_column(err) }),
+            "23502" => Some(Self::NotNull {
+                field: err.column().unwrap_or_default().to_string(),
+            }),
+            _ => None,
+        }
     }
+
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Unique { .. } | Self::StillReferenced { .. } => StatusCode::CONFLICT,
+            Self::MissingReference { .. } | Self::Check { .. } | Self::NotNull { .. } => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+        }
+    }
+
+    fn into_response(self) -> ErrorResponse {
+        let (code, error, field, message) = match self {
+            Self::Unique { field } => (
+                "unique_violation",
+                format!("A record with this {} already exists", field),
+                field,
+                "must be unique".to_string(),
+            ),
+            Self::MissingReference { field, table } => (
+                "invalid_reference",
+                format!("{} does not refer to an existing record", field),
+                field,
+                format!("must refer to an existing row in {}", table),
+            ),
+            Self::StillReferenced { field, referenced_by } => (
+                "still_referenced",
+                format!("This record is still referenced from {}", referenced_by),
+                field,
+                format!("is still referenced from {}", referenced_by),
+            ),
+            Self::Check { field } => (
+                "check_violation",
+                format!("{} has an invalid value", field),
+                field,
+                "is not an allowed value".to_string(),
+            ),
+            Self::NotNull { field } => (
+                "missing_field",
+                format!("{} is required", field),
+                field,
+                "is required".to_string(),
+            ),
+        };
+        ErrorResponse::new(code, error).with_details(vec![FieldError { field, message }])
+    }
+}
+
+/// The column named in a `Key (column)=(value) ...` detail message.
+fn key_column(err: &SqlxPostgresError) -> Option<String> {
+    let detail = err.detail()?.strip_prefix("Key (")?;
+    let (columns, _) = detail.split_once(")=(")?;
+    Some(columns.to_string())
+}
+
+/// The column in a constraint named by Postgres' `<table>_<column>_<suffix>` convention.
+fn constraint_column(err: &SqlxPostgresError) -> String {
+    let constraint = err.constraint().unwrap_or_default();
+    let column = err
+        .table()
+        .and_then(|table| constraint.strip_prefix(table))
+        .and_then(|rest| rest.strip_prefix('_'))
+        .unwrap_or(constraint);
+    
+    ["_check", "_key", "_fkey"]
+        .iter()
+        .find_map(|suffix| column.strip_suffix(suffix))
+        .unwrap_or(column)
+        .to_string()
+}
+
+fn unquote_table(table: &str) -> String {
+    table.trim_end_matches('.').trim_matches('"').to_string()
 }
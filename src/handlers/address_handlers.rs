@@ -0,0 +1,153 @@
+use actix_web::{web, HttpResponse};
+
+use crate::{
+    dtos::*,
+    state::AppState,
+    services::*,
+    error::{AppError, ErrorResponse},
+    validation::ValidJson,
+    concurrency::{IfMatch, etag},
+    db::customer_addresses,
+};
+
+fn address_response(address: customer_addresses::Model) -> CustomerAddressResponse {
+    CustomerAddressResponse {
+        address_id: address.address_id,
+        customer_id: address.customer_id,
+        line1: address.line1,
+        line2: address.line2,
+        city: address.city,
+        region: address.region,
+        postal_code: address.postal_code,
+        country: address.country,
+        is_default_shipping: address.is_default_shipping,
+        is_default_billing: address.is_default_billing,
+    }
+}
+
+/// List a customer's addresses.
+#[utoipa::path(
+    get,
+    path = "/customers/{id}/addresses",
+    tag = "customers",
+    params(("id" = i32, Path, description = "Customer id")),
+    responses(
+        (status = 200, description = "The customer's addresses", body = Vec<CustomerAddressResponse>),
+        (status = 404, description = "Customer not found", body = ErrorResponse),
+    ),
+)]
+pub async fn get_customer_addresses(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let addresses = AddressService::find_all(&data.db, path.into_inner()).await?;
+    let response: Vec<CustomerAddressResponse> = addresses.into_iter().map(address_response).collect();
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Get one of a customer's addresses.
+#[utoipa::path(
+    get,
+    path = "/customers/{id}/addresses/{address_id}",
+    tag = "customers",
+    params(
+        ("id" = i32, Path, description = "Customer id"),
+        ("address_id" = i32, Path, description = "Address id"),
+    ),
+    responses(
+        (status = 200, description = "The address", body = CustomerAddressResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 404, description = "Customer or address not found", body = ErrorResponse),
+    ),
+)]
+pub async fn get_customer_address(
+    data: web::Data<AppState>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, AppError> {
+    let (customer_id, address_id) = path.into_inner();
+    let address = AddressService::find_by_id(&data.db, customer_id, address_id).await?;
+    Ok(HttpResponse::Ok().insert_header(etag(address.version)).json(address_response(address)))
+}
+
+/// Add an address to a customer's address book.
+#[utoipa::path(
+    post,
+    path = "/customers/{id}/addresses",
+    tag = "customers",
+    params(("id" = i32, Path, description = "Customer id")),
+    request_body = CustomerAddressCreate,
+    responses(
+        (status = 201, description = "The created address", body = CustomerAddressResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Customer not found", body = ErrorResponse),
+        (status = 422, description = "A value violates a database constraint", body = ErrorResponse),
+    ),
+)]
+pub async fn create_customer_address(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+    dto: ValidJson<CustomerAddressCreate>,
+) -> Result<HttpResponse, AppError> {
+    let address = AddressService::create(&data.db, path.into_inner(), dto.into_inner()).await?;
+    Ok(HttpResponse::Created().insert_header(etag(address.version)).json(address_response(address)))
+}
+
+/// Update one of a customer's addresses.
+///
+/// Making it the default shipping or billing address takes that flag off the address
+/// that had it. Orders already placed keep the address they were placed with.
+#[utoipa::path(
+    put,
+    path = "/customers/{id}/addresses/{address_id}",
+    tag = "customers",
+    params(
+        ("id" = i32, Path, description = "Customer id"),
+        ("address_id" = i32, Path, description = "Address id"),
+        ("If-Match" = String, Header, description = "ETag the change is based on"),
+    ),
+    request_body = CustomerAddressUpdate,
+    responses(
+        (status = 200, description = "The updated address", body = CustomerAddressResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Customer or address not found", body = ErrorResponse),
+        (status = 412, description = "The record changed since the given ETag", body = ErrorResponse),
+        (status = 428, description = "If-Match was not sent", body = ErrorResponse),
+        (status = 422, description = "A value violates a database constraint", body = ErrorResponse),
+    ),
+)]
+pub async fn update_customer_address(
+    data: web::Data<AppState>,
+    path: web::Path<(i32, i32)>,
+    if_match: IfMatch,
+    dto: ValidJson<CustomerAddressUpdate>,
+) -> Result<HttpResponse, AppError> {
+    let (customer_id, address_id) = path.into_inner();
+    let address = AddressService::update(&data.db, customer_id, address_id, &if_match, dto.into_inner()).await?;
+    Ok(HttpResponse::Ok().insert_header(etag(address.version)).json(address_response(address)))
+}
+
+/// Delete one of a customer's addresses.
+#[utoipa::path(
+    delete,
+    path = "/customers/{id}/addresses/{address_id}",
+    tag = "customers",
+    params(
+        ("id" = i32, Path, description = "Customer id"),
+        ("address_id" = i32, Path, description = "Address id"),
+        ("If-Match" = String, Header, description = "ETag the change is based on"),
+    ),
+    responses(
+        (status = 204, description = "Address deleted"),
+        (status = 404, description = "Customer or address not found", body = ErrorResponse),
+        (status = 412, description = "The record changed since the given ETag", body = ErrorResponse),
+        (status = 428, description = "If-Match was not sent", body = ErrorResponse),
+    ),
+)]
+pub async fn delete_customer_address(
+    data: web::Data<AppState>,
+    path: web::Path<(i32, i32)>,
+    if_match: IfMatch,
+) -> Result<HttpResponse, AppError> {
+    let (customer_id, address_id) = path.into_inner();
+    AddressService::delete(&data.db, customer_id, address_id, &if_match).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,48 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use crate::{
+    dtos::*,
+    state::AppState,
+    services::*,
+    error::{AppError, ErrorResponse},
+    pagination::{Page, PageParams},
+};
+
+/// Browse the audit log, newest first.
+///
+/// Every create, update and delete is logged with who made it and the fields it changed.
+#[utoipa::path(
+    get,
+    path = "/audit",
+    tag = "audit",
+    params(AuditFilter, PageParams),
+    responses(
+        (status = 200, description = "A page of audit entries", body = Page<AuditEntryResponse>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+    ),
+)]
+pub async fn get_audit_log(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    filter: web::Query<AuditFilter>,
+    page: web::Query<PageParams>,
+) -> Result<HttpResponse, AppError> {
+    let (entries, total) = AuditService::find_all(&data.db, &filter, &page).await?;
+    
+    let response: Vec<AuditEntryResponse> = entries
+        .into_iter()
+        .map(|entry| AuditEntryResponse {
+            audit_id: entry.audit_id,
+            entity: entry.entity,
+            entity_id: entry.entity_id,
+            action: entry.action,
+            actor: entry.actor,
+            request_id: entry.request_id,
+            changes: serde_json::from_str(&entry.changes)
+                .unwrap_or(serde_json::Value::String(entry.changes)),
+            created_at: entry.created_at,
+        })
+        .collect();
+    
+    Ok(HttpResponse::Ok().json(Page::new(response, total, &page, &req)))
+}
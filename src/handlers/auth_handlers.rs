@@ -0,0 +1,83 @@
+use actix_web::{web, HttpResponse};
+use chrono::Duration;
+
+use crate::{
+    auth::CurrentUser,
+    dtos::*,
+    state::AppState,
+    services::*,
+    error::{AppError, ErrorResponse},
+};
+
+/// Log in and open a session.
+#[utoipa::path(
+    post,
+    path = "/auth/login",
+    tag = "auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Session opened", body = LoginResponse),
+        (status = 401, description = "Invalid username or password", body = ErrorResponse),
+    ),
+    security(()),
+)]
+pub async fn login(
+    data: web::Data<AppState>,
+    dto: web::Json<LoginRequest>,
+) -> Result<HttpResponse, AppError> {
+    let ttl = Duration::hours(data.auth.session_ttl_hours);
+    let (token, session, user) = AuthService::login(&data.db, dto.into_inner(), ttl).await?;
+    let response = LoginResponse {
+        token,
+        expires_at: session.expires_at,
+        user: UserResponse {
+            user_id: user.user_id,
+            username: user.username,
+            role: user.role,
+            created_at: user.created_at,
+        },
+    };
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Close the current session.
+#[utoipa::path(
+    post,
+    path = "/auth/logout",
+    tag = "auth",
+    responses(
+        (status = 204, description = "Session closed"),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+    ),
+)]
+pub async fn logout(
+    data: web::Data<AppState>,
+    user: CurrentUser,
+) -> Result<HttpResponse, AppError> {
+    AuthService::logout(&data.db, &user.token).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Get the logged in user.
+#[utoipa::path(
+    get,
+    path = "/auth/me",
+    tag = "auth",
+    responses(
+        (status = 200, description = "The current user", body = UserResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+    ),
+)]
+pub async fn get_current_user(
+    data: web::Data<AppState>,
+    user: CurrentUser,
+) -> Result<HttpResponse, AppError> {
+    let user = UserService::find_by_id(&data.db, user.user_id).await?;
+    let response = UserResponse {
+        user_id: user.user_id,
+        username: user.username,
+        role: user.role,
+        created_at: user.created_at,
+    };
+    Ok(HttpResponse::Ok().json(response))
+}
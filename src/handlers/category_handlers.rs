@@ -1,71 +1,228 @@
-use sea_orm::EntityTrait;
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
 
 use crate::{
     dtos::*,
     state::AppState,
     services::*,
-    error::AppError,
+    error::{AppError, ErrorResponse},
+    validation::ValidJson,
+    concurrency::{IfMatch, etag},
+    pagination::{Page, PageParams},
+    auth::IncludeDeleted,
 };
 
-pub async fn get_categories(data: web::Data<AppState>) -> Result<HttpResponse, AppError> {
-    let categories = CategoryService::find_all(&data.db).await?;
+/// List categories.
+#[utoipa::path(
+    get,
+    path = "/categories",
+    tag = "categories",
+    params(PageParams, IncludeDeleted),
+    responses(
+        (status = 200, description = "A page of categories", body = Page<CategoryResponse>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Only admins may include deleted categories", body = ErrorResponse),
+    ),
+)]
+pub async fn get_categories(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    page: web::Query<PageParams>,
+    deleted: IncludeDeleted,
+) -> Result<HttpResponse, AppError> {
+    let (categories, total) = CategoryService::find_all(&data.db, &page, deleted.include_deleted).await?;
     let response: Vec<CategoryResponse> = categories.into_iter().map(|c| CategoryResponse {
         category_id: c.category_id,
         name: c.name,
         description: c.description,
+        deleted_at: c.deleted_at,
     }).collect();
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().json(Page::new(response, total, &page, &req)))
 }
 
+/// Get a category.
+#[utoipa::path(
+    get,
+    path = "/categories/{id}",
+    tag = "categories",
+    params(("id" = i32, Path, description = "Category id"), IncludeDeleted),
+    responses(
+        (status = 200, description = "The category", body = CategoryResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 403, description = "Only admins may include deleted categories", body = ErrorResponse),
+        (status = 404, description = "Category not found", body = ErrorResponse),
+    ),
+)]
 pub async fn get_category(
     data: web::Data<AppState>,
     path: web::Path<i32>,
+    deleted: IncludeDeleted,
 ) -> Result<HttpResponse, AppError> {
     let category_id = path.into_inner();
-    let category = CategoryService::find_by_id(&data.db, category_id).await?;
+    let category = CategoryService::find_by_id(&data.db, category_id, deleted.include_deleted).await?;
     let response = CategoryResponse {
         category_id: category.category_id,
         name: category.name,
         description: category.description,
+        deleted_at: category.deleted_at,
     };
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().insert_header(etag(category.version)).json(response))
 }
 
+/// Create a category.
+#[utoipa::path(
+    post,
+    path = "/categories",
+    tag = "categories",
+    request_body = CategoryCreate,
+    responses(
+        (status = 201, description = "The created category", body = CategoryResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 409, description = "A record with the same unique value exists", body = ErrorResponse),
+        (status = 422, description = "A value violates a database constraint", body = ErrorResponse),
+    ),
+)]
 pub async fn create_category(
     data: web::Data<AppState>,
-    dto: web::Json<CategoryCreate>,
+    dto: ValidJson<CategoryCreate>,
 ) -> Result<HttpResponse, AppError> {
     let category = CategoryService::create(&data.db, dto.into_inner()).await?;
     let response = CategoryResponse {
         category_id: category.category_id,
         name: category.name,
         description: category.description,
+        deleted_at: category.deleted_at,
     };
-    Ok(HttpResponse::Created().json(response))
+    Ok(HttpResponse::Created().insert_header(etag(category.version)).json(response))
 }
 
+/// Update a category.
+#[utoipa::path(
+    put,
+    path = "/categories/{id}",
+    tag = "categories",
+    params(
+        ("id" = i32, Path, description = "Category id"),
+        ("If-Match" = String, Header, description = "ETag the change is based on"),
+    ),
+    request_body = CategoryUpdate,
+    responses(
+        (status = 200, description = "The updated category", body = CategoryResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Category not found", body = ErrorResponse),
+        (status = 409, description = "A record with the same unique value exists", body = ErrorResponse),
+        (status = 422, description = "A value violates a database constraint", body = ErrorResponse),
+        (status = 412, description = "The record changed since the given ETag", body = ErrorResponse),
+        (status = 428, description = "If-Match was not sent", body = ErrorResponse),
+    ),
+)]
 pub async fn update_category(
     data: web::Data<AppState>,
     path: web::Path<i32>,
-    dto: web::Json<CategoryUpdate>,
+    if_match: IfMatch,
+    dto: ValidJson<CategoryUpdate>,
 ) -> Result<HttpResponse, AppError> {
     let category_id = path.into_inner();
-    let category = CategoryService::update(&data.db, category_id, dto.into_inner()).await?;
+    let category = CategoryService::update(&data.db, category_id, &if_match, dto.into_inner()).await?;
     let response = CategoryResponse {
         category_id: category.category_id,
         name: category.name,
         description: category.description,
+        deleted_at: category.deleted_at,
     };
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().insert_header(etag(category.version)).json(response))
 }
 
+/// Delete a category.
+#[utoipa::path(
+    delete,
+    path = "/categories/{id}",
+    tag = "categories",
+    params(
+        ("id" = i32, Path, description = "Category id"),
+        ("If-Match" = String, Header, description = "ETag the change is based on"),
+    ),
+    responses(
+        (status = 204, description = "Category deleted"),
+        (status = 404, description = "Category not found", body = ErrorResponse),
+        (status = 412, description = "The record changed since the given ETag", body = ErrorResponse),
+        (status = 428, description = "If-Match was not sent", body = ErrorResponse),
+    ),
+)]
 pub async fn delete_category(
     data: web::Data<AppState>,
     path: web::Path<i32>,
+    if_match: IfMatch,
 ) -> Result<HttpResponse, AppError> {
     let category_id = path.into_inner();
-    CategoryService::delete(&data.db, category_id).await?;
+    CategoryService::delete(&data.db, category_id, &if_match).await?;
     Ok(HttpResponse::NoContent().finish())
 }
 
+/// Restore a deleted category.
+#[utoipa::path(
+    post,
+    path = "/categories/{id}/restore",
+    tag = "categories",
+    params(
+        ("id" = i32, Path, description = "Category id"),
+        ("If-Match" = Option<String>, Header, description = "ETag the change is based on"),
+    ),
+    responses(
+        (status = 200, description = "The restored category", body = CategoryResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 404, description = "Category not found", body = ErrorResponse),
+        (status = 409, description = "The category is not deleted, or a record with the same unique value exists", body = ErrorResponse),
+        (status = 412, description = "The record changed since the given ETag", body = ErrorResponse),
+    ),
+)]
+pub async fn restore_category(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+    if_match: IfMatch,
+) -> Result<HttpResponse, AppError> {
+    let category_id = path.into_inner();
+    let category = CategoryService::restore(&data.db, category_id, &if_match).await?;
+    let response = CategoryResponse {
+        category_id: category.category_id,
+        name: category.name,
+        description: category.description,
+        deleted_at: category.deleted_at,
+    };
+    Ok(HttpResponse::Ok().insert_header(etag(category.version)).json(response))
+}
+
+/// Create, update and delete categories in one request.
+///
+/// Operations run in order in a single transaction. In `atomic` mode the first failure
+/// undoes the batch and is reported with its `operations[<index>]`; in `per_item` mode
+/// every operation gets its own result and only the failed ones are undone.
+#[utoipa::path(
+    post,
+    path = "/categories/batch",
+    tag = "categories",
+    request_body = BatchRequest<CategoryCreate, CategoryUpdate>,
+    responses(
+        (status = 200, description = "The result of each operation", body = BatchResponse),
+        (status = 400, description = "Invalid request, or in atomic mode an invalid operation", body = ErrorResponse),
+        (status = 404, description = "In atomic mode, an operation on a missing category", body = ErrorResponse),
+        (status = 409, description = "In atomic mode, an operation that conflicts with other records", body = ErrorResponse),
+        (status = 412, description = "In atomic mode, an operation whose version is out of date", body = ErrorResponse),
+        (status = 428, description = "In atomic mode, an update or delete without a version", body = ErrorResponse),
+        (status = 422, description = "In atomic mode, an operation that violates a database constraint", body = ErrorResponse),
+    ),
+)]
+pub async fn batch_categories(
+    data: web::Data<AppState>,
+    body: web::Json<BatchRequest<CategoryCreate, CategoryUpdate>>,
+) -> Result<HttpResponse, AppError> {
+    let response = BatchService::run(&data.db, body.into_inner(), async |txn, operation| match operation {
+        BatchOperation::Create { data } => Ok(CategoryService::create(txn, data).await?.category_id),
+        BatchOperation::Update { id, version, data } => {
+            CategoryService::update(txn, id, &IfMatch::version(version)?, data).await?;
+            Ok(id)
+        }
+        BatchOperation::Delete { id, version } => {
+            CategoryService::delete(txn, id, &IfMatch::version(version)?).await?;
+            Ok(id)
+        }
+    }).await?;
+    Ok(HttpResponse::Ok().json(response))
+}
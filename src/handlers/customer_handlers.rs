@@ -1,15 +1,38 @@
-use sea_orm::EntityTrait;
-use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use sea_orm::ModelTrait;
+use actix_web::{web, HttpRequest, HttpResponse, http::header::ContentDisposition};
 
 use crate::{
     dtos::*,
     state::AppState,
     services::*,
-    error::AppError,
+    error::{AppError, ErrorResponse},
+    validation::ValidJson,
+    concurrency::{IfMatch, etag},
+    pagination::{Page, PageParams},
+    auth::IncludeDeleted,
+    db::CustomerAddresses,
 };
 
-pub async fn get_customers(data: web::Data<AppState>) -> Result<HttpResponse, AppError> {
-    let customers = CustomerService::find_all(&data.db).await?;
+/// List customers.
+#[utoipa::path(
+    get,
+    path = "/customers",
+    tag = "customers",
+    params(PageParams, IncludeDeleted),
+    responses(
+        (status = 200, description = "A page of customers", body = Page<CustomerResponse>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Only admins may include deleted customers", body = ErrorResponse),
+    ),
+)]
+pub async fn get_customers(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    page: web::Query<PageParams>,
+    deleted: IncludeDeleted,
+) -> Result<HttpResponse, AppError> {
+    let (customers, total) = CustomerService::find_all(&data.db, &page, deleted.include_deleted).await?;
     let response: Vec<CustomerResponse> = customers.into_iter().map(|c| CustomerResponse {
         customer_id: c.customer_id,
         first_name: c.first_name,
@@ -18,16 +41,30 @@ pub async fn get_customers(data: web::Data<AppState>) -> Result<HttpResponse, Ap
         phone: c.phone,
         registration_date: c.registration_date,
         address: c.address,
+        deleted_at: c.deleted_at,
     }).collect();
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().json(Page::new(response, total, &page, &req)))
 }
 
+/// Get a customer.
+#[utoipa::path(
+    get,
+    path = "/customers/{id}",
+    tag = "customers",
+    params(("id" = i32, Path, description = "Customer id"), IncludeDeleted),
+    responses(
+        (status = 200, description = "The customer", body = CustomerResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 403, description = "Only admins may include deleted customers", body = ErrorResponse),
+        (status = 404, description = "Customer not found", body = ErrorResponse),
+    ),
+)]
 pub async fn get_customer(
     data: web::Data<AppState>,
     path: web::Path<i32>,
+    deleted: IncludeDeleted,
 ) -> Result<HttpResponse, AppError> {
     let customer_id = path.into_inner();
-    let customer = CustomerService::find_by_id(&data.db, customer_id).await?;
+    let customer = CustomerService::find_by_id(&data.db, customer_id, deleted.include_deleted).await?;
     let response = CustomerResponse {
         customer_id: customer.customer_id,
         first_name: customer.first_name,
@@ -36,13 +73,27 @@ pub async fn get_customer(
         phone: customer.phone,
         registration_date: customer.registration_date,
         address: customer.address,
+        deleted_at: customer.deleted_at,
     };
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().insert_header(etag(customer.version)).json(response))
 }
 
+/// Create a customer.
+#[utoipa::path(
+    post,
+    path = "/customers",
+    tag = "customers",
+    request_body = CustomerCreate,
+    responses(
+        (status = 201, description = "The created customer", body = CustomerResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 409, description = "A record with the same unique value exists", body = ErrorResponse),
+        (status = 422, description = "A value violates a database constraint", body = ErrorResponse),
+    ),
+)]
 pub async fn create_customer(
     data: web::Data<AppState>,
-    dto: web::Json<CustomerCreate>,
+    dto: ValidJson<CustomerCreate>,
 ) -> Result<HttpResponse, AppError> {
     let customer = CustomerService::create(&data.db, dto.into_inner()).await?;
     let response = CustomerResponse {
@@ -53,17 +104,39 @@ pub async fn create_customer(
         phone: customer.phone,
         registration_date: customer.registration_date,
         address: customer.address,
+        deleted_at: customer.deleted_at,
     };
-    Ok(HttpResponse::Created().json(response))
+    Ok(HttpResponse::Created().insert_header(etag(customer.version)).json(response))
 }
 
+/// Update a customer.
+#[utoipa::path(
+    put,
+    path = "/customers/{id}",
+    tag = "customers",
+    params(
+        ("id" = i32, Path, description = "Customer id"),
+        ("If-Match" = String, Header, description = "ETag the change is based on"),
+    ),
+    request_body = CustomerUpdate,
+    responses(
+        (status = 200, description = "The updated customer", body = CustomerResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Customer not found", body = ErrorResponse),
+        (status = 409, description = "A record with the same unique value exists", body = ErrorResponse),
+        (status = 422, description = "A value violates a database constraint", body = ErrorResponse),
+        (status = 412, description = "The record changed since the given ETag", body = ErrorResponse),
+        (status = 428, description = "If-Match was not sent", body = ErrorResponse),
+    ),
+)]
 pub async fn update_customer(
     data: web::Data<AppState>,
     path: web::Path<i32>,
-    dto: web::Json<CustomerUpdate>,
+    if_match: IfMatch,
+    dto: ValidJson<CustomerUpdate>,
 ) -> Result<HttpResponse, AppError> {
     let customer_id = path.into_inner();
-    let customer = CustomerService::update(&data.db, customer_id, dto.into_inner()).await?;
+    let customer = CustomerService::update(&data.db, customer_id, &if_match, dto.into_inner()).await?;
     let response = CustomerResponse {
         customer_id: customer.customer_id,
         first_name: customer.first_name,
@@ -72,19 +145,90 @@ pub async fn update_customer(
         phone: customer.phone,
         registration_date: customer.registration_date,
         address: customer.address,
+        deleted_at: customer.deleted_at,
     };
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().insert_header(etag(customer.version)).json(response))
 }
 
+/// Delete a customer.
+///
+/// A customer that still has orders is refused with 409 listing them, unless `archive`
+/// is set, which deletes the customer and keeps them as they are.
+#[utoipa::path(
+    delete,
+    path = "/customers/{id}",
+    tag = "customers",
+    params(
+        ("id" = i32, Path, description = "Customer id"),
+        ("If-Match" = String, Header, description = "ETag the change is based on"),
+        DeleteParams,
+    ),
+    responses(
+        (status = 204, description = "Customer deleted"),
+        (status = 404, description = "Customer not found", body = ErrorResponse),
+        (status = 409, description = "Other records still refer to it", body = ErrorResponse),
+        (status = 412, description = "The record changed since the given ETag", body = ErrorResponse),
+        (status = 428, description = "If-Match was not sent", body = ErrorResponse),
+    ),
+)]
 pub async fn delete_customer(
     data: web::Data<AppState>,
     path: web::Path<i32>,
+    if_match: IfMatch,
+    params: web::Query<DeleteParams>,
 ) -> Result<HttpResponse, AppError> {
     let customer_id = path.into_inner();
-    CustomerService::delete(&data.db, customer_id).await?;
+    CustomerService::delete(&data.db, customer_id, &if_match, params.archive).await?;
     Ok(HttpResponse::NoContent().finish())
 }
 
+/// Restore a deleted customer.
+#[utoipa::path(
+    post,
+    path = "/customers/{id}/restore",
+    tag = "customers",
+    params(
+        ("id" = i32, Path, description = "Customer id"),
+        ("If-Match" = Option<String>, Header, description = "ETag the change is based on"),
+    ),
+    responses(
+        (status = 200, description = "The restored customer", body = CustomerResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 404, description = "Customer not found", body = ErrorResponse),
+        (status = 409, description = "The customer is not deleted, or a record with the same unique value exists", body = ErrorResponse),
+        (status = 412, description = "The record changed since the given ETag", body = ErrorResponse),
+    ),
+)]
+pub async fn restore_customer(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+    if_match: IfMatch,
+) -> Result<HttpResponse, AppError> {
+    let customer_id = path.into_inner();
+    let customer = CustomerService::restore(&data.db, customer_id, &if_match).await?;
+    let response = CustomerResponse {
+        customer_id: customer.customer_id,
+        first_name: customer.first_name,
+        last_name: customer.last_name,
+        email: customer.email,
+        phone: customer.phone,
+        registration_date: customer.registration_date,
+        address: customer.address,
+        deleted_at: customer.deleted_at,
+    };
+    Ok(HttpResponse::Ok().insert_header(etag(customer.version)).json(response))
+}
+
+/// Get a customer with their orders.
+#[utoipa::path(
+    get,
+    path = "/customers/{id}/orders",
+    tag = "customers",
+    params(("id" = i32, Path, description = "Customer id")),
+    responses(
+        (status = 200, description = "The customer and their orders", body = CustomerOrdersResponse),
+        (status = 404, description = "Customer not found", body = ErrorResponse),
+    ),
+)]
 pub async fn get_customer_orders(
     data: web::Data<AppState>,
     path: web::Path<i32>,
@@ -100,6 +244,7 @@ pub async fn get_customer_orders(
         phone: customer.phone,
         registration_date: customer.registration_date,
         address: customer.address,
+        deleted_at: customer.deleted_at,
     };
     
     let orders_response: Vec<OrderResponse> = orders.into_iter().map(|o| OrderResponse {
@@ -112,9 +257,204 @@ pub async fn get_customer_orders(
         customer_name: Some(format!("{} {}", customer.first_name, customer.last_name)),
     }).collect();
     
-    Ok(HttpResponse::Ok().json(serde_json::json!({
-        "customer": customer_response,
-        "orders": orders_response
-    })))
+    let response = CustomerOrdersResponse {
+        customer: customer_response,
+        orders: orders_response,
+    };
+    Ok(HttpResponse::Ok().json(response))
 }
 
+/// Export everything stored about a customer.
+///
+/// Returns the customer with all of their orders and order items as one JSON document.
+#[utoipa::path(
+    get,
+    path = "/customers/{id}/export",
+    tag = "customers",
+    params(("id" = i32, Path, description = "Customer id"), IncludeDeleted),
+    responses(
+        (status = 200, description = "The customer's data", body = CustomerExport),
+        (status = 403, description = "Only admins may include deleted customers", body = ErrorResponse),
+        (status = 404, description = "Customer not found", body = ErrorResponse),
+    ),
+)]
+pub async fn export_customer(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+    deleted: IncludeDeleted,
+) -> Result<HttpResponse, AppError> {
+    let customer_id = path.into_inner();
+    let (customer, orders) = CustomerService::find_for_export(&data.db, customer_id, deleted.include_deleted).await?;
+    let customer_name = format!("{} {}", customer.first_name, customer.last_name);
+    let addresses = customer.find_related(CustomerAddresses).all(&data.db).await?;
+    
+    let orders = orders.into_iter().map(|(order, items)| {
+        let items: Vec<OrderItemResponse> = items.into_iter().map(|(item, product)| OrderItemResponse {
+            order_id: item.order_id,
+            product_id: item.product_id,
+            quantity: item.quantity,
+            unit_price: item.unit_price,
+            line_total: item.line_total(),
+            product_name: product.map(|p| p.name),
+        }).collect();
+        OrderDetailsResponse {
+            order_id: order.order_id,
+            customer_id: order.customer_id,
+            order_date: order.order_date,
+            status: order.status,
+            total_amount: order.total_amount,
+            shipping_address: order.shipping_address,
+            customer_name: Some(customer_name.clone()),
+            subtotal: items.iter().map(|item| item.line_total).sum(),
+            items,
+        }
+    }).collect();
+    
+    let response = CustomerExport {
+        exported_at: Utc::now(),
+        customer: CustomerResponse {
+            customer_id: customer.customer_id,
+            first_name: customer.first_name,
+            last_name: customer.last_name,
+            email: customer.email,
+            phone: customer.phone,
+            registration_date: customer.registration_date,
+            address: customer.address,
+            deleted_at: customer.deleted_at,
+        },
+        addresses: addresses.into_iter().map(|a| CustomerAddressResponse {
+            address_id: a.address_id,
+            customer_id: a.customer_id,
+            line1: a.line1,
+            line2: a.line2,
+            city: a.city,
+            region: a.region,
+            postal_code: a.postal_code,
+            country: a.country,
+            is_default_shipping: a.is_default_shipping,
+            is_default_billing: a.is_default_billing,
+        }).collect(),
+        orders,
+    };
+    Ok(HttpResponse::Ok()
+        .insert_header(ContentDisposition::attachment(format!("customer-{}.json", customer_id)))
+        .json(response))
+}
+
+/// Anonymize a customer.
+///
+/// Replaces the customer's name, email, phone and address and the shipping address of
+/// each of their orders, including the copies kept in the audit log and in events. Order
+/// statuses, items and amounts are kept. This cannot be undone.
+#[utoipa::path(
+    post,
+    path = "/customers/{id}/anonymize",
+    tag = "customers",
+    params(
+        ("id" = i32, Path, description = "Customer id"),
+        ("If-Match" = Option<String>, Header, description = "ETag the change is based on"),
+    ),
+    responses(
+        (status = 200, description = "The anonymized customer", body = CustomerResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 404, description = "Customer not found", body = ErrorResponse),
+        (status = 412, description = "The record changed since the given ETag", body = ErrorResponse),
+    ),
+)]
+pub async fn anonymize_customer(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+    if_match: IfMatch,
+) -> Result<HttpResponse, AppError> {
+    let customer_id = path.into_inner();
+    let customer = CustomerService::anonymize(&data.db, customer_id, &if_match).await?;
+    let response = CustomerResponse {
+        customer_id: customer.customer_id,
+        first_name: customer.first_name,
+        last_name: customer.last_name,
+        email: customer.email,
+        phone: customer.phone,
+        registration_date: customer.registration_date,
+        address: customer.address,
+        deleted_at: customer.deleted_at,
+    };
+    Ok(HttpResponse::Ok().insert_header(etag(customer.version)).json(response))
+}
+
+/// Import customers from CSV.
+///
+/// Customers are matched on `email`. Matching rows are
+/// updated with the non-empty cells if their `version` is still the exported one, the rest
+/// are created. Either every row is saved or, if any row fails, none are.
+#[utoipa::path(
+    post,
+    path = "/customers/import",
+    tag = "customers",
+    params(ImportParams),
+    request_body(content = String, content_type = "text/csv", description = "A header row followed by one customer per row"),
+    responses(
+        (status = 200, description = "What was imported, or on a dry run what would be", body = ImportReport),
+        (status = 400, description = "Unreadable CSV, or the errors of each failed row", body = ErrorResponse),
+    ),
+)]
+pub async fn import_customers(
+    data: web::Data<AppState>,
+    params: web::Query<ImportParams>,
+    body: web::Bytes,
+) -> Result<HttpResponse, AppError> {
+    let report = CsvService::import_customers(&data.db, &body, &params).await?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Export all customers as CSV, in the columns the import reads.
+#[utoipa::path(
+    get,
+    path = "/customers/export.csv",
+    tag = "customers",
+    responses(
+        (status = 200, description = "Every customer", content_type = "text/csv", body = String),
+    ),
+)]
+pub async fn export_customers(data: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header(ContentDisposition::attachment("customers.csv"))
+        .streaming(CsvService::export_customers(data.db.clone()))
+}
+
+/// Create, update and delete customers in one request.
+///
+/// Operations run in order in a single transaction. In `atomic` mode the first failure
+/// undoes the batch and is reported with its `operations[<index>]`; in `per_item` mode
+/// every operation gets its own result and only the failed ones are undone.
+#[utoipa::path(
+    post,
+    path = "/customers/batch",
+    tag = "customers",
+    request_body = BatchRequest<CustomerCreate, CustomerUpdate>,
+    responses(
+        (status = 200, description = "The result of each operation", body = BatchResponse),
+        (status = 400, description = "Invalid request, or in atomic mode an invalid operation", body = ErrorResponse),
+        (status = 404, description = "In atomic mode, an operation on a missing customer", body = ErrorResponse),
+        (status = 409, description = "In atomic mode, an operation that conflicts with other records", body = ErrorResponse),
+        (status = 412, description = "In atomic mode, an operation whose version is out of date", body = ErrorResponse),
+        (status = 428, description = "In atomic mode, an update or delete without a version", body = ErrorResponse),
+        (status = 422, description = "In atomic mode, an operation that violates a database constraint", body = ErrorResponse),
+    ),
+)]
+pub async fn batch_customers(
+    data: web::Data<AppState>,
+    body: web::Json<BatchRequest<CustomerCreate, CustomerUpdate>>,
+) -> Result<HttpResponse, AppError> {
+    let response = BatchService::run(&data.db, body.into_inner(), async |txn, operation| match operation {
+        BatchOperation::Create { data } => Ok(CustomerService::create(txn, data).await?.customer_id),
+        BatchOperation::Update { id, version, data } => {
+            CustomerService::update(txn, id, &IfMatch::version(version)?, data).await?;
+            Ok(id)
+        }
+        BatchOperation::Delete { id, version } => {
+            CustomerService::delete(txn, id, &IfMatch::version(version)?, false).await?;
+            Ok(id)
+        }
+    }).await?;
+    Ok(HttpResponse::Ok().json(response))
+}
@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use actix_web::{HttpResponse, http::header, web};
+use futures_util::stream;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{
+    auth::CurrentUser,
+    error::ErrorResponse,
+    state::AppState,
+};
+
+/// Idle time after which a comment is sent so proxies keep the connection open.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// Stream committed changes as Server-Sent Events.
+///
+/// Each `change` event carries an `EntityChange` as its data. A `resync` event means the
+/// client fell too far behind to be told about every change and should reload its views.
+#[utoipa::path(
+    get,
+    path = "/events",
+    tag = "events",
+    responses(
+        (status = 200, description = "An endless `text/event-stream` of `change` and `resync` events", content_type = "text/event-stream", body = String),
+        (status = 401, description = "Not signed in", body = ErrorResponse),
+    ),
+)]
+pub async fn stream_events(data: web::Data<AppState>, _user: CurrentUser) -> HttpResponse {
+    let receiver = data.changes.subscribe();
+    let frames = stream::unfold(receiver, |mut receiver| async move {
+        let frame = match actix_web::rt::time::timeout(KEEP_ALIVE, receiver.recv()).await {
+            Err(_) => ": keep-alive\n\n".to_string(),
+            Ok(Ok(change)) => format!(
+                "event: change\ndata: {}\n\n",
+                serde_json::to_string(&change).unwrap_or_default()
+            ),
+            Ok(Err(RecvError::Lagged(_))) => "event: resync\ndata: {}\n\n".to_string(),
+            Ok(Err(RecvError::Closed)) => return None,
+        };
+        Some((Ok::<_, actix_web::Error>(web::Bytes::from(frame)), receiver))
+    });
+    let opening = stream::once(async { Ok(web::Bytes::from_static(b"retry: 3000\n\n")) });
+    
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(futures_util::StreamExt::chain(opening, frames))
+}
@@ -0,0 +1,59 @@
+use actix_web::{web, HttpResponse};
+
+use crate::{
+    dtos::*,
+    state::AppState,
+    services::*,
+    error::{AppError, ErrorResponse},
+};
+
+/// List a product's stock movements, newest first.
+#[utoipa::path(
+    get,
+    path = "/products/{id}/movements",
+    tag = "inventory",
+    params(("id" = i32, Path, description = "Product id")),
+    responses(
+        (status = 200, description = "The product's stock ledger", body = Vec<InventoryMovementResponse>),
+        (status = 404, description = "Product not found", body = ErrorResponse),
+    ),
+)]
+pub async fn get_product_movements(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let product_id = path.into_inner();
+    ProductService::find_by_id(&data.db, product_id, false).await?;
+    
+    let movements = InventoryService::find_movements(&data.db, product_id).await?;
+    let response: Vec<InventoryMovementResponse> = movements.into_iter().map(|m| InventoryMovementResponse {
+        movement_id: m.movement_id,
+        product_id: m.product_id,
+        delta: m.delta,
+        reason: m.reason,
+        order_id: m.order_id,
+        shipment_id: m.shipment_id,
+        actor: m.actor,
+        created_at: m.created_at,
+    }).collect();
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// List products whose stock differs from their ledger.
+#[utoipa::path(
+    get,
+    path = "/inventory/reconciliation",
+    tag = "inventory",
+    responses(
+        (status = 200, description = "Products out of balance", body = Vec<StockDiscrepancyResponse>),
+    ),
+)]
+pub async fn get_inventory_reconciliation(data: web::Data<AppState>) -> Result<HttpResponse, AppError> {
+    let discrepancies = InventoryService::reconcile(&data.db).await?;
+    let response: Vec<StockDiscrepancyResponse> = discrepancies.into_iter().map(|d| StockDiscrepancyResponse {
+        product_id: d.product_id,
+        stock_quantity: d.stock_quantity,
+        ledger_quantity: d.ledger_quantity,
+    }).collect();
+    Ok(HttpResponse::Ok().json(response))
+}
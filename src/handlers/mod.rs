@@ -1,14 +1,28 @@
+pub mod address_handlers;
+pub mod audit_handlers;
+pub mod auth_handlers;
 pub mod category_handlers;
 pub mod customer_handlers;
+pub mod event_handlers;
+pub mod inventory_handlers;
 pub mod order_handlers;
 pub mod product_handlers;
 pub mod shipment_handlers;
 pub mod supplier_handlers;
+pub mod user_handlers;
+pub mod webhook_handlers;
 
+pub use address_handlers::*;
+pub use audit_handlers::*;
+pub use auth_handlers::*;
 pub use category_handlers::*;
 pub use customer_handlers::*;
+pub use event_handlers::*;
+pub use inventory_handlers::*;
 pub use order_handlers::*;
 pub use product_handlers::*;
 pub use shipment_handlers::*;
 pub use supplier_handlers::*;
+pub use user_handlers::*;
+pub use webhook_handlers::*;
 
@@ -1,23 +1,41 @@
 use sea_orm::EntityTrait;
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
 
 use crate::{
     dtos::*,
     state::AppState,
     services::*,
-    error::AppError,
+    error::{AppError, ErrorResponse},
+    validation::ValidJson,
+    concurrency::{IfMatch, etag},
+    pagination::{Page, PageParams},
     db::*,
+    db::orders::OrderStatus,
 };
 
-pub async fn get_orders(data: web::Data<AppState>) -> Result<HttpResponse, AppError> {
-    let orders = OrderService::find_all(&data.db).await?;
+/// List orders.
+#[utoipa::path(
+    get,
+    path = "/orders",
+    tag = "orders",
+    params(OrderFilter, PageParams),
+    responses(
+        (status = 200, description = "A page of orders", body = Page<OrderResponse>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+    ),
+)]
+pub async fn get_orders(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    filter: web::Query<OrderFilter>,
+    page: web::Query<PageParams>,
+) -> Result<HttpResponse, AppError> {
+    let (orders, total) = OrderService::find_all(&data.db, &filter, &page).await?;
+    let customers = OrderService::load_customers(&data.db, &orders).await?;
     
     let mut orders_with_additional_information = Vec::new();
-    for order in orders {
-        let customer = Customers::find_by_id(order.customer_id)
-            .one(&data.db)
-            .await?
-            .unwrap_or_default();
+    for (order, customer) in orders.into_iter().zip(customers) {
+        let customer = customer.unwrap_or_default();
         
         let response = OrderResponse {
             order_id: order.order_id,
@@ -31,9 +49,20 @@ pub async fn get_orders(data: web::Data<AppState>) -> Result<HttpResponse, AppEr
         orders_with_additional_information.push(response);
     }
     
-    Ok(HttpResponse::Ok().json(orders_with_additional_information))
+    Ok(HttpResponse::Ok().json(Page::new(orders_with_additional_information, total, &page, &req)))
 }
 
+/// Get a order.
+#[utoipa::path(
+    get,
+    path = "/orders/{id}",
+    tag = "orders",
+    params(("id" = i32, Path, description = "Order id")),
+    responses(
+        (status = 200, description = "The order", body = OrderResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 404, description = "Order not found", body = ErrorResponse),
+    ),
+)]
 pub async fn get_order(
     data: web::Data<AppState>,
     path: web::Path<i32>,
@@ -56,12 +85,24 @@ pub async fn get_order(
         customer_name: Some(format!("{} {}", customer.first_name, customer.last_name)),
     };
     
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().insert_header(etag(order.version)).json(response))
 }
 
+/// Create a order.
+#[utoipa::path(
+    post,
+    path = "/orders",
+    tag = "orders",
+    request_body = OrderCreate,
+    responses(
+        (status = 201, description = "The created order", body = OrderResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 422, description = "A value violates a database constraint", body = ErrorResponse),
+    ),
+)]
 pub async fn create_order(
     data: web::Data<AppState>,
-    dto: web::Json<OrderCreate>,
+    dto: ValidJson<OrderCreate>,
 ) -> Result<HttpResponse, AppError> {
     let order = OrderService::create(&data.db, dto.into_inner()).await?;
     
@@ -80,16 +121,37 @@ pub async fn create_order(
         customer_name: Some(format!("{} {}", customer.first_name, customer.last_name)),
     };
     
-    Ok(HttpResponse::Created().json(response))
+    Ok(HttpResponse::Created().insert_header(etag(order.version)).json(response))
 }
 
+/// Update a order.
+#[utoipa::path(
+    put,
+    path = "/orders/{id}",
+    tag = "orders",
+    params(
+        ("id" = i32, Path, description = "Order id"),
+        ("If-Match" = String, Header, description = "ETag the change is based on"),
+    ),
+    request_body = OrderUpdate,
+    responses(
+        (status = 200, description = "The updated order", body = OrderResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Order not found", body = ErrorResponse),
+        (status = 409, description = "The order can no longer change this way", body = ErrorResponse),
+        (status = 422, description = "A value violates a database constraint", body = ErrorResponse),
+        (status = 412, description = "The record changed since the given ETag", body = ErrorResponse),
+        (status = 428, description = "If-Match was not sent", body = ErrorResponse),
+    ),
+)]
 pub async fn update_order(
     data: web::Data<AppState>,
     path: web::Path<i32>,
-    dto: web::Json<OrderUpdate>,
+    if_match: IfMatch,
+    dto: ValidJson<OrderUpdate>,
 ) -> Result<HttpResponse, AppError> {
     let order_id = path.into_inner();
-    let order = OrderService::update(&data.db, order_id, dto.into_inner()).await?;
+    let order = OrderService::update(&data.db, order_id, &if_match, dto.into_inner()).await?;
     
     let customer = Customers::find_by_id(order.customer_id)
         .one(&data.db)
@@ -106,18 +168,47 @@ pub async fn update_order(
         customer_name: Some(format!("{} {}", customer.first_name, customer.last_name)),
     };
     
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().insert_header(etag(order.version)).json(response))
 }
 
+/// Delete a order.
+#[utoipa::path(
+    delete,
+    path = "/orders/{id}",
+    tag = "orders",
+    params(
+        ("id" = i32, Path, description = "Order id"),
+        ("If-Match" = String, Header, description = "ETag the change is based on"),
+    ),
+    responses(
+        (status = 204, description = "Order deleted"),
+        (status = 404, description = "Order not found", body = ErrorResponse),
+        (status = 409, description = "Other records still refer to it", body = ErrorResponse),
+        (status = 412, description = "The record changed since the given ETag", body = ErrorResponse),
+        (status = 428, description = "If-Match was not sent", body = ErrorResponse),
+    ),
+)]
 pub async fn delete_order(
     data: web::Data<AppState>,
     path: web::Path<i32>,
+    if_match: IfMatch,
 ) -> Result<HttpResponse, AppError> {
     let order_id = path.into_inner();
-    OrderService::delete(&data.db, order_id).await?;
+    OrderService::delete(&data.db, order_id, &if_match).await?;
     Ok(HttpResponse::NoContent().finish())
 }
 
+/// Get an order with its items.
+#[utoipa::path(
+    get,
+    path = "/orders/{id}/details",
+    tag = "orders",
+    params(("id" = i32, Path, description = "Order id")),
+    responses(
+        (status = 200, description = "The order and its items", body = OrderDetailsResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 404, description = "Order not found", body = ErrorResponse),
+    ),
+)]
 pub async fn get_order_details(
     data: web::Data<AppState>,
     path: web::Path<i32>,
@@ -133,13 +224,12 @@ pub async fn get_order_details(
         .await?
         .ok_or_else(|| AppError::NotFound)?;
     
+    let subtotal = items.iter().map(|(item, _)| item.line_total()).sum();
+    
     // Build item responses WITHOUT full product objects
     let mut item_responses = Vec::new();
-    for item in items {
-        let product = Products::find_by_id(item.product_id)
-            .one(&data.db)
-            .await?
-            .ok_or_else(|| AppError::NotFound)?;
+    for (item, product) in items {
+        let product = product.ok_or(AppError::NotFound)?;
         
         item_responses.push(OrderItemResponse {
             product_id: item.product_id,
@@ -147,6 +237,7 @@ pub async fn get_order_details(
             product_name: Some(product.name),
             quantity: item.quantity,
             unit_price: item.unit_price,   // Price at order time
+            line_total: item.line_total(),
         });
     }
     
@@ -159,8 +250,110 @@ pub async fn get_order_details(
         shipping_address: order.shipping_address,
         customer_name: format!("{} {}", customer.first_name, customer.last_name).into(),
         items: item_responses,
+        subtotal,
     };
     
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().insert_header(etag(order.version)).json(response))
+}
+
+
+/// Confirm a pending order.
+#[utoipa::path(
+    post,
+    path = "/orders/{id}/confirm",
+    tag = "orders",
+    params(("id" = i32, Path, description = "Order id")),
+    responses(
+        (status = 200, description = "The updated order", body = OrderResponse),
+        (status = 404, description = "Order not found", body = ErrorResponse),
+        (status = 409, description = "The order cannot make this transition", body = ErrorResponse),
+    ),
+)]
+pub async fn confirm_order(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    transition_order(&data, path.into_inner(), OrderStatus::Confirmed).await
+}
+
+/// Mark a confirmed order as shipped.
+#[utoipa::path(
+    post,
+    path = "/orders/{id}/ship",
+    tag = "orders",
+    params(("id" = i32, Path, description = "Order id")),
+    responses(
+        (status = 200, description = "The updated order", body = OrderResponse),
+        (status = 404, description = "Order not found", body = ErrorResponse),
+        (status = 409, description = "The order cannot make this transition", body = ErrorResponse),
+    ),
+)]
+pub async fn ship_order(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    transition_order(&data, path.into_inner(), OrderStatus::Shipped).await
+}
+
+/// Mark a shipped order as delivered.
+#[utoipa::path(
+    post,
+    path = "/orders/{id}/deliver",
+    tag = "orders",
+    params(("id" = i32, Path, description = "Order id")),
+    responses(
+        (status = 200, description = "The updated order", body = OrderResponse),
+        (status = 404, description = "Order not found", body = ErrorResponse),
+        (status = 409, description = "The order cannot make this transition", body = ErrorResponse),
+    ),
+)]
+pub async fn deliver_order(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    transition_order(&data, path.into_inner(), OrderStatus::Delivered).await
+}
+
+/// Cancel an order, returning its stock.
+#[utoipa::path(
+    post,
+    path = "/orders/{id}/cancel",
+    tag = "orders",
+    params(("id" = i32, Path, description = "Order id")),
+    responses(
+        (status = 200, description = "The updated order", body = OrderResponse),
+        (status = 404, description = "Order not found", body = ErrorResponse),
+        (status = 409, description = "The order cannot make this transition", body = ErrorResponse),
+    ),
+)]
+pub async fn cancel_order(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    transition_order(&data, path.into_inner(), OrderStatus::Cancelled).await
 }
 
+async fn transition_order(
+    data: &AppState,
+    order_id: i32,
+    status: OrderStatus,
+) -> Result<HttpResponse, AppError> {
+    let order = OrderService::transition(&data.db, order_id, status).await?;
+    
+    let customer = Customers::find_by_id(order.customer_id)
+        .one(&data.db)
+        .await?
+        .unwrap_or_default();
+    
+    let response = OrderResponse {
+        order_id: order.order_id,
+        customer_id: order.customer_id,
+        order_date: order.order_date,
+        status: order.status,
+        total_amount: order.total_amount,
+        shipping_address: order.shipping_address,
+        customer_name: Some(format!("{} {}", customer.first_name, customer.last_name)),
+    };
+    
+    Ok(HttpResponse::Ok().json(response))
+}
@@ -1,29 +1,42 @@
 use sea_orm::EntityTrait;
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse, http::header::ContentDisposition};
 
 use crate::{
     dtos::*,
     state::AppState,
     services::*,
-    error::AppError,
+    error::{AppError, ErrorResponse},
+    validation::ValidJson,
+    concurrency::{IfMatch, etag},
+    pagination::{Page, PageParams},
+    auth::IncludeDeleted,
     db::*,
 };
 
-pub async fn get_products(data: web::Data<AppState>) -> Result<HttpResponse, AppError> {
-    let products = ProductService::find_all(&data.db).await?;
+/// List products.
+#[utoipa::path(
+    get,
+    path = "/products",
+    tag = "products",
+    params(ProductFilter, PageParams, IncludeDeleted),
+    responses(
+        (status = 200, description = "A page of products", body = Page<ProductResponse>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Only admins may include deleted products", body = ErrorResponse),
+    ),
+)]
+pub async fn get_products(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    filter: web::Query<ProductFilter>,
+    page: web::Query<PageParams>,
+    deleted: IncludeDeleted,
+) -> Result<HttpResponse, AppError> {
+    let (products, total) = ProductService::find_all(&data.db, &filter, &page, deleted.include_deleted).await?;
+    let related = ProductService::load_related(&data.db, &products).await?;
     
     let mut products_with_additional_data = Vec::new();
-    for product in products {
-        let category = Categories::find_by_id(product.category_id)
-            .one(&data.db)
-            .await?
-            .unwrap_or_default();
-        
-        let supplier = Suppliers::find_by_id(product.supplier_id)
-            .one(&data.db)
-            .await?
-            .unwrap_or_default();
-        
+    for (product, (category, supplier)) in products.into_iter().zip(related) {
         let response = ProductResponse {
             product_id: product.product_id,
             name: product.name,
@@ -32,21 +45,87 @@ pub async fn get_products(data: web::Data<AppState>) -> Result<HttpResponse, App
             stock_quantity: product.stock_quantity,
             category_id: product.category_id,
             supplier_id: product.supplier_id,
-            category_name: Some(category.name),
-            supplier_name: Some(supplier.company_name),
+            category_name: category.map(|c| c.name),
+            supplier_name: supplier.map(|s| s.company_name),
+            deleted_at: product.deleted_at,
         };
         products_with_additional_data.push(response);
     }
     
-    Ok(HttpResponse::Ok().json(products_with_additional_data))
+    Ok(HttpResponse::Ok().json(Page::new(products_with_additional_data, total, &page, &req)))
 }
 
+/// Search products.
+///
+/// Finds products whose name or description contains every word of `q`, counting words
+/// that start with a searched word as matches, ranked with name matches first. The
+/// facets count the matches per category, supplier and price range.
+#[utoipa::path(
+    get,
+    path = "/products/search",
+    tag = "products",
+    params(SearchParams, ProductFilter, PageParams),
+    responses(
+        (status = 200, description = "A page of matching products with facet counts", body = ProductSearchResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+    ),
+)]
+pub async fn search_products(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    search: web::Query<SearchParams>,
+    filter: web::Query<ProductFilter>,
+    page: web::Query<PageParams>,
+) -> Result<HttpResponse, AppError> {
+    let (matches, total) = ProductService::search(&data.db, &search.q, &filter, &page).await?;
+    let facets = ProductService::search_facets(&data.db, &search.q, &filter).await?;
+    
+    let hits = matches
+        .into_iter()
+        .map(|m| ProductSearchHit {
+            product: ProductResponse {
+                product_id: m.product_id,
+                name: m.name,
+                description: m.description,
+                price: m.price,
+                stock_quantity: m.stock_quantity,
+                category_id: m.category_id,
+                supplier_id: m.supplier_id,
+                category_name: m.category_name,
+                supplier_name: m.supplier_name,
+                deleted_at: m.deleted_at,
+            },
+            rank: m.rank,
+            name_highlight: m.name_highlight,
+            snippet: m.snippet,
+        })
+        .collect();
+    
+    Ok(HttpResponse::Ok().json(ProductSearchResponse {
+        page: Page::new(hits, total, &page, &req),
+        facets,
+    }))
+}
+
+/// Get a product.
+#[utoipa::path(
+    get,
+    path = "/products/{id}",
+    tag = "products",
+    params(("id" = i32, Path, description = "Product id"), IncludeDeleted),
+    responses(
+        (status = 200, description = "The product", body = ProductResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 403, description = "Only admins may include deleted products", body = ErrorResponse),
+        (status = 404, description = "Product not found", body = ErrorResponse),
+    ),
+)]
 pub async fn get_product(
     data: web::Data<AppState>,
     path: web::Path<i32>,
+    deleted: IncludeDeleted,
 ) -> Result<HttpResponse, AppError> {
     let product_id = path.into_inner();
-    let product = ProductService::find_by_id(&data.db, product_id).await?;
+    let product = ProductService::find_by_id(&data.db, product_id, deleted.include_deleted).await?;
     
     let category = Categories::find_by_id(product.category_id)
         .one(&data.db)
@@ -68,14 +147,27 @@ pub async fn get_product(
         supplier_id: product.supplier_id,
         category_name: Some(category.name),
         supplier_name: Some(supplier.company_name),
+        deleted_at: product.deleted_at,
     };
     
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().insert_header(etag(product.version)).json(response))
 }
 
+/// Create a product.
+#[utoipa::path(
+    post,
+    path = "/products",
+    tag = "products",
+    request_body = ProductCreate,
+    responses(
+        (status = 201, description = "The created product", body = ProductResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 422, description = "A value violates a database constraint", body = ErrorResponse),
+    ),
+)]
 pub async fn create_product(
     data: web::Data<AppState>,
-    dto: web::Json<ProductCreate>,
+    dto: ValidJson<ProductCreate>,
 ) -> Result<HttpResponse, AppError> {
     let product = ProductService::create(&data.db, dto.into_inner()).await?;
     
@@ -99,18 +191,39 @@ pub async fn create_product(
         supplier_id: product.supplier_id,
         category_name: Some(category.name),
         supplier_name: Some(supplier.company_name),
+        deleted_at: product.deleted_at,
     };
     
-    Ok(HttpResponse::Created().json(response))
+    Ok(HttpResponse::Created().insert_header(etag(product.version)).json(response))
 }
 
+/// Update a product.
+#[utoipa::path(
+    put,
+    path = "/products/{id}",
+    tag = "products",
+    params(
+        ("id" = i32, Path, description = "Product id"),
+        ("If-Match" = String, Header, description = "ETag the change is based on"),
+    ),
+    request_body = ProductUpdate,
+    responses(
+        (status = 200, description = "The updated product", body = ProductResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Product not found", body = ErrorResponse),
+        (status = 422, description = "A value violates a database constraint", body = ErrorResponse),
+        (status = 412, description = "The record changed since the given ETag", body = ErrorResponse),
+        (status = 428, description = "If-Match was not sent", body = ErrorResponse),
+    ),
+)]
 pub async fn update_product(
     data: web::Data<AppState>,
     path: web::Path<i32>,
-    dto: web::Json<ProductUpdate>,
+    if_match: IfMatch,
+    dto: ValidJson<ProductUpdate>,
 ) -> Result<HttpResponse, AppError> {
     let product_id = path.into_inner();
-    let product = ProductService::update(&data.db, product_id, dto.into_inner()).await?;
+    let product = ProductService::update(&data.db, product_id, &if_match, dto.into_inner()).await?;
     
     let category = Categories::find_by_id(product.category_id)
         .one(&data.db)
@@ -132,17 +245,164 @@ pub async fn update_product(
         supplier_id: product.supplier_id,
         category_name: Some(category.name),
         supplier_name: Some(supplier.company_name),
+        deleted_at: product.deleted_at,
     };
     
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().insert_header(etag(product.version)).json(response))
 }
 
+/// Delete a product.
+#[utoipa::path(
+    delete,
+    path = "/products/{id}",
+    tag = "products",
+    params(
+        ("id" = i32, Path, description = "Product id"),
+        ("If-Match" = String, Header, description = "ETag the change is based on"),
+    ),
+    responses(
+        (status = 204, description = "Product deleted"),
+        (status = 404, description = "Product not found", body = ErrorResponse),
+        (status = 412, description = "The record changed since the given ETag", body = ErrorResponse),
+        (status = 428, description = "If-Match was not sent", body = ErrorResponse),
+    ),
+)]
 pub async fn delete_product(
     data: web::Data<AppState>,
     path: web::Path<i32>,
+    if_match: IfMatch,
 ) -> Result<HttpResponse, AppError> {
     let product_id = path.into_inner();
-    ProductService::delete(&data.db, product_id).await?;
+    ProductService::delete(&data.db, product_id, &if_match).await?;
     Ok(HttpResponse::NoContent().finish())
 }
 
+/// Restore a deleted product.
+#[utoipa::path(
+    post,
+    path = "/products/{id}/restore",
+    tag = "products",
+    params(
+        ("id" = i32, Path, description = "Product id"),
+        ("If-Match" = Option<String>, Header, description = "ETag the change is based on"),
+    ),
+    responses(
+        (status = 200, description = "The restored product", body = ProductResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 404, description = "Product not found", body = ErrorResponse),
+        (status = 409, description = "The product is not deleted, or a record with the same unique value exists", body = ErrorResponse),
+        (status = 412, description = "The record changed since the given ETag", body = ErrorResponse),
+    ),
+)]
+pub async fn restore_product(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+    if_match: IfMatch,
+) -> Result<HttpResponse, AppError> {
+    let product_id = path.into_inner();
+    let product = ProductService::restore(&data.db, product_id, &if_match).await?;
+    
+    let category = Categories::find_by_id(product.category_id)
+        .one(&data.db)
+        .await?
+        .unwrap_or_default();
+    
+    let supplier = Suppliers::find_by_id(product.supplier_id)
+        .one(&data.db)
+        .await?
+        .unwrap_or_default();
+    
+    let response = ProductResponse {
+        product_id: product.product_id,
+        name: product.name,
+        description: product.description,
+        price: product.price,
+        stock_quantity: product.stock_quantity,
+        category_id: product.category_id,
+        supplier_id: product.supplier_id,
+        category_name: Some(category.name),
+        supplier_name: Some(supplier.company_name),
+        deleted_at: product.deleted_at,
+    };
+    
+    Ok(HttpResponse::Ok().insert_header(etag(product.version)).json(response))
+}
+
+/// Import products from CSV.
+///
+/// Products are matched on `name` within their supplier, given as `supplier_id` or as the
+/// supplier's `company_name` in `supplier`; the category can likewise be given as `category`. Matching rows are
+/// updated with the non-empty cells if their `version` is still the exported one, the rest
+/// are created. Either every row is saved or, if any row fails, none are.
+#[utoipa::path(
+    post,
+    path = "/products/import",
+    tag = "products",
+    params(ImportParams),
+    request_body(content = String, content_type = "text/csv", description = "A header row followed by one product per row"),
+    responses(
+        (status = 200, description = "What was imported, or on a dry run what would be", body = ImportReport),
+        (status = 400, description = "Unreadable CSV, or the errors of each failed row", body = ErrorResponse),
+    ),
+)]
+pub async fn import_products(
+    data: web::Data<AppState>,
+    params: web::Query<ImportParams>,
+    body: web::Bytes,
+) -> Result<HttpResponse, AppError> {
+    let report = CsvService::import_products(&data.db, &body, &params).await?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Export all products as CSV, in the columns the import reads.
+#[utoipa::path(
+    get,
+    path = "/products/export.csv",
+    tag = "products",
+    responses(
+        (status = 200, description = "Every product", content_type = "text/csv", body = String),
+    ),
+)]
+pub async fn export_products(data: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header(ContentDisposition::attachment("products.csv"))
+        .streaming(CsvService::export_products(data.db.clone()))
+}
+
+/// Create, update and delete products in one request.
+///
+/// Operations run in order in a single transaction. In `atomic` mode the first failure
+/// undoes the batch and is reported with its `operations[<index>]`; in `per_item` mode
+/// every operation gets its own result and only the failed ones are undone.
+#[utoipa::path(
+    post,
+    path = "/products/batch",
+    tag = "products",
+    request_body = BatchRequest<ProductCreate, ProductUpdate>,
+    responses(
+        (status = 200, description = "The result of each operation", body = BatchResponse),
+        (status = 400, description = "Invalid request, or in atomic mode an invalid operation", body = ErrorResponse),
+        (status = 404, description = "In atomic mode, an operation on a missing product", body = ErrorResponse),
+        (status = 409, description = "In atomic mode, an operation that conflicts with other records", body = ErrorResponse),
+        (status = 412, description = "In atomic mode, an operation whose version is out of date", body = ErrorResponse),
+        (status = 428, description = "In atomic mode, an update or delete without a version", body = ErrorResponse),
+        (status = 422, description = "In atomic mode, an operation that violates a database constraint", body = ErrorResponse),
+    ),
+)]
+pub async fn batch_products(
+    data: web::Data<AppState>,
+    body: web::Json<BatchRequest<ProductCreate, ProductUpdate>>,
+) -> Result<HttpResponse, AppError> {
+    let response = BatchService::run(&data.db, body.into_inner(), async |txn, operation| match operation {
+        BatchOperation::Create { data } => Ok(ProductService::create(txn, data).await?.product_id),
+        BatchOperation::Update { id, version, data } => {
+            ProductService::update(txn, id, &IfMatch::version(version)?, data).await?;
+            Ok(id)
+        }
+        BatchOperation::Delete { id, version } => {
+            ProductService::delete(txn, id, &IfMatch::version(version)?).await?;
+            Ok(id)
+        }
+    }).await?;
+    Ok(HttpResponse::Ok().json(response))
+}
@@ -1,23 +1,41 @@
 use sea_orm::EntityTrait;
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
+use validator::Validate;
 
 use crate::{
     dtos::*,
     state::AppState,
     services::*,
-    error::AppError,
+    error::{AppError, ErrorResponse},
+    validation::ValidJson,
+    concurrency::{IfMatch, etag},
+    pagination::{Page, PageParams},
     db::*,
 };
 
-pub async fn get_shipments(data: web::Data<AppState>) -> Result<HttpResponse, AppError> {
-    let shipments = ShipmentService::find_all(&data.db).await?;
+/// List shipments.
+#[utoipa::path(
+    get,
+    path = "/shipments",
+    tag = "shipments",
+    params(ShipmentFilter, PageParams),
+    responses(
+        (status = 200, description = "A page of shipments", body = Page<ShipmentResponse>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+    ),
+)]
+pub async fn get_shipments(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    filter: web::Query<ShipmentFilter>,
+    page: web::Query<PageParams>,
+) -> Result<HttpResponse, AppError> {
+    let (shipments, total) = ShipmentService::find_all(&data.db, &filter, &page).await?;
+    let suppliers = ShipmentService::load_suppliers(&data.db, &shipments).await?;
     
     let mut shipments_with_additional_information = Vec::new();
-    for shipment in shipments {
-        let supplier = Suppliers::find_by_id(shipment.supplier_id)
-            .one(&data.db)
-            .await?
-            .unwrap_or_default();
+    for (shipment, supplier) in shipments.into_iter().zip(suppliers) {
+        let supplier = supplier.unwrap_or_default();
         
         let response = ShipmentResponse {
             shipment_id: shipment.shipment_id,
@@ -31,9 +49,20 @@ pub async fn get_shipments(data: web::Data<AppState>) -> Result<HttpResponse, Ap
         shipments_with_additional_information.push(response);
     }
     
-    Ok(HttpResponse::Ok().json(shipments_with_additional_information))
+    Ok(HttpResponse::Ok().json(Page::new(shipments_with_additional_information, total, &page, &req)))
 }
 
+/// Get a shipment.
+#[utoipa::path(
+    get,
+    path = "/shipments/{id}",
+    tag = "shipments",
+    params(("id" = i32, Path, description = "Shipment id")),
+    responses(
+        (status = 200, description = "The shipment", body = ShipmentResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 404, description = "Shipment not found", body = ErrorResponse),
+    ),
+)]
 pub async fn get_shipment(
     data: web::Data<AppState>,
     path: web::Path<i32>,
@@ -56,12 +85,24 @@ pub async fn get_shipment(
         supplier_name: Some(supplier.company_name),
     };
     
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().insert_header(etag(shipment.version)).json(response))
 }
 
+/// Create a shipment.
+#[utoipa::path(
+    post,
+    path = "/shipments",
+    tag = "shipments",
+    request_body = ShipmentCreate,
+    responses(
+        (status = 201, description = "The created shipment", body = ShipmentResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 422, description = "A value violates a database constraint", body = ErrorResponse),
+    ),
+)]
 pub async fn create_shipment(
     data: web::Data<AppState>,
-    dto: web::Json<ShipmentCreate>,
+    dto: ValidJson<ShipmentCreate>,
 ) -> Result<HttpResponse, AppError> {
     let shipment = ShipmentService::create(&data.db, dto.into_inner()).await?;
     
@@ -80,16 +121,82 @@ pub async fn create_shipment(
         supplier_name: Some(supplier.company_name),
     };
     
-    Ok(HttpResponse::Created().json(response))
+    Ok(HttpResponse::Created().insert_header(etag(shipment.version)).json(response))
 }
 
+/// Update a shipment.
+#[utoipa::path(
+    put,
+    path = "/shipments/{id}",
+    tag = "shipments",
+    params(
+        ("id" = i32, Path, description = "Shipment id"),
+        ("If-Match" = String, Header, description = "ETag the change is based on"),
+    ),
+    request_body = ShipmentUpdate,
+    responses(
+        (status = 200, description = "The updated shipment", body = ShipmentResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Shipment not found", body = ErrorResponse),
+        (status = 409, description = "The shipment can no longer change this way", body = ErrorResponse),
+        (status = 422, description = "A value violates a database constraint", body = ErrorResponse),
+        (status = 412, description = "The record changed since the given ETag", body = ErrorResponse),
+        (status = 428, description = "If-Match was not sent", body = ErrorResponse),
+    ),
+)]
 pub async fn update_shipment(
     data: web::Data<AppState>,
     path: web::Path<i32>,
-    dto: web::Json<ShipmentUpdate>,
+    if_match: IfMatch,
+    dto: ValidJson<ShipmentUpdate>,
+) -> Result<HttpResponse, AppError> {
+    let shipment_id = path.into_inner();
+    let shipment = ShipmentService::update(&data.db, shipment_id, &if_match, dto.into_inner()).await?;
+    
+    let supplier = Suppliers::find_by_id(shipment.supplier_id)
+        .one(&data.db)
+        .await?
+        .unwrap_or_default();
+    
+    let response = ShipmentResponse {
+        shipment_id: shipment.shipment_id,
+        supplier_id: shipment.supplier_id,
+        shipment_date: shipment.shipment_date,
+        expected_delivery_date: shipment.expected_delivery_date,
+        status: shipment.status,
+        total_cost: shipment.total_cost,
+        supplier_name: Some(supplier.company_name),
+    };
+    
+    Ok(HttpResponse::Ok().insert_header(etag(shipment.version)).json(response))
+}
+
+/// Receive some or, with no body, all outstanding items of a shipment.
+///
+/// Quantities beyond what was ordered are accepted and reported as discrepancies. With
+/// `close` the shipment is marked delivered without receiving what is still missing.
+#[utoipa::path(
+    post,
+    path = "/shipments/{id}/receive",
+    tag = "shipments",
+    params(("id" = i32, Path, description = "Shipment id")),
+    request_body = Option<ShipmentReceive>,
+    responses(
+        (status = 200, description = "The updated shipment", body = ShipmentResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Shipment not found", body = ErrorResponse),
+        (status = 409, description = "The shipment is no longer receiving", body = ErrorResponse),
+    ),
+)]
+pub async fn receive_shipment(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+    dto: Option<web::Json<ShipmentReceive>>,
 ) -> Result<HttpResponse, AppError> {
     let shipment_id = path.into_inner();
-    let shipment = ShipmentService::update(&data.db, shipment_id, dto.into_inner()).await?;
+    let dto = dto.map(web::Json::into_inner).unwrap_or_default();
+    dto.validate()?;
+    let shipment = ShipmentService::receive(&data.db, shipment_id, dto).await?;
     
     let supplier = Suppliers::find_by_id(shipment.supplier_id)
         .one(&data.db)
@@ -109,15 +216,44 @@ pub async fn update_shipment(
     Ok(HttpResponse::Ok().json(response))
 }
 
+/// Delete a shipment.
+#[utoipa::path(
+    delete,
+    path = "/shipments/{id}",
+    tag = "shipments",
+    params(
+        ("id" = i32, Path, description = "Shipment id"),
+        ("If-Match" = String, Header, description = "ETag the change is based on"),
+    ),
+    responses(
+        (status = 204, description = "Shipment deleted"),
+        (status = 404, description = "Shipment not found", body = ErrorResponse),
+        (status = 409, description = "Other records still refer to it", body = ErrorResponse),
+        (status = 412, description = "The record changed since the given ETag", body = ErrorResponse),
+        (status = 428, description = "If-Match was not sent", body = ErrorResponse),
+    ),
+)]
 pub async fn delete_shipment(
     data: web::Data<AppState>,
     path: web::Path<i32>,
+    if_match: IfMatch,
 ) -> Result<HttpResponse, AppError> {
     let shipment_id = path.into_inner();
-    ShipmentService::delete(&data.db, shipment_id).await?;
+    ShipmentService::delete(&data.db, shipment_id, &if_match).await?;
     Ok(HttpResponse::NoContent().finish())
 }
 
+/// Get a shipment with its items.
+#[utoipa::path(
+    get,
+    path = "/shipments/{id}/details",
+    tag = "shipments",
+    params(("id" = i32, Path, description = "Shipment id")),
+    responses(
+        (status = 200, description = "The shipment and its items", body = ShipmentDetailsResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 404, description = "Shipment not found", body = ErrorResponse),
+    ),
+)]
 pub async fn get_shipment_details(
     data: web::Data<AppState>,
     path: web::Path<i32>,
@@ -131,17 +267,15 @@ pub async fn get_shipment_details(
         .unwrap_or_default();
     
     let mut items_with_additional_information = Vec::new();
-    for item in items {
-        let product = Products::find_by_id(item.product_id)
-            .one(&data.db)
-            .await?
-            .unwrap_or_default();
+    for (item, product) in items {
+        let product = product.unwrap_or_default();
         
         let item_response = ShipmentItemResponse {
             shipment_id: item.shipment_id,
             product_id: item.product_id,
             quantity: item.quantity,
             unit_cost: item.unit_cost,
+            received_quantity: item.received_quantity,
             product_name: Some(product.name),
         };
         items_with_additional_information.push(item_response);
@@ -158,5 +292,5 @@ pub async fn get_shipment_details(
         items: items_with_additional_information,
     };
     
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().insert_header(etag(shipment.version)).json(response))
 }
@@ -1,15 +1,39 @@
-use sea_orm::EntityTrait;
-use actix_web::{web, HttpResponse};
+use std::collections::HashMap;
+
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use actix_web::{web, HttpRequest, HttpResponse, http::header::ContentDisposition};
 
 use crate::{
     dtos::*,
     state::AppState,
     services::*,
-    error::AppError,
+    error::{AppError, ErrorResponse},
+    validation::ValidJson,
+    concurrency::{IfMatch, etag},
+    pagination::{Page, PageParams},
+    auth::IncludeDeleted,
+    db::{Products, products},
 };
 
-pub async fn get_suppliers(data: web::Data<AppState>) -> Result<HttpResponse, AppError> {
-    let suppliers = SupplierService::find_all(&data.db).await?;
+/// List suppliers.
+#[utoipa::path(
+    get,
+    path = "/suppliers",
+    tag = "suppliers",
+    params(PageParams, IncludeDeleted),
+    responses(
+        (status = 200, description = "A page of suppliers", body = Page<SupplierResponse>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Only admins may include deleted suppliers", body = ErrorResponse),
+    ),
+)]
+pub async fn get_suppliers(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    page: web::Query<PageParams>,
+    deleted: IncludeDeleted,
+) -> Result<HttpResponse, AppError> {
+    let (suppliers, total) = SupplierService::find_all(&data.db, &page, deleted.include_deleted).await?;
     let response: Vec<SupplierResponse> = suppliers.into_iter().map(|s| SupplierResponse {
         supplier_id: s.supplier_id,
         company_name: s.company_name,
@@ -17,16 +41,30 @@ pub async fn get_suppliers(data: web::Data<AppState>) -> Result<HttpResponse, Ap
         email: s.email,
         phone: s.phone,
         address: s.address,
+        deleted_at: s.deleted_at,
     }).collect();
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().json(Page::new(response, total, &page, &req)))
 }
 
+/// Get a supplier.
+#[utoipa::path(
+    get,
+    path = "/suppliers/{id}",
+    tag = "suppliers",
+    params(("id" = i32, Path, description = "Supplier id"), IncludeDeleted),
+    responses(
+        (status = 200, description = "The supplier", body = SupplierResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 403, description = "Only admins may include deleted suppliers", body = ErrorResponse),
+        (status = 404, description = "Supplier not found", body = ErrorResponse),
+    ),
+)]
 pub async fn get_supplier(
     data: web::Data<AppState>,
     path: web::Path<i32>,
+    deleted: IncludeDeleted,
 ) -> Result<HttpResponse, AppError> {
     let supplier_id = path.into_inner();
-    let supplier = SupplierService::find_by_id(&data.db, supplier_id).await?;
+    let supplier = SupplierService::find_by_id(&data.db, supplier_id, deleted.include_deleted).await?;
     let response = SupplierResponse {
         supplier_id: supplier.supplier_id,
         company_name: supplier.company_name,
@@ -34,13 +72,26 @@ pub async fn get_supplier(
         email: supplier.email,
         phone: supplier.phone,
         address: supplier.address,
+        deleted_at: supplier.deleted_at,
     };
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().insert_header(etag(supplier.version)).json(response))
 }
 
+/// Create a supplier.
+#[utoipa::path(
+    post,
+    path = "/suppliers",
+    tag = "suppliers",
+    request_body = SupplierCreate,
+    responses(
+        (status = 201, description = "The created supplier", body = SupplierResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 422, description = "A value violates a database constraint", body = ErrorResponse),
+    ),
+)]
 pub async fn create_supplier(
     data: web::Data<AppState>,
-    dto: web::Json<SupplierCreate>,
+    dto: ValidJson<SupplierCreate>,
 ) -> Result<HttpResponse, AppError> {
     let supplier = SupplierService::create(&data.db, dto.into_inner()).await?;
     let response = SupplierResponse {
@@ -50,17 +101,38 @@ pub async fn create_supplier(
         email: supplier.email,
         phone: supplier.phone,
         address: supplier.address,
+        deleted_at: supplier.deleted_at,
     };
-    Ok(HttpResponse::Created().json(response))
+    Ok(HttpResponse::Created().insert_header(etag(supplier.version)).json(response))
 }
 
+/// Update a supplier.
+#[utoipa::path(
+    put,
+    path = "/suppliers/{id}",
+    tag = "suppliers",
+    params(
+        ("id" = i32, Path, description = "Supplier id"),
+        ("If-Match" = String, Header, description = "ETag the change is based on"),
+    ),
+    request_body = SupplierUpdate,
+    responses(
+        (status = 200, description = "The updated supplier", body = SupplierResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Supplier not found", body = ErrorResponse),
+        (status = 422, description = "A value violates a database constraint", body = ErrorResponse),
+        (status = 412, description = "The record changed since the given ETag", body = ErrorResponse),
+        (status = 428, description = "If-Match was not sent", body = ErrorResponse),
+    ),
+)]
 pub async fn update_supplier(
     data: web::Data<AppState>,
     path: web::Path<i32>,
-    dto: web::Json<SupplierUpdate>,
+    if_match: IfMatch,
+    dto: ValidJson<SupplierUpdate>,
 ) -> Result<HttpResponse, AppError> {
     let supplier_id = path.into_inner();
-    let supplier = SupplierService::update(&data.db, supplier_id, dto.into_inner()).await?;
+    let supplier = SupplierService::update(&data.db, supplier_id, &if_match, dto.into_inner()).await?;
     let response = SupplierResponse {
         supplier_id: supplier.supplier_id,
         company_name: supplier.company_name,
@@ -68,19 +140,89 @@ pub async fn update_supplier(
         email: supplier.email,
         phone: supplier.phone,
         address: supplier.address,
+        deleted_at: supplier.deleted_at,
     };
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().insert_header(etag(supplier.version)).json(response))
 }
 
+/// Delete a supplier.
+///
+/// A supplier that still has shipments or products is refused with 409 listing them, unless `archive`
+/// is set, which deletes the supplier and keeps them as they are.
+#[utoipa::path(
+    delete,
+    path = "/suppliers/{id}",
+    tag = "suppliers",
+    params(
+        ("id" = i32, Path, description = "Supplier id"),
+        ("If-Match" = String, Header, description = "ETag the change is based on"),
+        DeleteParams,
+    ),
+    responses(
+        (status = 204, description = "Supplier deleted"),
+        (status = 404, description = "Supplier not found", body = ErrorResponse),
+        (status = 409, description = "Other records still refer to it", body = ErrorResponse),
+        (status = 412, description = "The record changed since the given ETag", body = ErrorResponse),
+        (status = 428, description = "If-Match was not sent", body = ErrorResponse),
+    ),
+)]
 pub async fn delete_supplier(
     data: web::Data<AppState>,
     path: web::Path<i32>,
+    if_match: IfMatch,
+    params: web::Query<DeleteParams>,
 ) -> Result<HttpResponse, AppError> {
     let supplier_id = path.into_inner();
-    SupplierService::delete(&data.db, supplier_id).await?;
+    SupplierService::delete(&data.db, supplier_id, &if_match, params.archive).await?;
     Ok(HttpResponse::NoContent().finish())
 }
 
+/// Restore a deleted supplier.
+#[utoipa::path(
+    post,
+    path = "/suppliers/{id}/restore",
+    tag = "suppliers",
+    params(
+        ("id" = i32, Path, description = "Supplier id"),
+        ("If-Match" = Option<String>, Header, description = "ETag the change is based on"),
+    ),
+    responses(
+        (status = 200, description = "The restored supplier", body = SupplierResponse, headers(("ETag" = String, description = "Current version of the record"))),
+        (status = 404, description = "Supplier not found", body = ErrorResponse),
+        (status = 409, description = "The supplier is not deleted, or a record with the same unique value exists", body = ErrorResponse),
+        (status = 412, description = "The record changed since the given ETag", body = ErrorResponse),
+    ),
+)]
+pub async fn restore_supplier(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+    if_match: IfMatch,
+) -> Result<HttpResponse, AppError> {
+    let supplier_id = path.into_inner();
+    let supplier = SupplierService::restore(&data.db, supplier_id, &if_match).await?;
+    let response = SupplierResponse {
+        supplier_id: supplier.supplier_id,
+        company_name: supplier.company_name,
+        contact_name: supplier.contact_name,
+        email: supplier.email,
+        phone: supplier.phone,
+        address: supplier.address,
+        deleted_at: supplier.deleted_at,
+    };
+    Ok(HttpResponse::Ok().insert_header(etag(supplier.version)).json(response))
+}
+
+/// Get a supplier with their products.
+#[utoipa::path(
+    get,
+    path = "/suppliers/{id}/products",
+    tag = "suppliers",
+    params(("id" = i32, Path, description = "Supplier id")),
+    responses(
+        (status = 200, description = "The supplier and their products", body = SupplierProductsResponse),
+        (status = 404, description = "Supplier not found", body = ErrorResponse),
+    ),
+)]
 pub async fn get_supplier_products(
     data: web::Data<AppState>,
     path: web::Path<i32>,
@@ -95,6 +237,7 @@ pub async fn get_supplier_products(
         email: supplier.email,
         phone: supplier.phone,
         address: supplier.address,
+        deleted_at: supplier.deleted_at,
     };
     
     let products_response: Vec<ProductResponse> = products.into_iter().map(|p| ProductResponse {
@@ -107,10 +250,96 @@ pub async fn get_supplier_products(
         supplier_id: p.supplier_id,
         category_name: None,
         supplier_name: Some(supplier.company_name.clone()),
+        deleted_at: p.deleted_at,
     }).collect();
     
-    Ok(HttpResponse::Ok().json(serde_json::json!({
-        "supplier": supplier_response,
-        "products": products_response
-    })))
+    let response = SupplierProductsResponse {
+        supplier: supplier_response,
+        products: products_response,
+    };
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// List lines of a supplier's received shipments that arrived short or over.
+#[utoipa::path(
+    get,
+    path = "/suppliers/{id}/discrepancies",
+    tag = "suppliers",
+    params(("id" = i32, Path, description = "Supplier id")),
+    responses(
+        (status = 200, description = "Lines received short or over", body = Vec<ShipmentDiscrepancyResponse>),
+        (status = 404, description = "Supplier not found", body = ErrorResponse),
+    ),
+)]
+pub async fn get_supplier_discrepancies(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let supplier_id = path.into_inner();
+    SupplierService::find_by_id(&data.db, supplier_id, false).await?;
+    
+    let rows = ShipmentService::find_discrepancies(&data.db, supplier_id).await?;
+    
+    let product_ids: Vec<i32> = rows.iter().map(|(_, item)| item.product_id).collect();
+    let product_names: HashMap<i32, String> = Products::find()
+        .filter(products::Column::ProductId.is_in(product_ids))
+        .all(&data.db)
+        .await?
+        .into_iter()
+        .map(|p| (p.product_id, p.name))
+        .collect();
+    
+    let response: Vec<ShipmentDiscrepancyResponse> = rows.into_iter().map(|(shipment, item)| ShipmentDiscrepancyResponse {
+        shipment_id: shipment.shipment_id,
+        shipment_date: shipment.shipment_date,
+        expected_delivery_date: shipment.expected_delivery_date,
+        product_id: item.product_id,
+        product_name: product_names.get(&item.product_id).cloned(),
+        quantity: item.quantity,
+        received_quantity: item.received_quantity,
+        shortfall: item.quantity - item.received_quantity,
+    }).collect();
+    
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Import suppliers from CSV.
+///
+/// Suppliers are matched on `company_name`. Matching rows are
+/// updated with the non-empty cells if their `version` is still the exported one, the rest
+/// are created. Either every row is saved or, if any row fails, none are.
+#[utoipa::path(
+    post,
+    path = "/suppliers/import",
+    tag = "suppliers",
+    params(ImportParams),
+    request_body(content = String, content_type = "text/csv", description = "A header row followed by one supplier per row"),
+    responses(
+        (status = 200, description = "What was imported, or on a dry run what would be", body = ImportReport),
+        (status = 400, description = "Unreadable CSV, or the errors of each failed row", body = ErrorResponse),
+    ),
+)]
+pub async fn import_suppliers(
+    data: web::Data<AppState>,
+    params: web::Query<ImportParams>,
+    body: web::Bytes,
+) -> Result<HttpResponse, AppError> {
+    let report = CsvService::import_suppliers(&data.db, &body, &params).await?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// Export all suppliers as CSV, in the columns the import reads.
+#[utoipa::path(
+    get,
+    path = "/suppliers/export.csv",
+    tag = "suppliers",
+    responses(
+        (status = 200, description = "Every supplier", content_type = "text/csv", body = String),
+    ),
+)]
+pub async fn export_suppliers(data: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header(ContentDisposition::attachment("suppliers.csv"))
+        .streaming(CsvService::export_suppliers(data.db.clone()))
 }
@@ -0,0 +1,140 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use crate::{
+    dtos::*,
+    state::AppState,
+    services::*,
+    error::{AppError, ErrorResponse},
+    validation::ValidJson,
+    pagination::{Page, PageParams},
+};
+
+/// List users.
+#[utoipa::path(
+    get,
+    path = "/users",
+    tag = "users",
+    params(PageParams),
+    responses(
+        (status = 200, description = "A page of users", body = Page<UserResponse>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+    ),
+)]
+pub async fn get_users(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    page: web::Query<PageParams>,
+) -> Result<HttpResponse, AppError> {
+    let (users, total) = UserService::find_all(&data.db, &page).await?;
+    let response: Vec<UserResponse> = users.into_iter().map(|u| UserResponse {
+        user_id: u.user_id,
+        username: u.username,
+        role: u.role,
+        created_at: u.created_at,
+    }).collect();
+    Ok(HttpResponse::Ok().json(Page::new(response, total, &page, &req)))
+}
+
+/// Get a user.
+#[utoipa::path(
+    get,
+    path = "/users/{id}",
+    tag = "users",
+    params(("id" = i32, Path, description = "User id")),
+    responses(
+        (status = 200, description = "The user", body = UserResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+    ),
+)]
+pub async fn get_user(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = path.into_inner();
+    let user = UserService::find_by_id(&data.db, user_id).await?;
+    let response = UserResponse {
+        user_id: user.user_id,
+        username: user.username,
+        role: user.role,
+        created_at: user.created_at,
+    };
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Create a user.
+#[utoipa::path(
+    post,
+    path = "/users",
+    tag = "users",
+    request_body = UserCreate,
+    responses(
+        (status = 201, description = "The created user", body = UserResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 409, description = "The username is taken", body = ErrorResponse),
+        (status = 422, description = "A value violates a database constraint", body = ErrorResponse),
+    ),
+)]
+pub async fn create_user(
+    data: web::Data<AppState>,
+    dto: ValidJson<UserCreate>,
+) -> Result<HttpResponse, AppError> {
+    let user = UserService::create(&data.db, dto.into_inner()).await?;
+    let response = UserResponse {
+        user_id: user.user_id,
+        username: user.username,
+        role: user.role,
+        created_at: user.created_at,
+    };
+    Ok(HttpResponse::Created().json(response))
+}
+
+/// Update a user.
+#[utoipa::path(
+    put,
+    path = "/users/{id}",
+    tag = "users",
+    params(("id" = i32, Path, description = "User id")),
+    request_body = UserUpdate,
+    responses(
+        (status = 200, description = "The updated user", body = UserResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+        (status = 422, description = "A value violates a database constraint", body = ErrorResponse),
+    ),
+)]
+pub async fn update_user(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+    dto: ValidJson<UserUpdate>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = path.into_inner();
+    let user = UserService::update(&data.db, user_id, dto.into_inner()).await?;
+    let response = UserResponse {
+        user_id: user.user_id,
+        username: user.username,
+        role: user.role,
+        created_at: user.created_at,
+    };
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Delete a user.
+#[utoipa::path(
+    delete,
+    path = "/users/{id}",
+    tag = "users",
+    params(("id" = i32, Path, description = "User id")),
+    responses(
+        (status = 204, description = "User deleted"),
+        (status = 404, description = "User not found", body = ErrorResponse),
+        (status = 409, description = "Other records still refer to it", body = ErrorResponse),
+    ),
+)]
+pub async fn delete_user(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = path.into_inner();
+    UserService::delete(&data.db, user_id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
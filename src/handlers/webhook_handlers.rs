@@ -0,0 +1,188 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use crate::{
+    db::{webhook_deliveries, webhooks},
+    dtos::*,
+    state::AppState,
+    services::*,
+    error::{AppError, ErrorResponse},
+    validation::ValidJson,
+    pagination::{Page, PageParams},
+};
+
+fn webhook_response(webhook: webhooks::Model) -> WebhookResponse {
+    WebhookResponse {
+        webhook_id: webhook.webhook_id,
+        url: webhook.url,
+        event_type: webhook.event_type,
+        secret: None,
+        active: webhook.active,
+        created_at: webhook.created_at,
+    }
+}
+
+fn delivery_response(delivery: webhook_deliveries::Model) -> WebhookDeliveryResponse {
+    WebhookDeliveryResponse {
+        delivery_id: delivery.delivery_id,
+        webhook_id: delivery.webhook_id,
+        event_type: delivery.event_type,
+        payload: serde_json::from_str(&delivery.payload)
+            .unwrap_or(serde_json::Value::String(delivery.payload)),
+        status: delivery.status,
+        attempts: delivery.attempts,
+        next_attempt_at: delivery.next_attempt_at,
+        last_status_code: delivery.last_status_code,
+        last_error: delivery.last_error,
+        created_at: delivery.created_at,
+        delivered_at: delivery.delivered_at,
+    }
+}
+
+/// List webhooks.
+#[utoipa::path(
+    get,
+    path = "/webhooks",
+    tag = "webhooks",
+    params(PageParams),
+    responses(
+        (status = 200, description = "A page of webhooks", body = Page<WebhookResponse>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+    ),
+)]
+pub async fn get_webhooks(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    page: web::Query<PageParams>,
+) -> Result<HttpResponse, AppError> {
+    let (webhooks, total) = WebhookService::find_all(&data.db, &page).await?;
+    let response: Vec<WebhookResponse> = webhooks.into_iter().map(webhook_response).collect();
+    Ok(HttpResponse::Ok().json(Page::new(response, total, &page, &req)))
+}
+
+/// Get a webhook.
+#[utoipa::path(
+    get,
+    path = "/webhooks/{id}",
+    tag = "webhooks",
+    params(("id" = i32, Path, description = "Webhook id")),
+    responses(
+        (status = 200, description = "The webhook", body = WebhookResponse),
+        (status = 404, description = "Webhook not found", body = ErrorResponse),
+    ),
+)]
+pub async fn get_webhook(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let webhook = WebhookService::find_by_id(&data.db, path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(webhook_response(webhook)))
+}
+
+/// Register a webhook; its signing secret is generated and returned only in this response.
+#[utoipa::path(
+    post,
+    path = "/webhooks",
+    tag = "webhooks",
+    request_body = WebhookCreate,
+    responses(
+        (status = 201, description = "The created webhook", body = WebhookResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 422, description = "A value violates a database constraint", body = ErrorResponse),
+    ),
+)]
+pub async fn create_webhook(
+    data: web::Data<AppState>,
+    dto: ValidJson<WebhookCreate>,
+) -> Result<HttpResponse, AppError> {
+    let webhook = WebhookService::create(&data.db, dto.into_inner()).await?;
+    let secret = webhook.secret.clone();
+    let response = WebhookResponse { secret: Some(secret), ..webhook_response(webhook) };
+    Ok(HttpResponse::Created().json(response))
+}
+
+/// Update a webhook.
+#[utoipa::path(
+    put,
+    path = "/webhooks/{id}",
+    tag = "webhooks",
+    params(("id" = i32, Path, description = "Webhook id")),
+    request_body = WebhookUpdate,
+    responses(
+        (status = 200, description = "The updated webhook", body = WebhookResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Webhook not found", body = ErrorResponse),
+        (status = 422, description = "A value violates a database constraint", body = ErrorResponse),
+    ),
+)]
+pub async fn update_webhook(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+    dto: ValidJson<WebhookUpdate>,
+) -> Result<HttpResponse, AppError> {
+    let webhook = WebhookService::update(&data.db, path.into_inner(), dto.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(webhook_response(webhook)))
+}
+
+/// Delete a webhook and its delivery log.
+#[utoipa::path(
+    delete,
+    path = "/webhooks/{id}",
+    tag = "webhooks",
+    params(("id" = i32, Path, description = "Webhook id")),
+    responses(
+        (status = 204, description = "Webhook deleted"),
+        (status = 404, description = "Webhook not found", body = ErrorResponse),
+    ),
+)]
+pub async fn delete_webhook(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    WebhookService::delete(&data.db, path.into_inner()).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// List a webhook's deliveries, newest first.
+#[utoipa::path(
+    get,
+    path = "/webhooks/{id}/deliveries",
+    tag = "webhooks",
+    params(("id" = i32, Path, description = "Webhook id"), DeliveryFilter, PageParams),
+    responses(
+        (status = 200, description = "A page of deliveries", body = Page<WebhookDeliveryResponse>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Webhook not found", body = ErrorResponse),
+    ),
+)]
+pub async fn get_webhook_deliveries(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+    filter: web::Query<DeliveryFilter>,
+    page: web::Query<PageParams>,
+) -> Result<HttpResponse, AppError> {
+    let (deliveries, total) =
+        WebhookService::find_deliveries(&data.db, path.into_inner(), &filter, &page).await?;
+    let response: Vec<WebhookDeliveryResponse> = deliveries.into_iter().map(delivery_response).collect();
+    Ok(HttpResponse::Ok().json(Page::new(response, total, &page, &req)))
+}
+
+/// Send a finished delivery again.
+#[utoipa::path(
+    post,
+    path = "/webhooks/deliveries/{id}/retry",
+    tag = "webhooks",
+    params(("id" = i32, Path, description = "Delivery id")),
+    responses(
+        (status = 200, description = "The delivery, queued to be sent", body = WebhookDeliveryResponse),
+        (status = 404, description = "Delivery not found", body = ErrorResponse),
+        (status = 409, description = "The delivery is still pending", body = ErrorResponse),
+    ),
+)]
+pub async fn retry_webhook_delivery(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let delivery = WebhookService::redeliver(&data.db, path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(delivery_response(delivery)))
+}
@@ -0,0 +1,130 @@
+use actix_web::{
+    Error, HttpMessage, HttpResponse,
+    body::{self, BoxBody, MessageBody},
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    http::{
+        Method, StatusCode,
+        header::{self, HeaderName, HeaderValue},
+    },
+    middleware::Next,
+    web,
+};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    auth::CurrentUser, db::idempotency_keys, error::AppError, services::IdempotencyService,
+    state::AppState,
+};
+
+pub const IDEMPOTENCY_KEY_HEADER: HeaderName = HeaderName::from_static("idempotency-key");
+pub const IDEMPOTENT_REPLAYED_HEADER: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+/// Response headers stored alongside the body and sent again on replay.
+const REPLAYED_HEADERS: [HeaderName; 3] = [header::CONTENT_TYPE, header::ETAG, header::LOCATION];
+
+/// Makes authenticated POSTs that carry an `Idempotency-Key` safe to retry: the first
+/// response is stored for the configured window and replayed for retries with the same
+/// payload, while reusing the key for a different payload is rejected with 409.
+pub async fn replay(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let key = match req.headers().get(&IDEMPOTENCY_KEY_HEADER) {
+        Some(value) if req.method() == Method::POST => value.to_str().ok().map(str::to_string),
+        _ => return Ok(next.call(req).await?.map_into_boxed_body()),
+    };
+    let Some(key) = key.filter(|key| !key.is_empty() && key.len() <= 255) else {
+        return Ok(req.error_response(AppError::validation(
+            "Idempotency-Key must be between 1 and 255 visible ASCII characters",
+        )));
+    };
+    let Some(user_id) = req.extensions().get::<CurrentUser>().map(|user| user.user_id) else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+    let Some(state) = req.app_data::<web::Data<AppState>>().cloned() else {
+        return Ok(req.error_response(AppError::Internal));
+    };
+
+    let payload = req.extract::<web::Bytes>().await?;
+    let request_hash = request_hash(&req, &payload);
+    req.set_payload(Payload::from(payload));
+
+    let ttl = chrono::Duration::hours(state.idempotency.key_ttl_hours);
+    match IdempotencyService::begin(&state.db, user_id, &key, &request_hash, ttl).await {
+        Ok(None) => {}
+        Ok(Some(stored)) => {
+            let res = stored_response(stored);
+            return Ok(req.into_response(res));
+        }
+        Err(err) => return Ok(req.error_response(err)),
+    }
+
+    let res = match next.call(req).await {
+        Ok(res) => res,
+        Err(err) => {
+            IdempotencyService::release(&state.db, user_id, &key).await.ok();
+            return Err(err);
+        }
+    };
+
+    // Failures that may go away on retry are not remembered.
+    let status = res.status();
+    if status.is_server_error() || matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) {
+        IdempotencyService::release(&state.db, user_id, &key).await?;
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let (http_req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let body = body::to_bytes(body).await.map_err(|_| AppError::Internal)?;
+    let headers: Vec<(&str, &str)> = REPLAYED_HEADERS
+        .iter()
+        .filter_map(|name| Some((name.as_str(), res.headers().get(name)?.to_str().ok()?)))
+        .collect();
+    let stored = IdempotencyService::complete(
+        &state.db,
+        user_id,
+        &key,
+        status.as_u16(),
+        serde_json::to_string(&headers).unwrap_or_default(),
+        String::from_utf8_lossy(&body).into_owned(),
+    )
+    .await;
+    if let Err(err) = stored {
+        eprintln!("Storing response for Idempotency-Key failed: {err}");
+        IdempotencyService::release(&state.db, user_id, &key).await.ok();
+    }
+
+    Ok(ServiceResponse::new(http_req, res.set_body(body).map_into_boxed_body()))
+}
+
+/// Identifies the request a key was first used for by its method, target and body.
+fn request_hash(req: &ServiceRequest, payload: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(req.method().as_str());
+    hasher.update(b" ");
+    hasher.update(req.uri().path_and_query().map_or("", |target| target.as_str()));
+    hasher.update(b"\n");
+    hasher.update(payload);
+    hex::encode(hasher.finalize())
+}
+
+fn stored_response(stored: idempotency_keys::Model) -> HttpResponse {
+    let status = stored
+        .response_status
+        .and_then(|status| StatusCode::from_u16(status as u16).ok())
+        .unwrap_or(StatusCode::OK);
+    let headers: Vec<(String, String)> = stored
+        .response_headers
+        .and_then(|headers| serde_json::from_str(&headers).ok())
+        .unwrap_or_default();
+
+    let mut res = HttpResponse::build(status);
+    for (name, value) in headers {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+            res.insert_header((name, value));
+        }
+    }
+    res.insert_header((IDEMPOTENT_REPLAYED_HEADER, "true"));
+    res.body(stored.response_body.unwrap_or_default())
+}
@@ -1,17 +1,27 @@
 use std::{net::TcpListener};
 
 use actix_files::NamedFile;
-use actix_web::{HttpRequest, HttpServer, dev::Server, web};
-use sea_orm::DatabaseConnection;
+use actix_web::{HttpRequest, HttpServer, dev::Server, middleware::from_fn, web};
+use utoipa::OpenApi;
+use utoipa_scalar::{Scalar, Servable};
 
+use crate::state::AppState;
+
+pub mod auth;
+pub mod concurrency;
 pub mod config;
 pub mod db;
 pub mod handlers;
+pub mod idempotency;
 pub mod error;
 pub mod state;
 pub mod services;
 pub mod dtos;
+pub mod pagination;
+pub mod request_id;
+pub mod validation;
 pub mod migration;
+pub mod openapi;
 pub use actix_web::App;
 
 async fn index(_req: HttpRequest) -> actix_web::Result<NamedFile> {
@@ -24,56 +34,174 @@ async fn js(_req: HttpRequest) -> actix_web::Result<NamedFile> {
     Ok(NamedFile::open("index.js")?)
 }
 
-pub fn start_server(tcp_listener: TcpListener, db: DatabaseConnection) -> Result<Server, std::io::Error> {
-    let state = state::AppState::new(db);
+/// Reports malformed bodies, query strings and paths in the same shape as every other error.
+fn bad_request(err: impl std::fmt::Display) -> actix_web::Error {
+    error::AppError::validation(err.to_string()).into()
+}
+
+pub fn start_server(tcp_listener: TcpListener, state: AppState) -> Result<Server, std::io::Error> {
     let server = HttpServer::new(move || {
         
         App::new()
+            .wrap(from_fn(request_id::assign))
             .app_data(web::Data::new(state.clone()))
+            .app_data(web::JsonConfig::default().error_handler(|err, _| bad_request(err)))
+            .app_data(web::QueryConfig::default().error_handler(|err, _| bad_request(err)))
+            .app_data(web::PathConfig::default().error_handler(|err, _| bad_request(err)))
+            // Room for CSV imports
+            .app_data(web::PayloadConfig::new(10 * 1024 * 1024))
             .route("/", web::get().to(index))
             .route("/style.css", web::get().to(style))
             .route("/index.js", web::get().to(js))
             .service(
                 web::scope("/api")
-                    .route("/categories", web::get().to(handlers::get_categories))
-                    .route("/categories", web::post().to(handlers::create_category))
-                    .route("/categories/{id}", web::get().to(handlers::get_category))
-                    .route("/categories/{id}", web::put().to(handlers::update_category))
-                    .route("/categories/{id}", web::delete().to(handlers::delete_category))
-                    
-                    .route("/customers", web::get().to(handlers::get_customers))
-                    .route("/customers", web::post().to(handlers::create_customer))
-                    .route("/customers/{id}", web::get().to(handlers::get_customer))
-                    .route("/customers/{id}", web::put().to(handlers::update_customer))
-                    .route("/customers/{id}", web::delete().to(handlers::delete_customer))
-                    .route("/customers/{id}/orders", web::get().to(handlers::get_customer_orders))
-                    
-                    .route("/suppliers", web::get().to(handlers::get_suppliers))
-                    .route("/suppliers", web::post().to(handlers::create_supplier))
-                    .route("/suppliers/{id}", web::get().to(handlers::get_supplier))
-                    .route("/suppliers/{id}", web::put().to(handlers::update_supplier))
-                    .route("/suppliers/{id}", web::delete().to(handlers::delete_supplier))
-                    .route("/suppliers/{id}/products", web::get().to(handlers::get_supplier_products))
-                    
-                    .route("/products", web::get().to(handlers::get_products))
-                    .route("/products", web::post().to(handlers::create_product))
-                    .route("/products/{id}", web::get().to(handlers::get_product))
-                    .route("/products/{id}", web::put().to(handlers::update_product))
-                    .route("/products/{id}", web::delete().to(handlers::delete_product))
-                    
-                    .route("/orders", web::get().to(handlers::get_orders))
-                    .route("/orders", web::post().to(handlers::create_order))
-                    .route("/orders/{id}", web::get().to(handlers::get_order))
-                    .route("/orders/{id}", web::put().to(handlers::update_order))
-                    .route("/orders/{id}", web::delete().to(handlers::delete_order))
-                    .route("/orders/{id}/details", web::get().to(handlers::get_order_details))
-                    
-                    .route("/shipments", web::get().to(handlers::get_shipments))
-                    .route("/shipments", web::post().to(handlers::create_shipment))
-                    .route("/shipments/{id}", web::get().to(handlers::get_shipment))
-                    .route("/shipments/{id}", web::put().to(handlers::update_shipment))
-                    .route("/shipments/{id}", web::delete().to(handlers::delete_shipment))
-                    .route("/shipments/{id}/details", web::get().to(handlers::get_shipment_details))
+                    .wrap(from_fn(idempotency::replay))
+                    .wrap(from_fn(auth::authenticate))
+                    .route("/openapi.json", web::get().to(openapi::openapi_json))
+                    .route("/events", web::get().to(handlers::stream_events))
+                    .service(Scalar::with_url("/docs", openapi::ApiDoc::openapi()))
+                    .service(
+                        web::scope("/auth")
+                            .route("/login", web::post().to(handlers::login))
+                            .route("/logout", web::post().to(handlers::logout))
+                            .route("/me", web::get().to(handlers::get_current_user))
+                    )
+                    .service(
+                        web::scope("/users")
+                            .wrap(from_fn(|req, next| auth::authorize(auth::ADMIN_ONLY, req, next)))
+                            .route("", web::get().to(handlers::get_users))
+                            .route("", web::post().to(handlers::create_user))
+                            .route("/{id}", web::get().to(handlers::get_user))
+                            .route("/{id}", web::put().to(handlers::update_user))
+                            .route("/{id}", web::delete().to(handlers::delete_user))
+                    )
+                    .service(
+                        web::scope("/categories")
+                            .wrap(from_fn(|req, next| auth::authorize(auth::CATALOG, req, next)))
+                            .route("", web::get().to(handlers::get_categories))
+                            .route("", web::post().to(handlers::create_category))
+                            .route("/batch", web::post().to(handlers::batch_categories))
+                            .route("/{id}", web::get().to(handlers::get_category))
+                            .route("/{id}", web::put().to(handlers::update_category))
+                            .route("/{id}", web::delete().to(handlers::delete_category))
+                            .service(
+                                web::resource("/{id}/restore")
+                                    .wrap(from_fn(|req, next| auth::authorize(auth::ADMIN_ONLY, req, next)))
+                                    .route(web::post().to(handlers::restore_category))
+                            )
+                    )
+                    .service(
+                        web::scope("/customers")
+                            .wrap(from_fn(|req, next| auth::authorize(auth::CUSTOMERS, req, next)))
+                            .route("", web::get().to(handlers::get_customers))
+                            .route("", web::post().to(handlers::create_customer))
+                            .route("/batch", web::post().to(handlers::batch_customers))
+                            .route("/import", web::post().to(handlers::import_customers))
+                            .route("/export.csv", web::get().to(handlers::export_customers))
+                            .route("/{id}", web::get().to(handlers::get_customer))
+                            .route("/{id}", web::put().to(handlers::update_customer))
+                            .route("/{id}", web::delete().to(handlers::delete_customer))
+                            .service(
+                                web::resource("/{id}/restore")
+                                    .wrap(from_fn(|req, next| auth::authorize(auth::ADMIN_ONLY, req, next)))
+                                    .route(web::post().to(handlers::restore_customer))
+                            )
+                            .route("/{id}/orders", web::get().to(handlers::get_customer_orders))
+                            .route("/{id}/export", web::get().to(handlers::export_customer))
+                            .route("/{id}/addresses", web::get().to(handlers::get_customer_addresses))
+                            .route("/{id}/addresses", web::post().to(handlers::create_customer_address))
+                            .route("/{id}/addresses/{address_id}", web::get().to(handlers::get_customer_address))
+                            .route("/{id}/addresses/{address_id}", web::put().to(handlers::update_customer_address))
+                            .route("/{id}/addresses/{address_id}", web::delete().to(handlers::delete_customer_address))
+                            .service(
+                                web::resource("/{id}/anonymize")
+                                    .wrap(from_fn(|req, next| auth::authorize(auth::ADMIN_ONLY, req, next)))
+                                    .route(web::post().to(handlers::anonymize_customer))
+                            )
+                    )
+                    .service(
+                        web::scope("/suppliers")
+                            .wrap(from_fn(|req, next| auth::authorize(auth::CATALOG, req, next)))
+                            .route("", web::get().to(handlers::get_suppliers))
+                            .route("", web::post().to(handlers::create_supplier))
+                            .route("/import", web::post().to(handlers::import_suppliers))
+                            .route("/export.csv", web::get().to(handlers::export_suppliers))
+                            .route("/{id}", web::get().to(handlers::get_supplier))
+                            .route("/{id}", web::put().to(handlers::update_supplier))
+                            .route("/{id}", web::delete().to(handlers::delete_supplier))
+                            .service(
+                                web::resource("/{id}/restore")
+                                    .wrap(from_fn(|req, next| auth::authorize(auth::ADMIN_ONLY, req, next)))
+                                    .route(web::post().to(handlers::restore_supplier))
+                            )
+                            .route("/{id}/products", web::get().to(handlers::get_supplier_products))
+                            .route("/{id}/discrepancies", web::get().to(handlers::get_supplier_discrepancies))
+                    )
+                    .service(
+                        web::scope("/products")
+                            .wrap(from_fn(|req, next| auth::authorize(auth::CATALOG, req, next)))
+                            .route("", web::get().to(handlers::get_products))
+                            .route("", web::post().to(handlers::create_product))
+                            .route("/batch", web::post().to(handlers::batch_products))
+                            .route("/import", web::post().to(handlers::import_products))
+                            .route("/export.csv", web::get().to(handlers::export_products))
+                            .route("/search", web::get().to(handlers::search_products))
+                            .route("/{id}", web::get().to(handlers::get_product))
+                            .route("/{id}", web::put().to(handlers::update_product))
+                            .route("/{id}", web::delete().to(handlers::delete_product))
+                            .service(
+                                web::resource("/{id}/restore")
+                                    .wrap(from_fn(|req, next| auth::authorize(auth::ADMIN_ONLY, req, next)))
+                                    .route(web::post().to(handlers::restore_product))
+                            )
+                            .route("/{id}/movements", web::get().to(handlers::get_product_movements))
+                    )
+                    .service(
+                        web::scope("/orders")
+                            .wrap(from_fn(|req, next| auth::authorize(auth::ORDERS, req, next)))
+                            .route("", web::get().to(handlers::get_orders))
+                            .route("", web::post().to(handlers::create_order))
+                            .route("/{id}", web::get().to(handlers::get_order))
+                            .route("/{id}", web::put().to(handlers::update_order))
+                            .route("/{id}", web::delete().to(handlers::delete_order))
+                            .route("/{id}/details", web::get().to(handlers::get_order_details))
+                            .route("/{id}/confirm", web::post().to(handlers::confirm_order))
+                            .route("/{id}/ship", web::post().to(handlers::ship_order))
+                            .route("/{id}/deliver", web::post().to(handlers::deliver_order))
+                            .route("/{id}/cancel", web::post().to(handlers::cancel_order))
+                    )
+                    .service(
+                        web::scope("/shipments")
+                            .wrap(from_fn(|req, next| auth::authorize(auth::SHIPMENTS, req, next)))
+                            .route("", web::get().to(handlers::get_shipments))
+                            .route("", web::post().to(handlers::create_shipment))
+                            .route("/{id}", web::get().to(handlers::get_shipment))
+                            .route("/{id}", web::put().to(handlers::update_shipment))
+                            .route("/{id}", web::delete().to(handlers::delete_shipment))
+                            .route("/{id}/details", web::get().to(handlers::get_shipment_details))
+                            .route("/{id}/receive", web::post().to(handlers::receive_shipment))
+                    )
+                    .service(
+                        web::scope("/inventory")
+                            .wrap(from_fn(|req, next| auth::authorize(auth::CATALOG, req, next)))
+                            .route("/reconciliation", web::get().to(handlers::get_inventory_reconciliation))
+                    )
+                    .service(
+                        web::scope("/webhooks")
+                            .wrap(from_fn(|req, next| auth::authorize(auth::ADMIN_ONLY, req, next)))
+                            .route("", web::get().to(handlers::get_webhooks))
+                            .route("", web::post().to(handlers::create_webhook))
+                            .route("/{id}", web::get().to(handlers::get_webhook))
+                            .route("/{id}", web::put().to(handlers::update_webhook))
+                            .route("/{id}", web::delete().to(handlers::delete_webhook))
+                            .route("/{id}/deliveries", web::get().to(handlers::get_webhook_deliveries))
+                            .route("/deliveries/{id}/retry", web::post().to(handlers::retry_webhook_delivery))
+                    )
+                    .service(
+                        web::scope("/audit")
+                            .wrap(from_fn(|req, next| auth::authorize(auth::ADMIN_ONLY, req, next)))
+                            .route("", web::get().to(handlers::get_audit_log))
+                    )
             )
     })
     .listen(tcp_listener)?
@@ -1,9 +1,11 @@
-use std::net::TcpListener;
+use std::{net::TcpListener, time::Duration};
 
 use crud_shop_slop::{
     config::read_config,
     db::*,
+    services::{AuthService, ChangeService, IdempotencyService, InventoryService, OutboxService, WebhookService},
     start_server,
+    state::AppState,
 };
 
 #[actix_web::main]
@@ -24,7 +26,27 @@ async fn main() -> Result<(), std::io::Error> {
         .expect("Failed to connect to database");
 
         migrate(&pool).await.expect("Failed to run migrations on database");
-        start_server(listener, pool.into())
+        let admin = config.auth.admin_credentials().expect("Failed to read admin password file");
+        AuthService::ensure_admin(&pool, admin)
+            .await
+            .expect("Failed to create initial admin user");
+        actix_web::rt::spawn(AuthService::run_purge(
+            pool.clone(),
+            Duration::from_secs(config.auth.session_purge_interval_secs),
+        ));
+        actix_web::rt::spawn(InventoryService::run_reconciliation(
+            pool.clone(),
+            Duration::from_secs(config.inventory.reconcile_interval_secs),
+        ));
+        actix_web::rt::spawn(IdempotencyService::run_purge(
+            pool.clone(),
+            Duration::from_secs(config.idempotency.purge_interval_secs),
+        ));
+        actix_web::rt::spawn(OutboxService::run_relay(pool.clone(), config.outbox));
+        actix_web::rt::spawn(WebhookService::run_dispatcher(pool.clone(), config.webhooks));
+        let state = AppState::new(pool.clone(), config.auth, config.idempotency);
+        actix_web::rt::spawn(ChangeService::run_listener(pool, state.changes.clone()));
+        start_server(listener, state)
         .expect("Failed to start server")
         .await
 }
@@ -0,0 +1,55 @@
+pub use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+CREATE TABLE InventoryMovements (
+    movement_id SERIAL PRIMARY KEY,
+    product_id INT NOT NULL,
+    delta INT NOT NULL CHECK (delta <> 0),
+    reason VARCHAR(20) NOT NULL CHECK (reason IN ('sale', 'receipt', 'adjustment', 'return', 'cancel')),
+    order_id INT,
+    shipment_id INT,
+    actor VARCHAR(100),
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    CONSTRAINT fk_movement_product FOREIGN KEY (product_id)
+        REFERENCES Products (product_id)
+        ON DELETE CASCADE,
+    CONSTRAINT fk_movement_order FOREIGN KEY (order_id)
+        REFERENCES Orders (order_id)
+        ON DELETE SET NULL,
+    CONSTRAINT fk_movement_shipment FOREIGN KEY (shipment_id)
+        REFERENCES Shipments (shipment_id)
+        ON DELETE SET NULL
+);
+
+CREATE INDEX idx_movement_product ON InventoryMovements (product_id, created_at);
+
+-- Open the ledger with the stock that exists today
+INSERT INTO InventoryMovements (product_id, delta, reason, actor)
+SELECT product_id, stock_quantity, 'adjustment', 'migration'
+FROM Products
+WHERE stock_quantity <> 0;"
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+DROP TABLE IF EXISTS InventoryMovements CASCADE;
+",
+        )
+        .await?;
+        Ok(())
+    }
+}
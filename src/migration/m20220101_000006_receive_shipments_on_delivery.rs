@@ -0,0 +1,70 @@
+pub use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+-- Shipments still in transit were counted as stock when they were created;
+-- take them back out so they are added when they are actually received. Some of
+-- that stock may already have been sold, so a product gives back at most what it
+-- has on hand, shared out over its shipments in order, and the ledger records only
+-- what was taken.
+WITH taken AS (
+    SELECT si.product_id, si.shipment_id,
+        GREATEST(0, LEAST(
+            si.quantity,
+            p.stock_quantity - (SUM(si.quantity) OVER (PARTITION BY si.product_id ORDER BY si.shipment_id) - si.quantity)
+        )) AS quantity
+    FROM ShipmentItems si
+    JOIN Shipments s ON si.shipment_id = s.shipment_id
+    JOIN Products p ON si.product_id = p.product_id
+    WHERE s.status = 'in_transit'
+),
+movements AS (
+    INSERT INTO InventoryMovements (product_id, delta, reason, shipment_id, actor)
+    SELECT product_id, -quantity, 'adjustment', shipment_id, 'migration'
+    FROM taken
+    WHERE quantity > 0
+)
+UPDATE Products p
+SET stock_quantity = p.stock_quantity - sub.total_quantity
+FROM (
+    SELECT product_id, SUM(quantity) as total_quantity
+    FROM taken
+    GROUP BY product_id
+) sub
+WHERE p.product_id = sub.product_id;"
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+-- Put back exactly what was taken, as recorded in the ledger
+UPDATE Products p
+SET stock_quantity = p.stock_quantity - sub.total_delta
+FROM (
+    SELECT product_id, SUM(delta) as total_delta
+    FROM InventoryMovements
+    WHERE actor = 'migration' AND shipment_id IS NOT NULL
+    GROUP BY product_id
+) sub
+WHERE p.product_id = sub.product_id;
+
+DELETE FROM InventoryMovements
+WHERE actor = 'migration' AND shipment_id IS NOT NULL;
+",
+        )
+        .await?;
+        Ok(())
+    }
+}
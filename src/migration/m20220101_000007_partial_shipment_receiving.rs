@@ -0,0 +1,54 @@
+pub use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+-- More than the ordered quantity may arrive; the difference is reported as a discrepancy
+ALTER TABLE ShipmentItems
+ADD COLUMN received_quantity INT NOT NULL DEFAULT 0 CHECK (received_quantity >= 0);
+
+UPDATE ShipmentItems si
+SET received_quantity = si.quantity
+FROM Shipments s
+WHERE si.shipment_id = s.shipment_id
+  AND s.status = 'delivered';
+
+ALTER TABLE Shipments
+DROP CONSTRAINT shipments_status_check;
+
+ALTER TABLE Shipments
+ADD CONSTRAINT shipments_status_check CHECK (status IN ('in_transit', 'partially_received', 'delivered', 'cancelled'));"
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+UPDATE Shipments
+SET status = 'delivered'
+WHERE status = 'partially_received';
+
+ALTER TABLE Shipments
+DROP CONSTRAINT shipments_status_check;
+
+ALTER TABLE Shipments
+ADD CONSTRAINT shipments_status_check CHECK (status IN ('in_transit', 'delivered', 'cancelled'));
+
+ALTER TABLE ShipmentItems
+DROP COLUMN received_quantity;
+",
+        )
+        .await?;
+        Ok(())
+    }
+}
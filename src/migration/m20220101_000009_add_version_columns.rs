@@ -0,0 +1,37 @@
+pub use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+ALTER TABLE Categories ADD COLUMN version INT NOT NULL DEFAULT 1;
+ALTER TABLE Customers ADD COLUMN version INT NOT NULL DEFAULT 1;
+ALTER TABLE Suppliers ADD COLUMN version INT NOT NULL DEFAULT 1;
+ALTER TABLE Products ADD COLUMN version INT NOT NULL DEFAULT 1;
+ALTER TABLE Orders ADD COLUMN version INT NOT NULL DEFAULT 1;
+ALTER TABLE Shipments ADD COLUMN version INT NOT NULL DEFAULT 1;"
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+ALTER TABLE Categories DROP COLUMN version;
+ALTER TABLE Customers DROP COLUMN version;
+ALTER TABLE Suppliers DROP COLUMN version;
+ALTER TABLE Products DROP COLUMN version;
+ALTER TABLE Orders DROP COLUMN version;
+ALTER TABLE Shipments DROP COLUMN version;"
+        ).await?;
+        Ok(())
+    }
+}
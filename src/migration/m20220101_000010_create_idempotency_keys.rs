@@ -0,0 +1,42 @@
+pub use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+CREATE TABLE IdempotencyKeys (
+    user_id INT NOT NULL,
+    idempotency_key VARCHAR(255) NOT NULL,
+    request_hash CHAR(64) NOT NULL,
+    response_status INT,
+    response_headers TEXT,
+    response_body TEXT,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (user_id, idempotency_key),
+    CONSTRAINT fk_idempotency_key_user FOREIGN KEY (user_id)
+        REFERENCES Users (user_id)
+        ON DELETE CASCADE
+);
+
+CREATE INDEX idx_idempotency_keys_expires_at ON IdempotencyKeys (expires_at);"
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+DROP TABLE IF EXISTS IdempotencyKeys CASCADE;"
+        ).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+pub use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+CREATE TABLE Webhooks (
+    webhook_id SERIAL PRIMARY KEY,
+    url VARCHAR(500) NOT NULL,
+    event_type VARCHAR(50) NOT NULL
+        CHECK (event_type IN ('order.created', 'order.status_changed', 'shipment.received', 'product.low_stock', 'customer.created')),
+    secret VARCHAR(64) NOT NULL,
+    active BOOLEAN DEFAULT TRUE NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX idx_webhooks_event_type ON Webhooks (event_type) WHERE active;
+
+CREATE TABLE WebhookDeliveries (
+    delivery_id SERIAL PRIMARY KEY,
+    webhook_id INT NOT NULL,
+    event_type VARCHAR(50) NOT NULL,
+    payload TEXT NOT NULL,
+    status VARCHAR(20) DEFAULT 'pending' NOT NULL
+        CHECK (status IN ('pending', 'succeeded', 'failed')),
+    attempts INT DEFAULT 0 NOT NULL,
+    next_attempt_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_status_code INT,
+    last_error TEXT,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    delivered_at TIMESTAMPTZ,
+    CONSTRAINT fk_webhook_delivery_webhook FOREIGN KEY (webhook_id)
+        REFERENCES Webhooks (webhook_id)
+        ON DELETE CASCADE
+);
+
+CREATE INDEX idx_webhook_deliveries_webhook ON WebhookDeliveries (webhook_id, created_at);
+CREATE INDEX idx_webhook_deliveries_due ON WebhookDeliveries (next_attempt_at) WHERE status = 'pending';"
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+DROP TABLE IF EXISTS WebhookDeliveries CASCADE;
+DROP TABLE IF EXISTS Webhooks CASCADE;"
+        ).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+pub use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+CREATE TABLE Outbox (
+    outbox_id BIGSERIAL PRIMARY KEY,
+    event_type VARCHAR(50) NOT NULL,
+    aggregate_type VARCHAR(50) NOT NULL,
+    aggregate_id INT NOT NULL,
+    payload TEXT NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    published_at TIMESTAMPTZ,
+    -- The transaction that wrote the event, which orders events by when they can commit
+    xact_id XID8 DEFAULT pg_current_xact_id() NOT NULL
+);
+
+CREATE INDEX idx_outbox_unpublished ON Outbox (xact_id, outbox_id) WHERE published_at IS NULL;
+
+ALTER TABLE Webhooks DROP CONSTRAINT webhooks_event_type_check;
+ALTER TABLE Webhooks ADD CONSTRAINT webhooks_event_type_check
+    CHECK (event_type IN ('order.created', 'order.status_changed', 'shipment.created', 'shipment.status_changed',
+                          'shipment.received', 'product.low_stock', 'customer.created'));"
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+DELETE FROM Webhooks WHERE event_type IN ('shipment.created', 'shipment.status_changed');
+ALTER TABLE Webhooks DROP CONSTRAINT webhooks_event_type_check;
+ALTER TABLE Webhooks ADD CONSTRAINT webhooks_event_type_check
+    CHECK (event_type IN ('order.created', 'order.status_changed', 'shipment.received', 'product.low_stock', 'customer.created'));
+
+DROP TABLE IF EXISTS Outbox CASCADE;"
+        ).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,48 @@
+pub use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Names weigh more than descriptions when ranking matches
+        db.execute_unprepared(
+            "
+ALTER TABLE Products ADD COLUMN search_vector TSVECTOR;
+
+CREATE FUNCTION products_search_vector() RETURNS trigger AS $$
+BEGIN
+    NEW.search_vector :=
+        setweight(to_tsvector('english', coalesce(NEW.name, '')), 'A') ||
+        setweight(to_tsvector('english', coalesce(NEW.description, '')), 'B');
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER products_search_vector
+    BEFORE INSERT OR UPDATE OF name, description ON Products
+    FOR EACH ROW EXECUTE FUNCTION products_search_vector();
+
+-- Fills in existing rows through the trigger
+UPDATE Products SET name = name;
+
+CREATE INDEX idx_products_search_vector ON Products USING GIN (search_vector);"
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+DROP TRIGGER IF EXISTS products_search_vector ON Products;
+DROP FUNCTION IF EXISTS products_search_vector();
+ALTER TABLE Products DROP COLUMN search_vector;"
+        ).await?;
+        Ok(())
+    }
+}
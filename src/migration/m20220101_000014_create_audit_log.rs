@@ -0,0 +1,37 @@
+pub use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+CREATE TABLE AuditLog (
+    audit_id BIGSERIAL PRIMARY KEY,
+    entity VARCHAR(20) NOT NULL CHECK (entity IN ('category', 'customer', 'supplier', 'product', 'order',
+                                                  'shipment', 'user', 'webhook')),
+    entity_id INT NOT NULL,
+    action VARCHAR(10) NOT NULL CHECK (action IN ('create', 'update', 'delete')),
+    actor VARCHAR(50),
+    request_id VARCHAR(64),
+    changes TEXT NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX idx_auditlog_entity ON AuditLog (entity, entity_id, audit_id);
+CREATE INDEX idx_auditlog_actor ON AuditLog (actor, audit_id);"
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared("DROP TABLE IF EXISTS AuditLog CASCADE;").await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+pub use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+ALTER TABLE Categories ADD COLUMN deleted_at TIMESTAMPTZ;
+ALTER TABLE Suppliers ADD COLUMN deleted_at TIMESTAMPTZ;
+ALTER TABLE Customers ADD COLUMN deleted_at TIMESTAMPTZ;
+ALTER TABLE Products ADD COLUMN deleted_at TIMESTAMPTZ;
+
+-- A deleted category's name and a deleted customer's email may be taken again.
+ALTER TABLE Categories DROP CONSTRAINT categories_name_key;
+CREATE UNIQUE INDEX categories_name_key ON Categories (name) WHERE deleted_at IS NULL;
+ALTER TABLE Customers DROP CONSTRAINT customers_email_key;
+CREATE UNIQUE INDEX customers_email_key ON Customers (email) WHERE deleted_at IS NULL;"
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+DELETE FROM Products WHERE deleted_at IS NOT NULL;
+DELETE FROM Customers WHERE deleted_at IS NOT NULL;
+DELETE FROM Suppliers WHERE deleted_at IS NOT NULL;
+DELETE FROM Categories WHERE deleted_at IS NOT NULL;
+
+DROP INDEX customers_email_key;
+ALTER TABLE Customers ADD CONSTRAINT customers_email_key UNIQUE (email);
+DROP INDEX categories_name_key;
+ALTER TABLE Categories ADD CONSTRAINT categories_name_key UNIQUE (name);
+
+ALTER TABLE Products DROP COLUMN deleted_at;
+ALTER TABLE Customers DROP COLUMN deleted_at;
+ALTER TABLE Suppliers DROP COLUMN deleted_at;
+ALTER TABLE Categories DROP COLUMN deleted_at;"
+        ).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+pub use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+ALTER TABLE Orders DROP CONSTRAINT fk_order_customer,
+    ADD CONSTRAINT fk_order_customer FOREIGN KEY (customer_id)
+        REFERENCES Customers (customer_id)
+        ON DELETE RESTRICT;
+
+ALTER TABLE Shipments DROP CONSTRAINT fk_shipment_supplier,
+    ADD CONSTRAINT fk_shipment_supplier FOREIGN KEY (supplier_id)
+        REFERENCES Suppliers (supplier_id)
+        ON DELETE RESTRICT;
+
+ALTER TABLE InventoryMovements DROP CONSTRAINT fk_movement_product,
+    ADD CONSTRAINT fk_movement_product FOREIGN KEY (product_id)
+        REFERENCES Products (product_id)
+        ON DELETE RESTRICT;"
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+ALTER TABLE InventoryMovements DROP CONSTRAINT fk_movement_product,
+    ADD CONSTRAINT fk_movement_product FOREIGN KEY (product_id)
+        REFERENCES Products (product_id)
+        ON DELETE CASCADE;
+
+ALTER TABLE Shipments DROP CONSTRAINT fk_shipment_supplier,
+    ADD CONSTRAINT fk_shipment_supplier FOREIGN KEY (supplier_id)
+        REFERENCES Suppliers (supplier_id)
+        ON DELETE CASCADE;
+
+ALTER TABLE Orders DROP CONSTRAINT fk_order_customer,
+    ADD CONSTRAINT fk_order_customer FOREIGN KEY (customer_id)
+        REFERENCES Customers (customer_id)
+        ON DELETE CASCADE;"
+        ).await?;
+        Ok(())
+    }
+}
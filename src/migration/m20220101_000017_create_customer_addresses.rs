@@ -0,0 +1,56 @@
+pub use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+CREATE TABLE CustomerAddresses (
+    address_id SERIAL PRIMARY KEY,
+    customer_id INT NOT NULL,
+    line1 VARCHAR(200) NOT NULL,
+    line2 VARCHAR(200),
+    city VARCHAR(100) NOT NULL,
+    region VARCHAR(100),
+    postal_code VARCHAR(20),
+    country VARCHAR(2) NOT NULL CHECK (country ~ '^[A-Z]{2}$'),
+    is_default_shipping BOOLEAN NOT NULL DEFAULT FALSE,
+    is_default_billing BOOLEAN NOT NULL DEFAULT FALSE,
+    version INT NOT NULL DEFAULT 1,
+    CONSTRAINT fk_address_customer FOREIGN KEY (customer_id)
+        REFERENCES Customers (customer_id)
+        ON DELETE CASCADE
+);
+
+CREATE INDEX idx_customeraddresses_customer ON CustomerAddresses (customer_id);
+-- At most one default of each kind per customer
+CREATE UNIQUE INDEX customeraddresses_default_shipping_key ON CustomerAddresses (customer_id) WHERE is_default_shipping;
+CREATE UNIQUE INDEX customeraddresses_default_billing_key ON CustomerAddresses (customer_id) WHERE is_default_billing;
+
+ALTER TABLE AuditLog DROP CONSTRAINT auditlog_entity_check,
+    ADD CONSTRAINT auditlog_entity_check CHECK (entity IN ('category', 'customer', 'customer_address', 'supplier',
+                                                           'product', 'order', 'shipment', 'user', 'webhook'));"
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "
+DELETE FROM AuditLog WHERE entity = 'customer_address';
+ALTER TABLE AuditLog DROP CONSTRAINT auditlog_entity_check,
+    ADD CONSTRAINT auditlog_entity_check CHECK (entity IN ('category', 'customer', 'supplier', 'product', 'order',
+                                                           'shipment', 'user', 'webhook'));
+
+DROP TABLE IF EXISTS CustomerAddresses CASCADE;"
+        ).await?;
+        Ok(())
+    }
+}
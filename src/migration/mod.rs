@@ -4,6 +4,19 @@ mod m20220101_000001_create_table;
 mod m20220101_000002_insert_mock_data;
 mod m20220101_000003_change_time;
 mod m20220101_000004_change_order_time;
+mod m20220101_000005_create_inventory_movements;
+mod m20220101_000006_receive_shipments_on_delivery;
+mod m20220101_000007_partial_shipment_receiving;
+mod m20220101_000008_create_users_and_sessions;
+mod m20220101_000009_add_version_columns;
+mod m20220101_000010_create_idempotency_keys;
+mod m20220101_000011_create_webhooks;
+mod m20220101_000012_create_outbox;
+mod m20220101_000013_add_product_search;
+mod m20220101_000014_create_audit_log;
+mod m20220101_000015_add_soft_delete;
+mod m20220101_000016_restrict_history_deletes;
+mod m20220101_000017_create_customer_addresses;
 
 pub struct Migrator;
 
@@ -14,7 +27,20 @@ impl MigratorTrait for Migrator {
             Box::new(m20220101_000001_create_table::Migration),
             Box::new(m20220101_000002_insert_mock_data::Migration),
             Box::new(m20220101_000003_change_time::Migration),
-            Box::new(m20220101_000004_change_order_time::Migration)
+            Box::new(m20220101_000004_change_order_time::Migration),
+            Box::new(m20220101_000005_create_inventory_movements::Migration),
+            Box::new(m20220101_000006_receive_shipments_on_delivery::Migration),
+            Box::new(m20220101_000007_partial_shipment_receiving::Migration),
+            Box::new(m20220101_000008_create_users_and_sessions::Migration),
+            Box::new(m20220101_000009_add_version_columns::Migration),
+            Box::new(m20220101_000010_create_idempotency_keys::Migration),
+            Box::new(m20220101_000011_create_webhooks::Migration),
+            Box::new(m20220101_000012_create_outbox::Migration),
+            Box::new(m20220101_000013_add_product_search::Migration),
+            Box::new(m20220101_000014_create_audit_log::Migration),
+            Box::new(m20220101_000015_add_soft_delete::Migration),
+            Box::new(m20220101_000016_restrict_history_deletes::Migration),
+            Box::new(m20220101_000017_create_customer_addresses::Migration),
         ]
     }
 }
@@ -0,0 +1,162 @@
+use actix_web::HttpResponse;
+use utoipa::{
+    Modify, OpenApi,
+    openapi::path::{ParameterBuilder, ParameterIn},
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    openapi::{ObjectBuilder, Required, Type},
+};
+
+use crate::handlers;
+
+/// OpenAPI document for everything under `/api`, built from the handler annotations.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Slopshop API"),
+    servers((url = "/api")),
+    components(schemas(crate::dtos::EntityChange)),
+    paths(
+        handlers::login,
+        handlers::logout,
+        handlers::get_current_user,
+        handlers::stream_events,
+        handlers::get_users,
+        handlers::get_user,
+        handlers::create_user,
+        handlers::update_user,
+        handlers::delete_user,
+        handlers::get_categories,
+        handlers::get_category,
+        handlers::create_category,
+        handlers::update_category,
+        handlers::delete_category,
+        handlers::restore_category,
+        handlers::batch_categories,
+        handlers::get_customers,
+        handlers::get_customer,
+        handlers::create_customer,
+        handlers::update_customer,
+        handlers::delete_customer,
+        handlers::restore_customer,
+        handlers::get_customer_orders,
+        handlers::export_customer,
+        handlers::anonymize_customer,
+        handlers::get_customer_addresses,
+        handlers::get_customer_address,
+        handlers::create_customer_address,
+        handlers::update_customer_address,
+        handlers::delete_customer_address,
+        handlers::import_customers,
+        handlers::export_customers,
+        handlers::batch_customers,
+        handlers::get_suppliers,
+        handlers::get_supplier,
+        handlers::create_supplier,
+        handlers::update_supplier,
+        handlers::delete_supplier,
+        handlers::restore_supplier,
+        handlers::get_supplier_products,
+        handlers::get_supplier_discrepancies,
+        handlers::import_suppliers,
+        handlers::export_suppliers,
+        handlers::get_products,
+        handlers::search_products,
+        handlers::get_product,
+        handlers::create_product,
+        handlers::update_product,
+        handlers::delete_product,
+        handlers::restore_product,
+        handlers::get_product_movements,
+        handlers::import_products,
+        handlers::export_products,
+        handlers::batch_products,
+        handlers::get_inventory_reconciliation,
+        handlers::get_orders,
+        handlers::get_order,
+        handlers::create_order,
+        handlers::update_order,
+        handlers::delete_order,
+        handlers::get_order_details,
+        handlers::confirm_order,
+        handlers::ship_order,
+        handlers::deliver_order,
+        handlers::cancel_order,
+        handlers::get_shipments,
+        handlers::get_shipment,
+        handlers::create_shipment,
+        handlers::update_shipment,
+        handlers::receive_shipment,
+        handlers::delete_shipment,
+        handlers::get_shipment_details,
+        handlers::get_webhooks,
+        handlers::get_webhook,
+        handlers::create_webhook,
+        handlers::update_webhook,
+        handlers::delete_webhook,
+        handlers::get_webhook_deliveries,
+        handlers::retry_webhook_delivery,
+        handlers::get_audit_log,
+    ),
+    modifiers(&BearerAuth, &IdempotencyKey),
+    security(("bearer" = [])),
+    tags(
+        (name = "auth", description = "Sessions"),
+        (name = "events", description = "Live change notifications"),
+        (name = "users", description = "User accounts, admin only"),
+        (name = "categories", description = "Product categories"),
+        (name = "customers", description = "Customers"),
+        (name = "suppliers", description = "Suppliers"),
+        (name = "products", description = "Product catalog"),
+        (name = "inventory", description = "Stock ledger"),
+        (name = "orders", description = "Customer orders"),
+        (name = "shipments", description = "Inbound supplier shipments"),
+        (name = "webhooks", description = "Event notifications to other systems, admin only"),
+        (name = "audit", description = "Who changed what, admin only"),
+    )
+)]
+pub struct ApiDoc;
+
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("Token returned by `POST /auth/login`"))
+                    .build(),
+            ),
+        );
+    }
+}
+
+struct IdempotencyKey;
+
+impl Modify for IdempotencyKey {
+    /// Documents the optional `Idempotency-Key` header on every POST but login.
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        for (path, item) in openapi.paths.paths.iter_mut() {
+            let Some(operation) = item.post.as_mut().filter(|_| path != "/auth/login") else {
+                continue;
+            };
+            operation.parameters.get_or_insert_with(Vec::new).push(
+                ParameterBuilder::new()
+                    .name("Idempotency-Key")
+                    .parameter_in(ParameterIn::Header)
+                    .required(Required::False)
+                    .description(Some(
+                        "Makes the request safe to retry: the first response is replayed for \
+                         retries with the same body, and a different body is rejected with 409",
+                    ))
+                    .schema(Some(ObjectBuilder::new().schema_type(Type::String).max_length(Some(255))))
+                    .build(),
+            );
+        }
+    }
+}
+
+pub async fn openapi_json() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
@@ -0,0 +1,99 @@
+use actix_web::HttpRequest;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, Order, PaginatorTrait, Select};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::error::AppError;
+
+const DEFAULT_PER_PAGE: u64 = 50;
+const MAX_PER_PAGE: u64 = 1000;
+/// Highest page served, which keeps `page * per_page` and the row offset far from overflowing.
+const MAX_PAGE: u64 = 1_000_000;
+
+/// `?page=&per_page=&sort=` accepted by every collection endpoint.
+/// `sort` names a field, prefixed with `-` for descending order.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageParams {
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
+    pub sort: Option<String>,
+}
+
+impl PageParams {
+    pub fn page(&self) -> u64 {
+        self.page.unwrap_or(1).clamp(1, MAX_PAGE)
+    }
+
+    pub fn per_page(&self) -> u64 {
+        self.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE)
+    }
+
+    /// Resolves `sort` against the fields a collection allows sorting by.
+    pub fn sort_by<C: ColumnTrait>(&self, columns: &[(&str, C)]) -> Result<Option<(C, Order)>, AppError> {
+        let Some(sort) = self.sort.as_deref() else {
+            return Ok(None);
+        };
+        let (field, order) = match sort.strip_prefix('-') {
+            Some(field) => (field, Order::Desc),
+            None => (sort, Order::Asc),
+        };
+        
+        columns
+            .iter()
+            .find(|(name, _)| *name == field)
+            .map(|(_, column)| Some((*column, order)))
+            .ok_or_else(|| {
+                let allowed: Vec<&str> = columns.iter().map(|(name, _)| *name).collect();
+                AppError::validation(format!(
+                    "Cannot sort by '{}', expected one of: {}",
+                    field,
+                    allowed.join(", ")
+                ))
+            })
+    }
+
+    /// Fetches the requested page of `select` together with the total row count.
+    pub async fn fetch<E>(&self, db: &DatabaseConnection, select: Select<E>) -> Result<(Vec<E::Model>, u64), AppError>
+    where
+        E: EntityTrait,
+        E::Model: Sync,
+    {
+        let paginator = select.paginate(db, self.per_page());
+        let total = paginator.num_items().await?;
+        let items = paginator.fetch_page(self.page() - 1).await?;
+        Ok((items, total))
+    }
+}
+
+/// Envelope returned by every collection endpoint.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: u64,
+    pub page: u64,
+    pub per_page: u64,
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+impl<T> Page<T> {
+    pub fn new(items: Vec<T>, total: u64, params: &PageParams, req: &HttpRequest) -> Self {
+        let page = params.page();
+        let per_page = params.per_page();
+        let next = (page.saturating_mul(per_page) < total).then(|| page_link(req, page + 1));
+        let prev = (page > 1).then(|| page_link(req, page - 1));
+        Self { items, total, page, per_page, next, prev }
+    }
+}
+
+/// The request's own URL with `page` replaced, keeping every other query parameter.
+fn page_link(req: &HttpRequest, page: u64) -> String {
+    let mut query: Vec<(String, String)> =
+        serde_urlencoded::from_str(req.query_string()).unwrap_or_default();
+    query.retain(|(key, _)| key != "page");
+    query.push(("page".to_string(), page.to_string()));
+    
+    let query = serde_urlencoded::to_string(&query).unwrap_or_default();
+    format!("{}?{}", req.path(), query)
+}
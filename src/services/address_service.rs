@@ -0,0 +1,195 @@
+use anyhow::Result;
+use sea_orm::sea_query::LockType;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, DatabaseTransaction, EntityTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait
+};
+use crate::db::{CustomerAddresses, Customers, customer_addresses, customers};
+use crate::db::audit_log::AuditEntity;
+use crate::dtos::*;
+use crate::concurrency::IfMatch;
+use crate::error::AppError;
+use crate::services::{AuditService, ChangeService, CustomerService};
+
+pub struct AddressService;
+
+impl AddressService {
+    pub async fn find_all(db: &DatabaseConnection, customer_id: i32) -> Result<Vec<customer_addresses::Model>, AppError> {
+        CustomerService::find_by_id(db, customer_id, false).await?;
+        Ok(CustomerAddresses::find()
+            .filter(customer_addresses::Column::CustomerId.eq(customer_id))
+            .order_by_asc(customer_addresses::Column::AddressId)
+            .all(db)
+            .await?)
+    }
+
+    pub async fn find_by_id(db: &DatabaseConnection, customer_id: i32, id: i32) -> Result<customer_addresses::Model, AppError> {
+        CustomerService::find_by_id(db, customer_id, false).await?;
+        CustomerAddresses::find_by_id(id)
+            .filter(customer_addresses::Column::CustomerId.eq(customer_id))
+            .one(db)
+            .await?
+            .ok_or(AppError::NotFound)
+    }
+
+    pub async fn create(db: &DatabaseConnection, customer_id: i32, dto: CustomerAddressCreate) -> Result<customer_addresses::Model, AppError> {
+        let txn = db.begin().await?;
+        Self::lock_customer(&txn, customer_id).await?;
+        Self::take_defaults(&txn, customer_id, None, dto.is_default_shipping, dto.is_default_billing).await?;
+        
+        let address = customer_addresses::ActiveModel {
+            customer_id: Set(customer_id),
+            line1: Set(dto.line1),
+            line2: Set(dto.line2),
+            city: Set(dto.city),
+            region: Set(dto.region),
+            postal_code: Set(dto.postal_code),
+            country: Set(dto.country),
+            is_default_shipping: Set(dto.is_default_shipping),
+            is_default_billing: Set(dto.is_default_billing),
+            ..Default::default()
+        };
+        let address = address.insert(&txn).await?;
+        ChangeService::notify(&txn, EntityKind::CustomerAddress, address.address_id, ChangeAction::Created).await?;
+        AuditService::record(&txn, AuditEntity::CustomerAddress, address.address_id, None, Some(&address)).await?;
+        
+        txn.commit().await?;
+        Ok(address)
+    }
+
+    pub async fn update(
+        db: &DatabaseConnection,
+        customer_id: i32,
+        id: i32,
+        if_match: &IfMatch,
+        dto: CustomerAddressUpdate,
+    ) -> Result<customer_addresses::Model, AppError> {
+        let txn = db.begin().await?;
+        Self::lock_customer(&txn, customer_id).await?;
+        
+        let address = CustomerAddresses::find_by_id(id)
+            .filter(customer_addresses::Column::CustomerId.eq(customer_id))
+            .lock(LockType::NoKeyUpdate)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if_match.check(address.version)?;
+        let before = address.clone();
+        Self::take_defaults(
+            &txn,
+            customer_id,
+            Some(id),
+            dto.is_default_shipping == Some(true),
+            dto.is_default_billing == Some(true),
+        ).await?;
+        
+        let mut address: customer_addresses::ActiveModel = address.into();
+        
+        if let Some(line1) = dto.line1 {
+            address.line1 = Set(line1);
+        }
+        if let Some(line2) = dto.line2 {
+            address.line2 = Set(Some(line2));
+        }
+        if let Some(city) = dto.city {
+            address.city = Set(city);
+        }
+        if let Some(region) = dto.region {
+            address.region = Set(Some(region));
+        }
+        if let Some(postal_code) = dto.postal_code {
+            address.postal_code = Set(Some(postal_code));
+        }
+        if let Some(country) = dto.country {
+            address.country = Set(country);
+        }
+        if let Some(is_default_shipping) = dto.is_default_shipping {
+            address.is_default_shipping = Set(is_default_shipping);
+        }
+        if let Some(is_default_billing) = dto.is_default_billing {
+            address.is_default_billing = Set(is_default_billing);
+        }
+        
+        let address = address.update(&txn).await?;
+        ChangeService::notify(&txn, EntityKind::CustomerAddress, id, ChangeAction::Updated).await?;
+        AuditService::record(&txn, AuditEntity::CustomerAddress, id, Some(&before), Some(&address)).await?;
+        
+        txn.commit().await?;
+        Ok(address)
+    }
+
+    /// Deletes the address for good. Orders keep the copy they were placed with.
+    pub async fn delete<C: ConnectionTrait + TransactionTrait>(db: &C, customer_id: i32, id: i32, if_match: &IfMatch) -> Result<(), AppError> {
+        let txn = db.begin().await?;
+        
+        let address = CustomerAddresses::find_by_id(id)
+            .filter(customer_addresses::Column::CustomerId.eq(customer_id))
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if_match.check(address.version)?;
+        AuditService::record(&txn, AuditEntity::CustomerAddress, id, Some(&address), None).await?;
+        
+        let address: customer_addresses::ActiveModel = address.into();
+        address.delete(&txn).await?;
+        ChangeService::notify(&txn, EntityKind::CustomerAddress, id, ChangeAction::Deleted).await?;
+        
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Locks the customer, so that changes to which of their addresses are the defaults
+    /// happen one at a time.
+    async fn lock_customer(txn: &DatabaseTransaction, customer_id: i32) -> Result<(), AppError> {
+        Customers::find_by_id(customer_id)
+            .filter(customers::Column::DeletedAt.is_null())
+            .lock(LockType::NoKeyUpdate)
+            .one(txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        Ok(())
+    }
+
+    /// Takes the default shipping and billing flags, where set, off the customer's addresses
+    /// other than `except`.
+    async fn take_defaults(
+        txn: &DatabaseTransaction,
+        customer_id: i32,
+        except: Option<i32>,
+        shipping: bool,
+        billing: bool,
+    ) -> Result<(), AppError> {
+        if !shipping && !billing {
+            return Ok(());
+        }
+        let mut defaults = Condition::any();
+        if shipping {
+            defaults = defaults.add(customer_addresses::Column::IsDefaultShipping.eq(true));
+        }
+        if billing {
+            defaults = defaults.add(customer_addresses::Column::IsDefaultBilling.eq(true));
+        }
+        let mut query = CustomerAddresses::find()
+            .filter(customer_addresses::Column::CustomerId.eq(customer_id))
+            .filter(defaults);
+        if let Some(except) = except {
+            query = query.filter(customer_addresses::Column::AddressId.ne(except));
+        }
+        
+        for address in query.all(txn).await? {
+            let before = address.clone();
+            let mut address: customer_addresses::ActiveModel = address.into();
+            if shipping {
+                address.is_default_shipping = Set(false);
+            }
+            if billing {
+                address.is_default_billing = Set(false);
+            }
+            let address = address.update(txn).await?;
+            ChangeService::notify(txn, EntityKind::CustomerAddress, address.address_id, ChangeAction::Updated).await?;
+            AuditService::record(txn, AuditEntity::CustomerAddress, address.address_id, Some(&before), Some(&address)).await?;
+        }
+        Ok(())
+    }
+}
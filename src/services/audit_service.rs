@@ -0,0 +1,130 @@
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, Set
+};
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use chrono::Utc;
+use crate::auth::current_actor;
+use crate::db::{AuditLog, audit_log};
+use crate::db::audit_log::{AuditAction, AuditEntity};
+use crate::dtos::AuditFilter;
+use crate::error::AppError;
+use crate::pagination::PageParams;
+use crate::request_id;
+
+pub struct AuditService;
+
+impl AuditService {
+    /// Logs a change to one record on `conn`, which should be the transaction making it.
+    /// `before` is empty for a create and `after` for a delete.
+    pub async fn record<C: ConnectionTrait, M: Serialize>(
+        conn: &C,
+        entity: AuditEntity,
+        entity_id: i32,
+        before: Option<&M>,
+        after: Option<&M>,
+    ) -> Result<(), AppError> {
+        let action = match (before, after) {
+            (None, _) => AuditAction::Create,
+            (Some(_), Some(_)) => AuditAction::Update,
+            (Some(_), None) => AuditAction::Delete,
+        };
+        let snapshot = |model: Option<&M>| match model.map(serde_json::to_value).transpose() {
+            Ok(Some(Value::Object(fields))) => Ok(fields),
+            Ok(_) => Ok(Map::new()),
+            Err(_) => Err(AppError::Internal),
+        };
+        let changes = diff(snapshot(before)?, snapshot(after)?);
+
+        let entry = audit_log::ActiveModel {
+            entity: Set(entity),
+            entity_id: Set(entity_id),
+            action: Set(action),
+            actor: Set(current_actor()),
+            request_id: Set(request_id::current()),
+            changes: Set(changes.to_string()),
+            created_at: Set(Utc::now()),
+            ..Default::default()
+        };
+        entry.insert(conn).await?;
+        Ok(())
+    }
+
+    pub async fn find_all(db: &DatabaseConnection, filter: &AuditFilter, page: &PageParams) -> Result<(Vec<audit_log::Model>, u64), AppError> {
+        let mut query = AuditLog::find();
+        if let Some(entity) = filter.entity {
+            query = query.filter(audit_log::Column::Entity.eq(entity));
+        }
+        if let Some(id) = filter.id {
+            query = query.filter(audit_log::Column::EntityId.eq(id));
+        }
+        if let Some(actor) = &filter.actor {
+            query = query.filter(audit_log::Column::Actor.eq(actor));
+        }
+
+        page.fetch(db, query.order_by_desc(audit_log::Column::AuditId)).await
+    }
+}
+
+/// The fields whose value differs between the two snapshots, as `{"field": {"old": .., "new": ..}}`.
+fn diff(before: Map<String, Value>, mut after: Map<String, Value>) -> Value {
+    let mut changes = Map::new();
+    for (field, old) in before {
+        let new = after.remove(&field).unwrap_or(Value::Null);
+        if old != new {
+            changes.insert(field, json!({ "old": old, "new": new }));
+        }
+    }
+    for (field, new) in after {
+        if !new.is_null() {
+            changes.insert(field, json!({ "old": Value::Null, "new": new }));
+        }
+    }
+    Value::Object(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::diff;
+    use serde_json::{Map, Value, json};
+
+    fn fields(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(fields) => fields,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn only_changed_fields_are_kept() {
+        let before = fields(json!({ "name": "Mouse", "price": "10.00", "version": 1 }));
+        let after = fields(json!({ "name": "Mouse", "price": "12.50", "version": 2 }));
+        assert_eq!(diff(before, after), json!({
+            "price": { "old": "10.00", "new": "12.50" },
+            "version": { "old": 1, "new": 2 },
+        }));
+    }
+
+    #[test]
+    fn a_create_lists_every_set_field_as_new() {
+        let after = fields(json!({ "name": "Mouse", "description": null }));
+        assert_eq!(diff(Map::new(), after), json!({
+            "name": { "old": null, "new": "Mouse" },
+        }));
+    }
+
+    #[test]
+    fn a_delete_lists_every_set_field_as_old() {
+        let before = fields(json!({ "name": "Mouse", "description": null }));
+        assert_eq!(diff(before, Map::new()), json!({
+            "name": { "old": "Mouse", "new": null },
+        }));
+    }
+
+    #[test]
+    fn nothing_changed_is_an_empty_object() {
+        let model = fields(json!({ "name": "Mouse" }));
+        assert_eq!(diff(model.clone(), model), json!({}));
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::Result;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter, Set
+};
+use chrono::{Duration, Utc};
+use sha2::{Digest, Sha256};
+use validator::Validate;
+use crate::db::{Sessions, Users, sessions, users};
+use crate::db::users::Role;
+use crate::dtos::*;
+use crate::error::AppError;
+
+pub struct AuthService;
+
+impl AuthService {
+    pub fn hash_password(password: &str) -> Result<String, AppError> {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|_| AppError::Internal)
+    }
+
+    fn verify_password(password: &str, password_hash: &str) -> bool {
+        PasswordHash::new(password_hash)
+            .map(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+            .unwrap_or(false)
+    }
+
+    /// Only the SHA-256 of a session token is stored, so a leaked table cannot be replayed.
+    fn hash_token(token: &str) -> String {
+        hex::encode(Sha256::digest(token.as_bytes()))
+    }
+
+    /// Checks the credentials and opens a session, returning the bearer token.
+    pub async fn login(
+        db: &DatabaseConnection,
+        dto: LoginRequest,
+        ttl: Duration,
+    ) -> Result<(String, sessions::Model, users::Model), AppError> {
+        let user = Users::find()
+            .filter(users::Column::Username.eq(dto.username))
+            .one(db)
+            .await?
+            .filter(|user| Self::verify_password(&dto.password, &user.password_hash))
+            .ok_or(AppError::Unauthorized)?;
+        
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let token = hex::encode(bytes);
+        
+        let now = Utc::now();
+        let session = sessions::ActiveModel {
+            token_hash: Set(Self::hash_token(&token)),
+            user_id: Set(user.user_id),
+            created_at: Set(now),
+            expires_at: Set(now + ttl),
+        };
+        let session = session.insert(db).await?;
+        
+        Ok((token, session, user))
+    }
+
+    /// Resolves a bearer token to the user owning an unexpired session.
+    pub async fn authenticate(db: &DatabaseConnection, token: &str) -> Result<users::Model, AppError> {
+        let (session, user) = Sessions::find_by_id(Self::hash_token(token))
+            .find_also_related(Users)
+            .one(db)
+            .await?
+            .ok_or(AppError::Unauthorized)?;
+        
+        if session.expires_at <= Utc::now() {
+            return Err(AppError::Unauthorized);
+        }
+        user.ok_or(AppError::Unauthorized)
+    }
+
+    pub async fn logout(db: &DatabaseConnection, token: &str) -> Result<(), AppError> {
+        Sessions::delete_by_id(Self::hash_token(token)).exec(db).await?;
+        Ok(())
+    }
+
+    /// Creates the configured admin account when no users exist yet. Without configured
+    /// credentials an empty users table is an error, as nobody could ever log in.
+    pub async fn ensure_admin(db: &DatabaseConnection, credentials: Option<(String, String)>) -> Result<(), AppError> {
+        if Users::find().count(db).await? > 0 {
+            return Ok(());
+        }
+        let Some((username, password)) = credentials else {
+            return Err(AppError::validation(
+                "no users exist yet, set SHOP_AUTH__ADMIN_USERNAME and SHOP_AUTH__ADMIN_PASSWORD to create the first admin",
+            ));
+        };
+        UserCreate { username: username.clone(), password: password.clone(), role: Role::Admin }.validate()?;
+
+        let admin = users::ActiveModel {
+            username: Set(username),
+            password_hash: Set(Self::hash_password(&password)?),
+            role: Set(Role::Admin),
+            created_at: Set(Utc::now()),
+            ..Default::default()
+        };
+        admin.insert(db).await?;
+        Ok(())
+    }
+
+    /// Deletes sessions that have expired.
+    pub async fn purge_expired(db: &DatabaseConnection) -> Result<u64, AppError> {
+        let result = Sessions::delete_many()
+            .filter(sessions::Column::ExpiresAt.lte(Utc::now()))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+
+    /// Periodically purges expired sessions; meant to be spawned once at startup.
+    pub async fn run_purge(db: DatabaseConnection, interval: std::time::Duration) {
+        let mut ticker = actix_web::rt::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(err) = Self::purge_expired(&db).await {
+                eprintln!("Purging expired sessions failed: {err}");
+            }
+        }
+    }
+}
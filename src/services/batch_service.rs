@@ -0,0 +1,90 @@
+use actix_web::{ResponseError, http::StatusCode};
+use sea_orm::{DatabaseConnection, DatabaseTransaction, TransactionTrait};
+use validator::Validate;
+use crate::dtos::*;
+use crate::error::{AppError, FieldError};
+
+/// Most operations accepted in one batch.
+const MAX_OPERATIONS: usize = 500;
+
+pub struct BatchService;
+
+impl BatchService {
+    /// Runs the operations in order in one transaction through `apply`, which returns the
+    /// id of the record it touched. In per-item mode each operation gets a savepoint so
+    /// that a failed one is undone without undoing the others.
+    pub async fn run<C: Validate, U: Validate>(
+        db: &DatabaseConnection,
+        request: BatchRequest<C, U>,
+        apply: impl AsyncFn(&DatabaseTransaction, BatchOperation<C, U>) -> Result<i32, AppError>,
+    ) -> Result<BatchResponse, AppError> {
+        if !(1..=MAX_OPERATIONS).contains(&request.operations.len()) {
+            return Err(AppError::Validation {
+                message: "1 field(s) failed validation".to_string(),
+                details: vec![FieldError {
+                    field: "operations".to_string(),
+                    message: format!("must contain between 1 and {} operations", MAX_OPERATIONS),
+                }],
+            });
+        }
+        let mode = request.mode;
+        let mut response = BatchResponse { mode, succeeded: 0, failed: 0, results: Vec::new() };
+
+        let txn = db.begin().await?;
+        for (index, operation) in request.operations.into_iter().enumerate() {
+            let (status, id) = match &operation {
+                BatchOperation::Create { .. } => (StatusCode::CREATED, None),
+                BatchOperation::Update { id, .. } => (StatusCode::OK, Some(*id)),
+                BatchOperation::Delete { id, .. } => (StatusCode::NO_CONTENT, Some(*id)),
+            };
+
+            let outcome = match mode {
+                BatchMode::Atomic => Self::apply_one(&txn, operation, &apply).await,
+                BatchMode::PerItem => {
+                    let savepoint = txn.begin().await?;
+                    let outcome = Self::apply_one(&savepoint, operation, &apply).await;
+                    match outcome {
+                        Ok(_) => savepoint.commit().await?,
+                        Err(_) => savepoint.rollback().await?,
+                    }
+                    outcome
+                }
+            };
+
+            let result = match outcome {
+                Ok(id) => BatchResult { index, status: status.as_u16(), id: Some(id), error: None },
+                Err(err) if mode == BatchMode::Atomic => {
+                    txn.rollback().await?;
+                    return Err(AppError::Operation { index, source: Box::new(err) });
+                }
+                Err(err) => BatchResult {
+                    index,
+                    status: err.status_code().as_u16(),
+                    id,
+                    error: Some(err.response_body()),
+                },
+            };
+            match result.error {
+                None => response.succeeded += 1,
+                Some(_) => response.failed += 1,
+            }
+            response.results.push(result);
+        }
+
+        txn.commit().await?;
+        Ok(response)
+    }
+
+    async fn apply_one<C: Validate, U: Validate>(
+        txn: &DatabaseTransaction,
+        operation: BatchOperation<C, U>,
+        apply: &impl AsyncFn(&DatabaseTransaction, BatchOperation<C, U>) -> Result<i32, AppError>,
+    ) -> Result<i32, AppError> {
+        match &operation {
+            BatchOperation::Create { data } => data.validate()?,
+            BatchOperation::Update { data, .. } => data.validate()?,
+            BatchOperation::Delete { .. } => {}
+        }
+        apply(txn, operation).await
+    }
+}
@@ -1,42 +1,68 @@
 use anyhow::Result;
+use sea_orm::sea_query::LockType;
 use sea_orm::{
-    ActiveModelTrait, DatabaseConnection, EntityTrait, QueryOrder, Set
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter, QuerySelect,
+    TransactionTrait, QueryOrder, Set
 };
+use chrono::Utc;
 use crate::db::{Categories, categories};
+use crate::db::audit_log::AuditEntity;
 use crate::dtos::*;
+use crate::concurrency::IfMatch;
 use crate::error::AppError;
+use crate::pagination::PageParams;
+use crate::services::{AuditService, ChangeService};
 
 
 pub struct CategoryService;
 
 impl CategoryService {
-    pub async fn find_all(db: &DatabaseConnection) -> Result<Vec<categories::Model>, AppError> {
-        let categories = Categories::find()
-            .order_by_asc(categories::Column::Name)
-            .all(db)
-            .await?;
-        Ok(categories)
+    pub async fn find_all(db: &DatabaseConnection, page: &PageParams, include_deleted: bool) -> Result<(Vec<categories::Model>, u64), AppError> {
+        let mut query = Categories::find();
+        if !include_deleted {
+            query = query.filter(categories::Column::DeletedAt.is_null());
+        }
+        let query = match page.sort_by(&[("name", categories::Column::Name)])? {
+            Some((column, order)) => query.order_by(column, order),
+            None => query.order_by_asc(categories::Column::Name),
+        };
+        page.fetch(db, query.order_by_asc(categories::Column::CategoryId)).await
     }
 
-    pub async fn find_by_id(db: &DatabaseConnection, id: i32) -> Result<categories::Model, AppError> {
+    pub async fn find_by_id(db: &DatabaseConnection, id: i32, include_deleted: bool) -> Result<categories::Model, AppError> {
         Categories::find_by_id(id)
             .one(db)
             .await?
+            .filter(|category| include_deleted || category.deleted_at.is_none())
             .ok_or(AppError::NotFound)
     }
 
-    pub async fn create(db: &DatabaseConnection, dto: CategoryCreate) -> Result<categories::Model, AppError> {
+    pub async fn create<C: ConnectionTrait + TransactionTrait>(db: &C, dto: CategoryCreate) -> Result<categories::Model, AppError> {
         let category = categories::ActiveModel {
             name: Set(dto.name),
             description: Set(dto.description),
             ..Default::default()
         };
         
-        Ok(category.insert(db).await?)
+        let txn = db.begin().await?;
+        let category = category.insert(&txn).await?;
+        ChangeService::notify(&txn, EntityKind::Category, category.category_id, ChangeAction::Created).await?;
+        AuditService::record(&txn, AuditEntity::Category, category.category_id, None, Some(&category)).await?;
+        txn.commit().await?;
+        Ok(category)
     }
 
-    pub async fn update(db: &DatabaseConnection, id: i32, dto: CategoryUpdate) -> Result<categories::Model, AppError> {
-        let category = Self::find_by_id(db, id).await?;
+    pub async fn update<C: ConnectionTrait + TransactionTrait>(db: &C, id: i32, if_match: &IfMatch, dto: CategoryUpdate) -> Result<categories::Model, AppError> {
+        let txn = db.begin().await?;
+        
+        let category = Categories::find_by_id(id)
+            .filter(categories::Column::DeletedAt.is_null())
+            .lock(LockType::NoKeyUpdate)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if_match.check(category.version)?;
+        let before = category.clone();
         
         let mut category: categories::ActiveModel = category.into();
         
@@ -47,14 +73,58 @@ impl CategoryService {
             category.description = Set(Some(description));
         }
         
-        Ok(category.update(db).await?)
+        let category = category.update(&txn).await?;
+        ChangeService::notify(&txn, EntityKind::Category, id, ChangeAction::Updated).await?;
+        AuditService::record(&txn, AuditEntity::Category, id, Some(&before), Some(&category)).await?;
+        
+        txn.commit().await?;
+        Ok(category)
     }
 
-    pub async fn delete(db: &DatabaseConnection, id: i32) -> Result<(), AppError> {
-        let category = Self::find_by_id(db, id).await?;
-        let category: categories::ActiveModel = category.into();
-        category.delete(db).await?;
+    /// Soft-deletes the category; it stays in the table until restored.
+    pub async fn delete<C: ConnectionTrait + TransactionTrait>(db: &C, id: i32, if_match: &IfMatch) -> Result<(), AppError> {
+        let txn = db.begin().await?;
+        
+        let category = Categories::find_by_id(id)
+            .filter(categories::Column::DeletedAt.is_null())
+            .lock(LockType::NoKeyUpdate)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if_match.check(category.version)?;
+        AuditService::record(&txn, AuditEntity::Category, id, Some(&category), None).await?;
+        
+        let mut category: categories::ActiveModel = category.into();
+        category.deleted_at = Set(Some(Utc::now()));
+        category.update(&txn).await?;
+        ChangeService::notify(&txn, EntityKind::Category, id, ChangeAction::Deleted).await?;
+        
+        txn.commit().await?;
         Ok(())
     }
+
+    pub async fn restore(db: &DatabaseConnection, id: i32, if_match: &IfMatch) -> Result<categories::Model, AppError> {
+        let txn = db.begin().await?;
+        
+        let category = Categories::find_by_id(id)
+            .lock(LockType::NoKeyUpdate)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if_match.check(category.version)?;
+        if category.deleted_at.is_none() {
+            return Err(AppError::Conflict(format!("Category {} is not deleted", id)));
+        }
+        let before = category.clone();
+        
+        let mut category: categories::ActiveModel = category.into();
+        category.deleted_at = Set(None);
+        let category = category.update(&txn).await?;
+        ChangeService::notify(&txn, EntityKind::Category, id, ChangeAction::Created).await?;
+        AuditService::record(&txn, AuditEntity::Category, id, Some(&before), Some(&category)).await?;
+        
+        txn.commit().await?;
+        Ok(category)
+    }
 }
 
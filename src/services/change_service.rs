@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use sea_orm::sqlx::postgres::PgListener;
+use sea_orm::{ConnectionTrait, DatabaseBackend, DatabaseConnection, Statement};
+use tokio::sync::broadcast;
+use crate::dtos::{ChangeAction, EntityChange, EntityKind};
+use crate::error::AppError;
+
+/// Postgres channel that carries `EntityChange`s between instances.
+const CHANNEL: &str = "entity_changes";
+
+pub struct ChangeService;
+
+impl ChangeService {
+    /// Announces a change on `conn`. Postgres holds the notification until the surrounding
+    /// transaction commits and drops it on rollback, so listeners only hear about changes
+    /// that actually happened.
+    pub async fn notify<C: ConnectionTrait>(
+        conn: &C,
+        entity: EntityKind,
+        id: i32,
+        action: ChangeAction,
+    ) -> Result<(), AppError> {
+        let change = EntityChange { entity, id, action };
+        let payload = serde_json::to_string(&change).map_err(|_| AppError::Internal)?;
+        conn.execute(Statement::from_sql_and_values(
+            DatabaseBackend::Postgres,
+            "SELECT pg_notify($1, $2)",
+            [CHANNEL.into(), payload.into()],
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// Forwards every committed change, from this instance or any other, to `changes`.
+    /// Reconnects after a lost connection; meant to be spawned once at startup.
+    pub async fn run_listener(db: DatabaseConnection, changes: broadcast::Sender<EntityChange>) {
+        loop {
+            if let Err(err) = Self::listen(&db, &changes).await {
+                eprintln!("Change listener failed, reconnecting: {err}");
+            }
+            actix_web::rt::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    async fn listen(
+        db: &DatabaseConnection,
+        changes: &broadcast::Sender<EntityChange>,
+    ) -> Result<(), sea_orm::sqlx::Error> {
+        let mut listener = PgListener::connect_with(db.get_postgres_connection_pool()).await?;
+        listener.listen(CHANNEL).await?;
+        loop {
+            let notification = listener.recv().await?;
+            match serde_json::from_str::<EntityChange>(notification.payload()) {
+                // Nobody subscribed is not an error
+                Ok(change) => _ = changes.send(change),
+                Err(err) => eprintln!("Ignoring malformed change notification: {err}"),
+            }
+        }
+    }
+}
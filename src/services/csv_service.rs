@@ -0,0 +1,552 @@
+use actix_web::web::Bytes;
+use futures_util::{Stream, stream};
+use rust_decimal::Decimal;
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, DatabaseTransaction, EntityTrait, LoaderTrait, QueryFilter,
+    QueryOrder, QuerySelect, TransactionTrait
+};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use validator::Validate;
+use crate::db::{Categories, Customers, Products, Suppliers, categories, customers, products, suppliers};
+use crate::dtos::*;
+use crate::concurrency::IfMatch;
+use crate::error::{AppError, FieldError};
+use crate::services::{CustomerService, ProductService, SupplierService};
+
+/// Rows fetched per query while streaming an export.
+const EXPORT_CHUNK: u64 = 500;
+
+const PRODUCT_COLUMNS: [&str; 10] = [
+    "product_id", "name", "description", "price", "stock_quantity",
+    "category_id", "category", "supplier_id", "supplier", "version",
+];
+const CUSTOMER_COLUMNS: [&str; 8] = [
+    "customer_id", "first_name", "last_name", "email", "phone", "address", "registration_date", "version",
+];
+const SUPPLIER_COLUMNS: [&str; 7] = [
+    "supplier_id", "company_name", "contact_name", "email", "phone", "address", "version",
+];
+
+/// A product row of an import. Products are matched on name within their supplier,
+/// which can be given by id or by company name, and likewise for the category.
+#[derive(Deserialize)]
+struct ProductRow {
+    name: Option<String>,
+    description: Option<String>,
+    // Parsed by hand so that prices are not rounded through a float
+    price: Option<String>,
+    stock_quantity: Option<i32>,
+    category_id: Option<i32>,
+    category: Option<String>,
+    supplier_id: Option<i32>,
+    supplier: Option<String>,
+    version: Option<i32>,
+}
+
+/// A customer row of an import, matched on `email`.
+#[derive(Deserialize)]
+struct CustomerRow {
+    first_name: Option<String>,
+    last_name: Option<String>,
+    email: Option<String>,
+    phone: Option<String>,
+    address: Option<String>,
+    version: Option<i32>,
+}
+
+/// A supplier row of an import, matched on `company_name`.
+#[derive(Deserialize)]
+struct SupplierRow {
+    company_name: Option<String>,
+    contact_name: Option<String>,
+    email: Option<String>,
+    phone: Option<String>,
+    address: Option<String>,
+    version: Option<i32>,
+}
+
+enum Upserted {
+    Created,
+    Updated,
+}
+
+/// The parsed rows of an uploaded CSV, keyed by line number.
+struct Sheet<R> {
+    rows: Vec<(u64, Result<R, Vec<FieldError>>)>,
+    ignored_columns: Vec<String>,
+}
+
+pub struct CsvService;
+
+impl CsvService {
+    /// Upserts products matched on `(supplier, name)`.
+    pub async fn import_products(db: &DatabaseConnection, body: &[u8], params: &ImportParams) -> Result<ImportReport, AppError> {
+        let sheet = Self::read(body, params, &PRODUCT_COLUMNS, "name")?;
+        Self::apply(db, sheet, params.dry_run, Self::upsert_product).await
+    }
+
+    /// Upserts customers matched on `email`.
+    pub async fn import_customers(db: &DatabaseConnection, body: &[u8], params: &ImportParams) -> Result<ImportReport, AppError> {
+        let sheet = Self::read(body, params, &CUSTOMER_COLUMNS, "email")?;
+        Self::apply(db, sheet, params.dry_run, Self::upsert_customer).await
+    }
+
+    /// Upserts suppliers matched on `company_name`.
+    pub async fn import_suppliers(db: &DatabaseConnection, body: &[u8], params: &ImportParams) -> Result<ImportReport, AppError> {
+        let sheet = Self::read(body, params, &SUPPLIER_COLUMNS, "company_name")?;
+        Self::apply(db, sheet, params.dry_run, Self::upsert_supplier).await
+    }
+
+    pub fn export_products(db: DatabaseConnection) -> impl Stream<Item = Result<Bytes, AppError>> {
+        Self::export::<Products, _>(db, &PRODUCT_COLUMNS, products::Column::ProductId, products::Column::DeletedAt, |p| p.product_id, async |db, products| {
+            let categories = products.load_one(Categories, db).await?;
+            let suppliers = products.load_one(Suppliers, db).await?;
+            Ok(products.into_iter().zip(categories).zip(suppliers).map(|((p, category), supplier)| vec![
+                p.product_id.to_string(),
+                p.name,
+                p.description.unwrap_or_default(),
+                p.price.to_string(),
+                p.stock_quantity.to_string(),
+                p.category_id.to_string(),
+                category.map(|c| c.name).unwrap_or_default(),
+                p.supplier_id.to_string(),
+                supplier.map(|s| s.company_name).unwrap_or_default(),
+                p.version.to_string(),
+            ]).collect())
+        })
+    }
+
+    pub fn export_customers(db: DatabaseConnection) -> impl Stream<Item = Result<Bytes, AppError>> {
+        Self::export::<Customers, _>(db, &CUSTOMER_COLUMNS, customers::Column::CustomerId, customers::Column::DeletedAt, |c| c.customer_id, async |_, customers| {
+            Ok(customers.into_iter().map(|c| vec![
+                c.customer_id.to_string(),
+                c.first_name,
+                c.last_name,
+                c.email,
+                c.phone.unwrap_or_default(),
+                c.address.unwrap_or_default(),
+                c.registration_date.to_rfc3339(),
+                c.version.to_string(),
+            ]).collect())
+        })
+    }
+
+    pub fn export_suppliers(db: DatabaseConnection) -> impl Stream<Item = Result<Bytes, AppError>> {
+        Self::export::<Suppliers, _>(db, &SUPPLIER_COLUMNS, suppliers::Column::SupplierId, suppliers::Column::DeletedAt, |s| s.supplier_id, async |_, suppliers| {
+            Ok(suppliers.into_iter().map(|s| vec![
+                s.supplier_id.to_string(),
+                s.company_name,
+                s.contact_name.unwrap_or_default(),
+                s.email.unwrap_or_default(),
+                s.phone.unwrap_or_default(),
+                s.address.unwrap_or_default(),
+                s.version.to_string(),
+            ]).collect())
+        })
+    }
+
+    /// Parses the body against `columns` after applying the header mapping.
+    /// A row that does not parse becomes an error for that row only.
+    fn read<R: DeserializeOwned>(body: &[u8], params: &ImportParams, columns: &[&str], key: &str) -> Result<Sheet<R>, AppError> {
+        let mapping = parse_mapping(params.mapping.as_deref())?;
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(body);
+
+        let headers: csv::StringRecord = reader
+            .headers()
+            .map_err(|err| AppError::validation(format!("Unreadable CSV header: {}", err)))?
+            .iter()
+            .map(|header| {
+                mapping
+                    .iter()
+                    .find(|(from, _)| from.eq_ignore_ascii_case(header))
+                    .map_or_else(|| header.to_lowercase(), |(_, to)| to.clone())
+            })
+            .collect();
+        if !headers.iter().any(|header| header == key) {
+            return Err(AppError::Validation {
+                message: format!("The CSV has no {} column", key),
+                details: vec![FieldError { field: key.to_string(), message: "column is required".to_string() }],
+            });
+        }
+        let ignored_columns = headers
+            .iter()
+            .filter(|header| !columns.contains(header))
+            .map(str::to_string)
+            .collect();
+
+        let mut rows = Vec::new();
+        for record in reader.records() {
+            let record = record.map_err(|err| AppError::validation(format!("Unreadable CSV: {}", err)))?;
+            let line = record.position().map_or(0, |position| position.line());
+            let row = record.deserialize::<R>(Some(&headers)).map_err(|err| {
+                let field = match err.kind() {
+                    csv::ErrorKind::Deserialize { err, .. } => err
+                        .field()
+                        .and_then(|index| headers.get(index as usize))
+                        .unwrap_or_default(),
+                    _ => "",
+                };
+                let message = match err.kind() {
+                    csv::ErrorKind::Deserialize { err, .. } => err.kind().to_string(),
+                    kind => format!("{:?}", kind),
+                };
+                vec![FieldError { field: field.to_string(), message }]
+            });
+            rows.push((line, row));
+        }
+        Ok(Sheet { rows, ignored_columns })
+    }
+
+    /// Upserts every row in one transaction, each in its own savepoint so that a bad row
+    /// is reported without hiding the errors of the rows after it. Nothing is kept
+    /// on a dry run or when any row failed.
+    async fn apply<R>(
+        db: &DatabaseConnection,
+        sheet: Sheet<R>,
+        dry_run: bool,
+        upsert: impl AsyncFn(&DatabaseTransaction, R) -> Result<Upserted, AppError>,
+    ) -> Result<ImportReport, AppError> {
+        let mut report = ImportReport {
+            dry_run,
+            rows: sheet.rows.len() as u64,
+            created: 0,
+            updated: 0,
+            ignored_columns: sheet.ignored_columns,
+            errors: Vec::new(),
+        };
+
+        let txn = db.begin().await?;
+        for (line, row) in sheet.rows {
+            let outcome = match row {
+                Ok(row) => {
+                    let savepoint = txn.begin().await?;
+                    match upsert(&savepoint, row).await {
+                        Ok(upserted) => {
+                            savepoint.commit().await?;
+                            Ok(upserted)
+                        }
+                        Err(err) => {
+                            savepoint.rollback().await?;
+                            Err(err.field_errors().ok_or(err)?)
+                        }
+                    }
+                }
+                Err(errors) => Err(errors),
+            };
+            match outcome {
+                Ok(Upserted::Created) => report.created += 1,
+                Ok(Upserted::Updated) => report.updated += 1,
+                Err(errors) => report.errors.extend(errors.into_iter().map(|error| FieldError {
+                    field: match error.field.as_str() {
+                        "" => format!("rows[{}]", line),
+                        field => format!("rows[{}].{}", line, field),
+                    },
+                    message: error.message,
+                })),
+            }
+        }
+
+        if dry_run {
+            txn.rollback().await?;
+            return Ok(report);
+        }
+        if !report.errors.is_empty() {
+            txn.rollback().await?;
+            return Err(AppError::Validation {
+                message: format!("{} row error(s), nothing was imported", report.errors.len()),
+                details: report.errors,
+            });
+        }
+        txn.commit().await?;
+        Ok(report)
+    }
+
+    async fn upsert_product(txn: &DatabaseTransaction, row: ProductRow) -> Result<Upserted, AppError> {
+        let name = row.name.ok_or_else(|| missing("name"))?;
+        let supplier_id = match (row.supplier_id, row.supplier) {
+            (Some(id), _) => id,
+            (None, Some(company_name)) => {
+                let suppliers = Suppliers::find()
+                    .filter(suppliers::Column::CompanyName.eq(&company_name))
+                    .filter(suppliers::Column::DeletedAt.is_null())
+                    .all(txn)
+                    .await?;
+                single(suppliers, "supplier")?.supplier_id
+            }
+            (None, None) => return Err(missing("supplier_id")),
+        };
+        let category_id = match (row.category_id, row.category) {
+            (Some(id), _) => Some(id),
+            (None, Some(category_name)) => {
+                let categories = Categories::find()
+                    .filter(categories::Column::Name.eq(&category_name))
+                    .filter(categories::Column::DeletedAt.is_null())
+                    .all(txn)
+                    .await?;
+                Some(single(categories, "category")?.category_id)
+            }
+            (None, None) => None,
+        };
+        let price = row.price
+            .map(|price| Decimal::from_str_exact(&price))
+            .transpose()
+            .map_err(|_| invalid("price", "must be a decimal number"))?;
+
+        let existing = Products::find()
+            .filter(products::Column::SupplierId.eq(supplier_id))
+            .filter(products::Column::Name.eq(&name))
+            .filter(products::Column::DeletedAt.is_null())
+            .limit(2)
+            .all(txn)
+            .await?;
+        if existing.len() > 1 {
+            return Err(invalid("name", "matches more than one product of this supplier"));
+        }
+        match existing.into_iter().next() {
+            Some(product) => {
+                let dto = ProductUpdate {
+                    name: None,
+                    description: row.description,
+                    price,
+                    stock_quantity: row.stock_quantity,
+                    category_id,
+                    supplier_id: None,
+                };
+                dto.validate()?;
+                let if_match = exported_version(row.version)?;
+                ProductService::update(txn, product.product_id, &if_match, dto).await.map_err(stale)?;
+                Ok(Upserted::Updated)
+            }
+            None => {
+                let dto = ProductCreate {
+                    name,
+                    description: row.description,
+                    price: price.ok_or_else(|| missing("price"))?,
+                    stock_quantity: row.stock_quantity.unwrap_or(0),
+                    category_id: category_id.ok_or_else(|| missing("category_id"))?,
+                    supplier_id,
+                };
+                dto.validate()?;
+                ProductService::create(txn, dto).await?;
+                Ok(Upserted::Created)
+            }
+        }
+    }
+
+    async fn upsert_customer(txn: &DatabaseTransaction, row: CustomerRow) -> Result<Upserted, AppError> {
+        let version = row.version;
+        let row = CustomerUpdate {
+            first_name: row.first_name,
+            last_name: row.last_name,
+            email: row.email,
+            phone: row.phone,
+            address: row.address,
+        };
+        row.validate()?;
+        let email = row.email.clone().ok_or_else(|| missing("email"))?;
+
+        let existing = Customers::find()
+            .filter(customers::Column::Email.eq(&email))
+            .filter(customers::Column::DeletedAt.is_null())
+            .one(txn)
+            .await?;
+        match existing {
+            Some(customer) => {
+                let if_match = exported_version(version)?;
+                CustomerService::update(txn, customer.customer_id, &if_match, row).await.map_err(stale)?;
+                Ok(Upserted::Updated)
+            }
+            None => {
+                let dto = CustomerCreate {
+                    first_name: row.first_name.ok_or_else(|| missing("first_name"))?,
+                    last_name: row.last_name.ok_or_else(|| missing("last_name"))?,
+                    email,
+                    phone: row.phone,
+                    address: row.address,
+                };
+                CustomerService::create(txn, dto).await?;
+                Ok(Upserted::Created)
+            }
+        }
+    }
+
+    async fn upsert_supplier(txn: &DatabaseTransaction, row: SupplierRow) -> Result<Upserted, AppError> {
+        let version = row.version;
+        let row = SupplierUpdate {
+            company_name: row.company_name,
+            contact_name: row.contact_name,
+            email: row.email,
+            phone: row.phone,
+            address: row.address,
+        };
+        row.validate()?;
+        let company_name = row.company_name.clone().ok_or_else(|| missing("company_name"))?;
+
+        let existing = Suppliers::find()
+            .filter(suppliers::Column::CompanyName.eq(&company_name))
+            .filter(suppliers::Column::DeletedAt.is_null())
+            .limit(2)
+            .all(txn)
+            .await?;
+        if existing.len() > 1 {
+            return Err(invalid("company_name", "matches more than one supplier"));
+        }
+        match existing.into_iter().next() {
+            Some(supplier) => {
+                let if_match = exported_version(version)?;
+                SupplierService::update(txn, supplier.supplier_id, &if_match, row).await.map_err(stale)?;
+                Ok(Upserted::Updated)
+            }
+            None => {
+                let dto = SupplierCreate {
+                    company_name,
+                    contact_name: row.contact_name,
+                    email: row.email,
+                    phone: row.phone,
+                    address: row.address,
+                };
+                SupplierService::create(txn, dto).await?;
+                Ok(Upserted::Created)
+            }
+        }
+    }
+
+    /// Streams `header` and then every row of `E` that is not deleted in id order, a chunk
+    /// at a time, so that large tables are never held in memory at once.
+    fn export<E, F>(
+        db: DatabaseConnection,
+        header: &'static [&'static str],
+        id: E::Column,
+        deleted_at: E::Column,
+        key: fn(&E::Model) -> i32,
+        records: F,
+    ) -> impl Stream<Item = Result<Bytes, AppError>>
+    where
+        E: EntityTrait,
+        F: AsyncFn(&DatabaseConnection, Vec<E::Model>) -> Result<Vec<Vec<String>>, AppError>,
+    {
+        stream::try_unfold((db, records, Some(0), true), move |(db, records, after, first)| async move {
+            let Some(after) = after else {
+                return Ok(None);
+            };
+            let models = E::find()
+                .filter(id.gt(after))
+                .filter(deleted_at.is_null())
+                .order_by_asc(id)
+                .limit(EXPORT_CHUNK)
+                .all(&db)
+                .await?;
+            let next = match models.len() as u64 {
+                EXPORT_CHUNK => models.last().map(key),
+                _ => None,
+            };
+
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            if first {
+                writer.write_record(header).map_err(|_| AppError::Internal)?;
+            }
+            for record in records(&db, models).await? {
+                writer.write_record(&record).map_err(|_| AppError::Internal)?;
+            }
+            let chunk = writer.into_inner().map_err(|_| AppError::Internal)?;
+            Ok(Some((Bytes::from(chunk), (db, records, next, false))))
+        })
+    }
+}
+
+/// Reads `Header:field,Header:field` into pairs.
+fn parse_mapping(mapping: Option<&str>) -> Result<Vec<(String, String)>, AppError> {
+    let Some(mapping) = mapping.filter(|mapping| !mapping.trim().is_empty()) else {
+        return Ok(Vec::new());
+    };
+    mapping
+        .split(',')
+        .map(|pair| match pair.split_once(':') {
+            Some((from, to)) => Ok((from.trim().to_string(), to.trim().to_lowercase())),
+            None => Err(AppError::Validation {
+                message: "Malformed header mapping".to_string(),
+                details: vec![FieldError {
+                    field: "mapping".to_string(),
+                    message: format!("{:?} is not a Header:field pair", pair),
+                }],
+            }),
+        })
+        .collect()
+}
+
+/// The one record a name refers to.
+fn single<M>(models: Vec<M>, field: &str) -> Result<M, AppError> {
+    let mut models = models.into_iter();
+    match (models.next(), models.next()) {
+        (Some(model), None) => Ok(model),
+        (None, _) => Err(invalid(field, "no record has this name")),
+        (Some(_), Some(_)) => Err(invalid(field, "more than one record has this name")),
+    }
+}
+
+/// The precondition an update row is saved under: the `version` the record was exported at.
+fn exported_version(version: Option<i32>) -> Result<IfMatch, AppError> {
+    IfMatch::version(version).map_err(|_| invalid("version", "is required to update an existing record"))
+}
+
+/// Reports a record that changed since it was exported as an error of its row.
+fn stale(err: AppError) -> AppError {
+    match err {
+        AppError::PreconditionFailed => invalid("version", "is out of date, the record changed since it was exported"),
+        err => err,
+    }
+}
+
+fn missing(field: &str) -> AppError {
+    invalid(field, "is required")
+}
+
+fn invalid(field: &str, message: &str) -> AppError {
+    AppError::Validation {
+        message: format!("{} {}", field, message),
+        details: vec![FieldError { field: field.to_string(), message: message.to_string() }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_mapping;
+    use crate::error::AppError;
+
+    fn pairs(mapping: &[(&str, &str)]) -> Vec<(String, String)> {
+        mapping.iter().map(|(from, to)| (from.to_string(), to.to_string())).collect()
+    }
+
+    #[test]
+    fn no_mapping_is_an_empty_one() {
+        assert_eq!(parse_mapping(None).unwrap(), pairs(&[]));
+        assert_eq!(parse_mapping(Some("  ")).unwrap(), pairs(&[]));
+    }
+
+    #[test]
+    fn pairs_are_trimmed_and_fields_lowercased() {
+        assert_eq!(
+            parse_mapping(Some("Product Name: NAME , Cost:price")).unwrap(),
+            pairs(&[("Product Name", "name"), ("Cost", "price")])
+        );
+    }
+
+    #[test]
+    fn only_the_first_colon_separates_header_from_field() {
+        assert_eq!(parse_mapping(Some("Time: 12:00:x")).unwrap(), pairs(&[("Time", "12:00:x")]));
+    }
+
+    #[test]
+    fn a_pair_without_a_colon_is_rejected() {
+        match parse_mapping(Some("Name:name,Price")) {
+            Err(AppError::Validation { details, .. }) => {
+                assert_eq!(details[0].field, "mapping");
+                assert!(details[0].message.contains("\"Price\""));
+            }
+            other => panic!("gave {other:?}"),
+        }
+    }
+}
@@ -1,33 +1,68 @@
 use anyhow::Result;
-use sea_orm::{ModelTrait};
+use std::collections::HashMap;
+
+use sea_orm::sea_query::{Expr, LockType};
+use sea_orm::{Condition, LoaderTrait, ModelTrait};
 use sea_orm::{
-    ActiveModelTrait, DatabaseConnection, EntityTrait, QueryOrder, Set
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DatabaseTransaction, EntityTrait, QueryFilter,
+    QuerySelect, TransactionTrait, QueryOrder, Set
 };
 use chrono::Utc;
-use crate::db::{Customers, Orders, customers, orders};
+use serde_json::{Value, json};
+use crate::db::{
+    AuditLog, CustomerAddresses, Customers, OrderItems, Orders, Outbox, Products, WebhookDeliveries, audit_log,
+    customer_addresses, customers, order_items, orders, outbox, products, webhook_deliveries
+};
+use crate::db::outbox::EventType;
+use crate::db::audit_log::AuditEntity;
 use crate::dtos::*;
-use crate::error::AppError;
+use crate::concurrency::IfMatch;
+use crate::error::{AppError, FieldError};
+use crate::pagination::PageParams;
+use crate::services::{AddressService, AuditService, ChangeService, OutboxService};
+
+/// What personal data is replaced with by `CustomerService::anonymize`.
+const ANONYMIZED: &str = "[anonymized]";
+
+/// Fields of customers, their addresses and orders, and of the events and audit entries
+/// about them, that hold personal data.
+const PERSONAL_FIELDS: [&str; 11] = [
+    "first_name", "last_name", "email", "phone", "address", "shipping_address",
+    "line1", "line2", "city", "region", "postal_code",
+];
 
 pub struct CustomerService;
 
 impl CustomerService {
-    pub async fn find_all(db: &DatabaseConnection) -> Result<Vec<customers::Model>, AppError> {
-        let customers = Customers::find()
-            .order_by_asc(customers::Column::LastName)
-            .order_by_asc(customers::Column::FirstName)
-            .all(db)
-            .await?;
-        Ok(customers)
+    pub async fn find_all(db: &DatabaseConnection, page: &PageParams, include_deleted: bool) -> Result<(Vec<customers::Model>, u64), AppError> {
+        let mut query = Customers::find();
+        if !include_deleted {
+            query = query.filter(customers::Column::DeletedAt.is_null());
+        }
+        let sortable = [
+            ("first_name", customers::Column::FirstName),
+            ("last_name", customers::Column::LastName),
+            ("email", customers::Column::Email),
+            ("registration_date", customers::Column::RegistrationDate),
+        ];
+        let query = match page.sort_by(&sortable)? {
+            Some((column, order)) => query.order_by(column, order),
+            None => query
+                .order_by_asc(customers::Column::LastName)
+                .order_by_asc(customers::Column::FirstName),
+        };
+        page.fetch(db, query.order_by_asc(customers::Column::CustomerId)).await
     }
 
-    pub async fn find_by_id(db: &DatabaseConnection, id: i32) -> Result<customers::Model, AppError> {
+    pub async fn find_by_id(db: &DatabaseConnection, id: i32, include_deleted: bool) -> Result<customers::Model, AppError> {
         Customers::find_by_id(id)
             .one(db)
             .await?
+            .filter(|customer| include_deleted || customer.deleted_at.is_none())
             .ok_or(AppError::NotFound)
     }
 
-    pub async fn create(db: &DatabaseConnection, dto: CustomerCreate) -> Result<customers::Model, AppError> {
+    pub async fn create<C: ConnectionTrait + TransactionTrait>(db: &C, dto: CustomerCreate) -> Result<customers::Model, AppError> {
         let customer = customers::ActiveModel {
             first_name: Set(dto.first_name),
             last_name: Set(dto.last_name),
@@ -38,11 +73,32 @@ impl CustomerService {
             ..Default::default()
         };
         
-        Ok(customer.insert(db).await?)
+        let txn = db.begin().await?;
+        let customer = customer.insert(&txn).await?;
+        ChangeService::notify(&txn, EntityKind::Customer, customer.customer_id, ChangeAction::Created).await?;
+        AuditService::record(&txn, AuditEntity::Customer, customer.customer_id, None, Some(&customer)).await?;
+        OutboxService::publish(&txn, EventType::CustomerCreated, "customer", customer.customer_id, json!({
+            "customer_id": customer.customer_id,
+            "first_name": customer.first_name,
+            "last_name": customer.last_name,
+            "email": customer.email,
+            "registration_date": customer.registration_date,
+        })).await?;
+        txn.commit().await?;
+        Ok(customer)
     }
 
-    pub async fn update(db: &DatabaseConnection, id: i32, dto: CustomerUpdate) -> Result<customers::Model, AppError> {
-        let customer = Self::find_by_id(db, id).await?;
+    pub async fn update<C: ConnectionTrait + TransactionTrait>(db: &C, id: i32, if_match: &IfMatch, dto: CustomerUpdate) -> Result<customers::Model, AppError> {
+        let txn = db.begin().await?;
+        
+        let customer = Customers::find_by_id(id)
+            .filter(customers::Column::DeletedAt.is_null())
+            .lock(LockType::NoKeyUpdate)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if_match.check(customer.version)?;
+        let before = customer.clone();
         
         let mut customer: customers::ActiveModel = customer.into();
         
@@ -62,19 +118,296 @@ impl CustomerService {
             customer.address = Set(Some(address));
         }
         
-        Ok(customer.update(db).await?)
+        let customer = customer.update(&txn).await?;
+        ChangeService::notify(&txn, EntityKind::Customer, id, ChangeAction::Updated).await?;
+        AuditService::record(&txn, AuditEntity::Customer, id, Some(&before), Some(&customer)).await?;
+        
+        txn.commit().await?;
+        Ok(customer)
     }
 
-    pub async fn delete(db: &DatabaseConnection, id: i32) -> Result<(), AppError> {
-        let customer = Self::find_by_id(db, id).await?;
-        let customer: customers::ActiveModel = customer.into();
-        customer.delete(db).await?;
+    /// Soft-deletes the customer; it stays in the table until restored. A customer with
+    /// orders is only deleted when `archive` is set, and the orders are kept.
+    pub async fn delete<C: ConnectionTrait + TransactionTrait>(db: &C, id: i32, if_match: &IfMatch, archive: bool) -> Result<(), AppError> {
+        let txn = db.begin().await?;
+        
+        // FOR UPDATE also holds off new orders, whose foreign key check needs a key share lock
+        let customer = Customers::find_by_id(id)
+            .filter(customers::Column::DeletedAt.is_null())
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if_match.check(customer.version)?;
+        
+        if !archive {
+            let orders: Vec<i32> = Orders::find()
+                .select_only()
+                .column(orders::Column::OrderId)
+                .filter(orders::Column::CustomerId.eq(id))
+                .order_by_asc(orders::Column::OrderId)
+                .into_tuple()
+                .all(&txn)
+                .await?;
+            if !orders.is_empty() {
+                return Err(AppError::StillReferenced {
+                    message: format!("Customer {} still has orders, archive it to keep them", id),
+                    dependents: vec![FieldError::dependents("orders", &orders)],
+                });
+            }
+        }
+        AuditService::record(&txn, AuditEntity::Customer, id, Some(&customer), None).await?;
+        
+        let mut customer: customers::ActiveModel = customer.into();
+        customer.deleted_at = Set(Some(Utc::now()));
+        customer.update(&txn).await?;
+        ChangeService::notify(&txn, EntityKind::Customer, id, ChangeAction::Deleted).await?;
+        
+        txn.commit().await?;
         Ok(())
     }
 
+    pub async fn restore(db: &DatabaseConnection, id: i32, if_match: &IfMatch) -> Result<customers::Model, AppError> {
+        let txn = db.begin().await?;
+        
+        let customer = Customers::find_by_id(id)
+            .lock(LockType::NoKeyUpdate)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if_match.check(customer.version)?;
+        if customer.deleted_at.is_none() {
+            return Err(AppError::Conflict(format!("Customer {} is not deleted", id)));
+        }
+        let before = customer.clone();
+        
+        let mut customer: customers::ActiveModel = customer.into();
+        customer.deleted_at = Set(None);
+        let customer = customer.update(&txn).await?;
+        ChangeService::notify(&txn, EntityKind::Customer, id, ChangeAction::Created).await?;
+        AuditService::record(&txn, AuditEntity::Customer, id, Some(&before), Some(&customer)).await?;
+        
+        txn.commit().await?;
+        Ok(customer)
+    }
+
     pub async fn find_with_orders(db: &DatabaseConnection, id: i32) -> Result<(customers::Model, Vec<orders::Model>), AppError> {
-        let customer = Self::find_by_id(db, id).await?;
+        let customer = Self::find_by_id(db, id, false).await?;
         let orders = customer.find_related(Orders).all(db).await?;
         Ok((customer, orders))
     }
+
+    /// The customer with each of their orders and its items, for a data export.
+    pub async fn find_for_export(
+        db: &DatabaseConnection,
+        id: i32,
+        include_deleted: bool,
+    ) -> Result<(customers::Model, Vec<(orders::Model, Vec<(order_items::Model, Option<products::Model>)>)>), AppError> {
+        let customer = Self::find_by_id(db, id, include_deleted).await?;
+        let orders = customer
+            .find_related(Orders)
+            .order_by_asc(orders::Column::OrderId)
+            .all(db)
+            .await?;
+        let items = orders.load_many(OrderItems, db).await?;
+        
+        let product_ids: Vec<i32> = items.iter().flatten().map(|item| item.product_id).collect();
+        let products: HashMap<i32, products::Model> = Products::find()
+            .filter(products::Column::ProductId.is_in(product_ids))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|product| (product.product_id, product))
+            .collect();
+        let orders = orders
+            .into_iter()
+            .zip(items)
+            .map(|(order, items)| {
+                let items = items
+                    .into_iter()
+                    .map(|item| {
+                        let product = products.get(&item.product_id).cloned();
+                        (item, product)
+                    })
+                    .collect();
+                (order, items)
+            })
+            .collect();
+        Ok((customer, orders))
+    }
+
+    /// Scrubs the customer's personal data, deletes their address book and scrubs the
+    /// shipping addresses of their orders, along with every copy kept in the audit log, the
+    /// outbox and webhook deliveries. The orders' items and amounts are left as they are
+    /// for accounting.
+    pub async fn anonymize(db: &DatabaseConnection, id: i32, if_match: &IfMatch) -> Result<customers::Model, AppError> {
+        let txn = db.begin().await?;
+        
+        let customer = Customers::find_by_id(id)
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if_match.check(customer.version)?;
+        let before = customer.clone();
+        
+        let mut customer: customers::ActiveModel = customer.into();
+        customer.first_name = Set(ANONYMIZED.to_string());
+        customer.last_name = Set(ANONYMIZED.to_string());
+        // Still unique and still a well-formed address
+        customer.email = Set(format!("anonymized-{}@example.invalid", id));
+        customer.phone = Set(None);
+        customer.address = Set(None);
+        let customer = customer.update(&txn).await?;
+        ChangeService::notify(&txn, EntityKind::Customer, id, ChangeAction::Updated).await?;
+        AuditService::record(&txn, AuditEntity::Customer, id, Some(&before), Some(&customer)).await?;
+        
+        let addresses = CustomerAddresses::find()
+            .filter(customer_addresses::Column::CustomerId.eq(id))
+            .all(&txn)
+            .await?;
+        let address_ids: Vec<i32> = addresses.iter().map(|address| address.address_id).collect();
+        for address in addresses {
+            AddressService::delete(&txn, id, address.address_id, &IfMatch::none()).await?;
+        }
+        
+        let orders = Orders::find()
+            .filter(orders::Column::CustomerId.eq(id))
+            .lock(LockType::NoKeyUpdate)
+            .all(&txn)
+            .await?;
+        let order_ids: Vec<i32> = orders.iter().map(|order| order.order_id).collect();
+        for order in orders {
+            let before = order.clone();
+            let mut order: orders::ActiveModel = order.into();
+            order.shipping_address = Set(ANONYMIZED.to_string());
+            let order = order.update(&txn).await?;
+            ChangeService::notify(&txn, EntityKind::Order, order.order_id, ChangeAction::Updated).await?;
+            AuditService::record(&txn, AuditEntity::Order, order.order_id, Some(&before), Some(&order)).await?;
+        }
+        
+        // Last, so that the entries recorded above are scrubbed too
+        Self::redact_history(&txn, id, address_ids, order_ids).await?;
+        
+        txn.commit().await?;
+        Ok(customer)
+    }
+
+    async fn redact_history(
+        txn: &DatabaseTransaction,
+        customer_id: i32,
+        address_ids: Vec<i32>,
+        order_ids: Vec<i32>,
+    ) -> Result<(), AppError> {
+        let entries = AuditLog::find()
+            .filter(
+                Condition::any()
+                    .add(audit_log::Column::Entity.eq(AuditEntity::Customer).and(audit_log::Column::EntityId.eq(customer_id)))
+                    .add(audit_log::Column::Entity.eq(AuditEntity::CustomerAddress).and(audit_log::Column::EntityId.is_in(address_ids)))
+                    .add(audit_log::Column::Entity.eq(AuditEntity::Order).and(audit_log::Column::EntityId.is_in(order_ids.clone()))),
+            )
+            .all(txn)
+            .await?;
+        for entry in entries {
+            let changes = redact(&entry.changes);
+            let mut entry: audit_log::ActiveModel = entry.into();
+            entry.changes = Set(changes);
+            entry.update(txn).await?;
+        }
+        
+        let events = Outbox::find()
+            .filter(
+                Condition::any()
+                    .add(outbox::Column::AggregateType.eq("customer").and(outbox::Column::AggregateId.eq(customer_id)))
+                    .add(outbox::Column::AggregateType.eq("order").and(outbox::Column::AggregateId.is_in(order_ids))),
+            )
+            .all(txn)
+            .await?;
+        for event in events {
+            let payload = redact(&event.payload);
+            let mut event: outbox::ActiveModel = event.into();
+            event.payload = Set(payload);
+            event.update(txn).await?;
+        }
+        
+        let deliveries = WebhookDeliveries::find()
+            .filter(Expr::cust_with_values("(payload::jsonb -> 'data' ->> 'customer_id')::int = $1", [customer_id]))
+            .all(txn)
+            .await?;
+        for delivery in deliveries {
+            let payload = redact(&delivery.payload);
+            let mut delivery: webhook_deliveries::ActiveModel = delivery.into();
+            delivery.payload = Set(payload);
+            delivery.update(txn).await?;
+        }
+        Ok(())
+    }
+}
+
+/// `json` with the value of every personal field in it replaced, at any depth.
+fn redact(json: &str) -> String {
+    match serde_json::from_str::<Value>(json) {
+        Ok(mut value) => {
+            redact_value(&mut value, false);
+            value.to_string()
+        }
+        Err(_) => json.to_string(),
+    }
+}
+
+/// Replaces every value under a personal field, or every value at all when `personal`.
+fn redact_value(value: &mut Value, personal: bool) {
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields.iter_mut() {
+                redact_value(field, personal || PERSONAL_FIELDS.contains(&name.as_str()));
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| redact_value(item, personal)),
+        Value::Null => {}
+        _ if personal => *value = Value::String(ANONYMIZED.to_string()),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ANONYMIZED, redact, redact_value};
+    use serde_json::json;
+
+    #[test]
+    fn personal_fields_are_replaced_wherever_they_are_nested() {
+        let mut value = json!({
+            "customer_id": 7,
+            "email": "ann@example.com",
+            "data": { "shipping_address": "1 Main St", "total_amount": "9.99" },
+            "items": [{ "city": "Springfield", "product_id": 2 }],
+        });
+        redact_value(&mut value, false);
+        assert_eq!(value, json!({
+            "customer_id": 7,
+            "email": ANONYMIZED,
+            "data": { "shipping_address": ANONYMIZED, "total_amount": "9.99" },
+            "items": [{ "city": ANONYMIZED, "product_id": 2 }],
+        }));
+    }
+
+    #[test]
+    fn everything_under_a_personal_field_is_replaced_but_nulls_are_kept() {
+        let mut value = json!({
+            "first_name": { "old": "Ann", "new": null },
+            "phone": ["555-1234", 5551234],
+        });
+        redact_value(&mut value, false);
+        assert_eq!(value, json!({
+            "first_name": { "old": ANONYMIZED, "new": null },
+            "phone": [ANONYMIZED, ANONYMIZED],
+        }));
+    }
+
+    #[test]
+    fn text_that_is_not_json_is_left_alone() {
+        assert_eq!(redact("not json"), "not json");
+        assert_eq!(redact(r#"{"email":"a@b.c"}"#), format!(r#"{{"email":"{ANONYMIZED}"}}"#));
+    }
 }
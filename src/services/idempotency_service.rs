@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set,
+    sea_query::OnConflict,
+};
+use crate::db::{IdempotencyKeys, idempotency_keys};
+use crate::error::AppError;
+
+pub struct IdempotencyService;
+
+impl IdempotencyService {
+    /// Claims `key` for a new request, or returns the stored outcome of the earlier request
+    /// that claimed it. Reusing a key for a different request, or while the first one is
+    /// still running, is a conflict.
+    pub async fn begin(
+        db: &DatabaseConnection,
+        user_id: i32,
+        key: &str,
+        request_hash: &str,
+        ttl: chrono::Duration,
+    ) -> Result<Option<idempotency_keys::Model>, AppError> {
+        let now = Utc::now();
+        IdempotencyKeys::delete_many()
+            .filter(idempotency_keys::Column::UserId.eq(user_id))
+            .filter(idempotency_keys::Column::IdempotencyKey.eq(key))
+            .filter(idempotency_keys::Column::ExpiresAt.lte(now))
+            .exec(db)
+            .await?;
+
+        let claim = idempotency_keys::ActiveModel {
+            user_id: Set(user_id),
+            idempotency_key: Set(key.to_string()),
+            request_hash: Set(request_hash.to_string()),
+            response_status: Set(None),
+            response_headers: Set(None),
+            response_body: Set(None),
+            created_at: Set(now),
+            expires_at: Set(now + ttl),
+        };
+        let inserted = IdempotencyKeys::insert(claim)
+            .on_conflict(
+                OnConflict::columns([
+                    idempotency_keys::Column::UserId,
+                    idempotency_keys::Column::IdempotencyKey,
+                ])
+                .do_nothing()
+                .to_owned(),
+            )
+            .exec_without_returning(db)
+            .await?;
+        if inserted == 1 {
+            return Ok(None);
+        }
+
+        let existing = IdempotencyKeys::find_by_id((user_id, key.to_string()))
+            .one(db)
+            .await?
+            .ok_or(AppError::Conflict(
+                "Idempotency-Key was released concurrently, retry the request".to_string(),
+            ))?;
+        if existing.request_hash != request_hash {
+            return Err(AppError::Conflict(
+                "Idempotency-Key was already used for a different request".to_string(),
+            ));
+        }
+        if existing.response_status.is_none() {
+            return Err(AppError::Conflict(
+                "A request with this Idempotency-Key is still being processed".to_string(),
+            ));
+        }
+        Ok(Some(existing))
+    }
+
+    /// Stores the response produced for a claimed key so retries can replay it.
+    pub async fn complete(
+        db: &DatabaseConnection,
+        user_id: i32,
+        key: &str,
+        status: u16,
+        headers: String,
+        body: String,
+    ) -> Result<(), AppError> {
+        let claim = idempotency_keys::ActiveModel {
+            user_id: Set(user_id),
+            idempotency_key: Set(key.to_string()),
+            response_status: Set(Some(status as i32)),
+            response_headers: Set(Some(headers)),
+            response_body: Set(Some(body)),
+            ..Default::default()
+        };
+        claim.update(db).await?;
+        Ok(())
+    }
+
+    /// Gives up a claimed key so that the request can be retried from scratch.
+    pub async fn release(db: &DatabaseConnection, user_id: i32, key: &str) -> Result<(), AppError> {
+        IdempotencyKeys::delete_by_id((user_id, key.to_string())).exec(db).await?;
+        Ok(())
+    }
+
+    /// Deletes keys whose replay window has passed.
+    pub async fn purge_expired(db: &DatabaseConnection) -> Result<u64, AppError> {
+        let result = IdempotencyKeys::delete_many()
+            .filter(idempotency_keys::Column::ExpiresAt.lte(Utc::now()))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+
+    /// Periodically purges expired keys; meant to be spawned once at startup.
+    pub async fn run_purge(db: DatabaseConnection, interval: Duration) {
+        let mut ticker = actix_web::rt::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(err) = Self::purge_expired(&db).await {
+                eprintln!("Purging expired idempotency keys failed: {err}");
+            }
+        }
+    }
+}
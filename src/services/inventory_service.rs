@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::Result;
+use sea_orm::sea_query::LockType;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set
+};
+use chrono::Utc;
+use serde_json::json;
+use crate::auth::current_actor;
+use crate::db::{InventoryMovements, Products, inventory_movements, products};
+use crate::db::inventory_movements::MovementReason;
+use crate::db::outbox::EventType;
+use crate::error::AppError;
+use crate::dtos::{ChangeAction, EntityKind};
+use crate::services::{ChangeService, OutboxService};
+
+/// Stock below which a product is reported as running low.
+pub const LOW_STOCK_THRESHOLD: i32 = 10;
+
+/// What caused a stock movement.
+#[derive(Clone, Copy, Debug)]
+pub enum MovementSource {
+    Order(i32),
+    Shipment(i32),
+    Manual,
+}
+
+/// A product whose `stock_quantity` disagrees with its ledger.
+#[derive(Debug)]
+pub struct StockDiscrepancy {
+    pub product_id: i32,
+    pub stock_quantity: i32,
+    pub ledger_quantity: i64,
+}
+
+pub struct InventoryService;
+
+impl InventoryService {
+    /// Adds `delta` to the product's stock and writes the matching ledger entry on `conn`,
+    /// refusing to go below zero. The product row stays locked until `conn`'s transaction
+    /// ends, so callers touching several products should do so in a stable order.
+    pub async fn record<C: ConnectionTrait>(
+        conn: &C,
+        product_id: i32,
+        delta: i32,
+        reason: MovementReason,
+        source: MovementSource,
+    ) -> Result<products::Model, AppError> {
+        let product = Products::find_by_id(product_id)
+            .lock(LockType::NoKeyUpdate)
+            .one(conn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        
+        let previous_stock = product.stock_quantity;
+        let new_stock = previous_stock + delta;
+        if new_stock < 0 {
+            return Err(AppError::validation(format!(
+                "Insufficient stock for product {}: {} available, {} requested",
+                product.product_id,
+                product.stock_quantity,
+                -delta
+            )));
+        }
+        
+        let mut product: products::ActiveModel = product.into();
+        product.stock_quantity = Set(new_stock);
+        let product = product.update(conn).await?;
+        
+        let (order_id, shipment_id) = match source {
+            MovementSource::Order(id) => (Some(id), None),
+            MovementSource::Shipment(id) => (None, Some(id)),
+            MovementSource::Manual => (None, None),
+        };
+        let movement = inventory_movements::ActiveModel {
+            product_id: Set(product_id),
+            delta: Set(delta),
+            reason: Set(reason),
+            order_id: Set(order_id),
+            shipment_id: Set(shipment_id),
+            actor: Set(current_actor()),
+            created_at: Set(Utc::now()),
+            ..Default::default()
+        };
+        movement.insert(conn).await?;
+        ChangeService::notify(conn, EntityKind::Product, product_id, ChangeAction::Updated).await?;
+        
+        // Only report crossing the threshold, not every sale while already low
+        if new_stock < LOW_STOCK_THRESHOLD && previous_stock >= LOW_STOCK_THRESHOLD {
+            OutboxService::publish(conn, EventType::ProductLowStock, "product", product.product_id, json!({
+                "product_id": product.product_id,
+                "name": product.name,
+                "stock_quantity": product.stock_quantity,
+                "threshold": LOW_STOCK_THRESHOLD,
+            })).await?;
+        }
+        
+        Ok(product)
+    }
+
+    pub async fn find_movements(db: &DatabaseConnection, product_id: i32) -> Result<Vec<inventory_movements::Model>, AppError> {
+        let movements = InventoryMovements::find()
+            .filter(inventory_movements::Column::ProductId.eq(product_id))
+            .order_by_desc(inventory_movements::Column::CreatedAt)
+            .order_by_desc(inventory_movements::Column::MovementId)
+            .all(db)
+            .await?;
+        Ok(movements)
+    }
+
+    /// Compares every product's `stock_quantity` with the sum of its ledger entries.
+    pub async fn reconcile(db: &DatabaseConnection) -> Result<Vec<StockDiscrepancy>, AppError> {
+        let ledger: HashMap<i32, i64> = InventoryMovements::find()
+            .select_only()
+            .column(inventory_movements::Column::ProductId)
+            .column_as(inventory_movements::Column::Delta.sum(), "total")
+            .group_by(inventory_movements::Column::ProductId)
+            .into_tuple::<(i32, i64)>()
+            .all(db)
+            .await?
+            .into_iter()
+            .collect();
+        
+        let products = Products::find()
+            .order_by_asc(products::Column::ProductId)
+            .all(db)
+            .await?;
+        
+        let discrepancies = products
+            .into_iter()
+            .filter_map(|product| {
+                let ledger_quantity = ledger.get(&product.product_id).copied().unwrap_or(0);
+                (ledger_quantity != i64::from(product.stock_quantity)).then_some(StockDiscrepancy {
+                    product_id: product.product_id,
+                    stock_quantity: product.stock_quantity,
+                    ledger_quantity,
+                })
+            })
+            .collect();
+        Ok(discrepancies)
+    }
+
+    /// Periodically reconciles stock against the ledger and reports any drift.
+    pub async fn run_reconciliation(db: DatabaseConnection, interval: Duration) {
+        let mut ticker = actix_web::rt::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match Self::reconcile(&db).await {
+                Ok(discrepancies) => {
+                    for d in discrepancies {
+                        eprintln!(
+                            "Stock mismatch for product {}: stock_quantity {} but ledger sums to {}",
+                            d.product_id, d.stock_quantity, d.ledger_quantity
+                        );
+                    }
+                }
+                Err(err) => eprintln!("Stock reconciliation failed: {err}"),
+            }
+        }
+    }
+}
@@ -4,6 +4,17 @@ pub mod customer_service;
 pub mod product_service;
 pub mod order_service;
 pub mod shipment_service;
+pub mod inventory_service;
+pub mod auth_service;
+pub mod user_service;
+pub mod idempotency_service;
+pub mod webhook_service;
+pub mod outbox_service;
+pub mod change_service;
+pub mod batch_service;
+pub mod audit_service;
+pub mod csv_service;
+pub mod address_service;
 
 pub use category_service::CategoryService;
 pub use supplier_service::SupplierService;
@@ -11,3 +22,14 @@ pub use customer_service::CustomerService;
 pub use product_service::ProductService;
 pub use order_service::OrderService;
 pub use shipment_service::ShipmentService;
+pub use inventory_service::InventoryService;
+pub use auth_service::AuthService;
+pub use user_service::UserService;
+pub use idempotency_service::IdempotencyService;
+pub use webhook_service::WebhookService;
+pub use outbox_service::OutboxService;
+pub use change_service::ChangeService;
+pub use batch_service::BatchService;
+pub use audit_service::AuditService;
+pub use csv_service::CsvService;
+pub use address_service::AddressService;
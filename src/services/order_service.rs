@@ -1,24 +1,64 @@
+use std::collections::{BTreeMap, HashMap};
+
 use anyhow::Result;
-use sea_orm::{ModelTrait, QueryOrder, TransactionTrait};
+use sea_orm::sea_query::LockType;
+use sea_orm::{ConnectionTrait, LoaderTrait, ModelTrait, QueryOrder, QuerySelect, TransactionTrait};
 use sea_orm::{
     ActiveModelTrait, DatabaseConnection, EntityTrait, QueryFilter, 
     ColumnTrait, Set
 };
-use chrono::Utc;
-use crate::db::{self, OrderItems, Orders};
+use chrono::{NaiveTime, Utc};
+use rust_decimal::Decimal;
+use serde_json::json;
+use crate::db::{self, CustomerAddresses, Customers, OrderItems, Orders, Products};
+use crate::db::orders::OrderStatus;
+use crate::db::audit_log::AuditEntity;
+use crate::db::inventory_movements::MovementReason;
 use crate::dtos::*;
-use crate::error::AppError;
-use crate::services::ProductService;
+use crate::concurrency::IfMatch;
+use crate::error::{AppError, FieldError};
+use crate::pagination::PageParams;
+use crate::db::outbox::EventType;
+use crate::services::{AuditService, ChangeService, InventoryService, OutboxService};
+use crate::services::inventory_service::MovementSource;
 
 pub struct OrderService;
 
 impl OrderService {
-    pub async fn find_all(db: &DatabaseConnection) -> Result<Vec<db::orders::Model>, AppError> {
-        let orders = Orders::find()
-            .order_by_desc(db::orders::Column::OrderDate)
-            .all(db)
-            .await?;
-        Ok(orders)
+    pub async fn find_all(db: &DatabaseConnection, filter: &OrderFilter, page: &PageParams) -> Result<(Vec<db::orders::Model>, u64), AppError> {
+        let mut query = Orders::find();
+        if let Some(status) = filter.status {
+            query = query.filter(db::orders::Column::Status.eq(status));
+        }
+        if let Some(customer_id) = filter.customer_id {
+            query = query.filter(db::orders::Column::CustomerId.eq(customer_id));
+        }
+        // Both ends of the date range are inclusive whole days
+        if let Some(from) = filter.from {
+            query = query.filter(db::orders::Column::OrderDate.gte(from.and_time(NaiveTime::MIN).and_utc()));
+        }
+        if let Some(to) = filter.to.and_then(|to| to.succ_opt()) {
+            query = query.filter(db::orders::Column::OrderDate.lt(to.and_time(NaiveTime::MIN).and_utc()));
+        }
+        
+        let sortable = [
+            ("order_date", db::orders::Column::OrderDate),
+            ("status", db::orders::Column::Status),
+            ("total_amount", db::orders::Column::TotalAmount),
+        ];
+        query = match page.sort_by(&sortable)? {
+            Some((column, order)) => query.order_by(column, order),
+            None => query.order_by_desc(db::orders::Column::OrderDate),
+        };
+        page.fetch(db, query.order_by_asc(db::orders::Column::OrderId)).await
+    }
+
+    /// Loads the customer of each order in a single query.
+    pub async fn load_customers(
+        db: &DatabaseConnection,
+        orders: &[db::orders::Model],
+    ) -> Result<Vec<Option<db::customers::Model>>, AppError> {
+        Ok(orders.load_one(Customers, db).await?)
     }
 
     pub async fn find_by_id(db: &DatabaseConnection, id: i32) -> Result<db::orders::Model, AppError> {
@@ -29,65 +69,102 @@ impl OrderService {
     }
 
     pub async fn create(db: &DatabaseConnection, dto: OrderCreate) -> Result<db::orders::Model, AppError> {
+        let status = dto.status.unwrap_or_default();
+        if !matches!(status, OrderStatus::Pending | OrderStatus::Confirmed) {
+            return Err(AppError::validation(format!(
+                "New orders must be pending or confirmed, not {}",
+                status.as_str()
+            )));
+        }
+        
         let txn = db.begin().await?;
         
+        if let Some(customer) = Customers::find_by_id(dto.customer_id).one(&txn).await?
+            && customer.deleted_at.is_some()
+        {
+            return Err(AppError::validation(format!("Customer {} has been deleted", dto.customer_id)));
+        }
+        let shipping_address = Self::shipping_address(&txn, dto.customer_id, dto.shipping_address, dto.shipping_address_id).await?;
+        
         // Create the order
         let order = db::orders::ActiveModel {
             customer_id: Set(dto.customer_id),
             order_date: Set(Utc::now()),
-            status: Set(dto.status.unwrap_or_else(|| "pending".to_string())),
-            total_amount: Set(dto.total_amount),
-            shipping_address: Set(dto.shipping_address),
+            status: Set(status),
+            total_amount: Set(Decimal::ZERO),
+            shipping_address: Set(shipping_address),
             ..Default::default()
         };
         
         let order = order.insert(&txn).await?;
         
-        // Create order items
-        for item in dto.items {
-            let order_item = db::order_items::ActiveModel {
-                order_id: Set(order.order_id),
-                product_id: Set(item.product_id),
-                quantity: Set(item.quantity),
-                unit_price: Set(item.unit_price),
-                ..Default::default()
-            };
-            order_item.insert(&txn).await?;
-            
-            // Update product stock
-            let product = ProductService::find_by_id(&db, item.product_id).await?;
-            let new_stock = product.stock_quantity - item.quantity;
-            if new_stock < 0 {
-                return Err(AppError::Validation("Insufficient stock".to_string()));
-            }
-            
-            let mut product: db::products::ActiveModel = product.into();
-            product.stock_quantity = Set(new_stock);
-            product.update(&txn).await?;
-        }
+        let items = Self::insert_items(&txn, order.order_id, dto.items).await?;
+        Self::apply_stock_changes(&txn, order.order_id, &[], &items, MovementReason::Adjustment).await?;
+        
+        let mut order: db::orders::ActiveModel = order.into();
+        order.total_amount = Set(Self::total(&items));
+        let order = order.update(&txn).await?;
+        
+        OutboxService::publish(&txn, EventType::OrderCreated, "order", order.order_id, json!({
+            "order_id": order.order_id,
+            "customer_id": order.customer_id,
+            "status": order.status,
+            "total_amount": order.total_amount,
+            "shipping_address": order.shipping_address,
+            "order_date": order.order_date,
+            "items": items.iter().map(|item| json!({
+                "product_id": item.product_id,
+                "quantity": item.quantity,
+                "unit_price": item.unit_price,
+            })).collect::<Vec<_>>(),
+        })).await?;
+        ChangeService::notify(&txn, EntityKind::Order, order.order_id, ChangeAction::Created).await?;
+        AuditService::record(&txn, AuditEntity::Order, order.order_id, None, Some(&order)).await?;
         
         txn.commit().await?;
         Ok(order)
     }
 
-    pub async fn update(db: &DatabaseConnection, id: i32, dto: OrderUpdate) -> Result<db::orders::Model, AppError> {
-        let order = Self::find_by_id(db, id).await?;
+    pub async fn update(db: &DatabaseConnection, id: i32, if_match: &IfMatch, dto: OrderUpdate) -> Result<db::orders::Model, AppError> {
+        let txn = db.begin().await?;
+        
+        let order = Orders::find_by_id(id)
+            .lock(LockType::NoKeyUpdate)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if_match.check(order.version)?;
+        let before = order.clone();
+        let previous_status = order.status;
+        
+        if let Some(status) = dto.status
+            && status != order.status
+        {
+            Self::check_transition(&order, status)?;
+        }
         
         let mut order: db::orders::ActiveModel = order.into();
         
         if let Some(status) = dto.status {
             order.status = Set(status);
         }
-        if let Some(total_amount) = dto.total_amount {
-            order.total_amount = Set(total_amount);
-        }
         if let Some(shipping_address) = dto.shipping_address {
             order.shipping_address = Set(shipping_address);
         }
         
         // Handle items update if provided
         if let Some(items) = dto.items {
-            let txn = db.begin().await?;
+            if !previous_status.reserves_stock() {
+                return Err(AppError::Conflict(format!(
+                    "Items of a {} order cannot be changed",
+                    previous_status.as_str()
+                )));
+            }
+            
+            let previous_items = OrderItems::find()
+                .filter(db::order_items::Column::OrderId.eq(id))
+                .all(&txn)
+                .await?;
             
             // Delete existing items
             OrderItems::delete_many()
@@ -95,34 +172,215 @@ impl OrderService {
                 .exec(&txn)
                 .await?;
             
-            // Add new items
-            for item in items {
-                let order_item = db::order_items::ActiveModel {
-                    order_id: Set(id),
-                    product_id: Set(item.product_id),
-                    quantity: Set(item.quantity),
-                    unit_price: Set(item.unit_price),
-                    ..Default::default()
-                };
-                order_item.insert(&txn).await?;
-            }
-            
-            txn.commit().await?;
+            // Add new items and move only the difference in stock. Items only change
+            // before the order ships, so anything given back is an adjustment.
+            let items = Self::insert_items(&txn, id, items).await?;
+            Self::apply_stock_changes(&txn, id, &previous_items, &items, MovementReason::Adjustment).await?;
+            order.total_amount = Set(Self::total(&items));
+        }
+        
+        let order = order.update(&txn).await?;
+        
+        if previous_status != OrderStatus::Cancelled && order.status == OrderStatus::Cancelled {
+            let items = order.find_related(OrderItems).all(&txn).await?;
+            Self::apply_stock_changes(&txn, id, &items, &[], MovementReason::Cancel).await?;
         }
+        Self::notify_status_change(&txn, &order, previous_status).await?;
+        ChangeService::notify(&txn, EntityKind::Order, id, ChangeAction::Updated).await?;
+        AuditService::record(&txn, AuditEntity::Order, id, Some(&before), Some(&order)).await?;
         
-        Ok(order.update(db).await?)
+        txn.commit().await?;
+        Ok(order)
     }
 
-    pub async fn delete(db: &DatabaseConnection, id: i32) -> Result<(), AppError> {
-        let order = Self::find_by_id(db, id).await?;
+    pub async fn transition(db: &DatabaseConnection, id: i32, status: OrderStatus) -> Result<db::orders::Model, AppError> {
+        let txn = db.begin().await?;
+        
+        let order = Orders::find_by_id(id)
+            .lock(LockType::NoKeyUpdate)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        Self::check_transition(&order, status)?;
+        let before = order.clone();
+        
+        if status == OrderStatus::Cancelled {
+            let items = order.find_related(OrderItems).all(&txn).await?;
+            Self::apply_stock_changes(&txn, id, &items, &[], MovementReason::Cancel).await?;
+        }
+        
+        let previous_status = order.status;
+        let mut order: db::orders::ActiveModel = order.into();
+        order.status = Set(status);
+        let order = order.update(&txn).await?;
+        Self::notify_status_change(&txn, &order, previous_status).await?;
+        ChangeService::notify(&txn, EntityKind::Order, id, ChangeAction::Updated).await?;
+        AuditService::record(&txn, AuditEntity::Order, id, Some(&before), Some(&order)).await?;
+        
+        txn.commit().await?;
+        Ok(order)
+    }
+
+    async fn notify_status_change<C: ConnectionTrait>(
+        conn: &C,
+        order: &db::orders::Model,
+        previous_status: OrderStatus,
+    ) -> Result<(), AppError> {
+        if order.status == previous_status {
+            return Ok(());
+        }
+        OutboxService::publish(conn, EventType::OrderStatusChanged, "order", order.order_id, json!({
+            "order_id": order.order_id,
+            "customer_id": order.customer_id,
+            "previous_status": previous_status,
+            "status": order.status,
+        })).await
+    }
+
+    fn check_transition(order: &db::orders::Model, status: OrderStatus) -> Result<(), AppError> {
+        if order.status.can_transition_to(status) {
+            Ok(())
+        } else {
+            Err(AppError::Conflict(format!(
+                "Order {} cannot move from {} to {}",
+                order.order_id,
+                order.status.as_str(),
+                status.as_str()
+            )))
+        }
+    }
+
+    pub async fn delete(db: &DatabaseConnection, id: i32, if_match: &IfMatch) -> Result<(), AppError> {
+        let txn = db.begin().await?;
+        
+        let order = Orders::find_by_id(id)
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if_match.check(order.version)?;
+        AuditService::record(&txn, AuditEntity::Order, id, Some(&order), None).await?;
+        
+        // Shipped and delivered goods have left the warehouse, so only
+        // orders still holding stock give it back.
+        if order.status.reserves_stock() {
+            let items = order.find_related(OrderItems).all(&txn).await?;
+            Self::apply_stock_changes(&txn, id, &items, &[], MovementReason::Cancel).await?;
+        }
+        
         let order: db::orders::ActiveModel = order.into();
-        order.delete(db).await?;
+        order.delete(&txn).await?;
+        ChangeService::notify(&txn, EntityKind::Order, id, ChangeAction::Deleted).await?;
+        
+        txn.commit().await?;
         Ok(())
     }
 
-    pub async fn find_with_details(db: &DatabaseConnection, id: i32) -> Result<(db::orders::Model, Vec<db::order_items::Model>), AppError> {
+    pub async fn find_with_details(
+        db: &DatabaseConnection,
+        id: i32,
+    ) -> Result<(db::orders::Model, Vec<(db::order_items::Model, Option<db::products::Model>)>), AppError> {
         let order = Self::find_by_id(db, id).await?;
         let items = order.find_related(OrderItems).all(db).await?;
-        Ok((order, items))
+        let products = items.load_one(Products, db).await?;
+        Ok((order, items.into_iter().zip(products).collect()))
+    }
+
+    /// The address an order ships to: the text given, else a copy of the customer's address
+    /// given, else a copy of the customer's default shipping address.
+    async fn shipping_address<C: ConnectionTrait>(
+        conn: &C,
+        customer_id: i32,
+        text: Option<String>,
+        address_id: Option<i32>,
+    ) -> Result<String, AppError> {
+        if let Some(text) = text {
+            return Ok(text);
+        }
+        let query = CustomerAddresses::find().filter(db::customer_addresses::Column::CustomerId.eq(customer_id));
+        let (query, field, message) = match address_id {
+            Some(address_id) => (
+                query.filter(db::customer_addresses::Column::AddressId.eq(address_id)),
+                "shipping_address_id",
+                format!("is not an address of customer {}", customer_id),
+            ),
+            None => (
+                query.filter(db::customer_addresses::Column::IsDefaultShipping.eq(true)),
+                "shipping_address",
+                "is required when the customer has no default shipping address".to_string(),
+            ),
+        };
+        match query.one(conn).await? {
+            Some(address) => Ok(address.one_line()),
+            None => Err(AppError::Validation {
+                message: "1 field(s) failed validation".to_string(),
+                details: vec![FieldError { field: field.to_string(), message }],
+            }),
+        }
+    }
+
+    /// Inserts the items priced at each product's current price.
+    async fn insert_items<C: ConnectionTrait>(
+        conn: &C,
+        order_id: i32,
+        items: Vec<OrderItemCreate>,
+    ) -> Result<Vec<db::order_items::Model>, AppError> {
+        let product_ids: Vec<i32> = items.iter().map(|item| item.product_id).collect();
+        let prices: HashMap<i32, Decimal> = Products::find()
+            .filter(db::products::Column::ProductId.is_in(product_ids))
+            .filter(db::products::Column::DeletedAt.is_null())
+            .all(conn)
+            .await?
+            .into_iter()
+            .map(|product| (product.product_id, product.price))
+            .collect();
+        
+        let mut inserted = Vec::with_capacity(items.len());
+        for item in items {
+            let unit_price = *prices.get(&item.product_id).ok_or_else(|| {
+                AppError::validation(format!("Product {} does not exist", item.product_id))
+            })?;
+            let order_item = db::order_items::ActiveModel {
+                order_id: Set(order_id),
+                product_id: Set(item.product_id),
+                quantity: Set(item.quantity),
+                unit_price: Set(unit_price),
+            };
+            inserted.push(order_item.insert(conn).await?);
+        }
+        Ok(inserted)
+    }
+
+    fn total(items: &[db::order_items::Model]) -> Decimal {
+        items.iter().map(db::order_items::Model::line_total).sum()
+    }
+
+    /// Moves stock by the per-product difference between the items an order
+    /// held before and the items it holds now. Stock the order gives back is
+    /// booked with `released`, what it takes as a sale.
+    async fn apply_stock_changes<C: ConnectionTrait>(
+        conn: &C,
+        order_id: i32,
+        before: &[db::order_items::Model],
+        after: &[db::order_items::Model],
+        released: MovementReason,
+    ) -> Result<(), AppError> {
+        let mut deltas: BTreeMap<i32, i32> = BTreeMap::new();
+        for item in before {
+            *deltas.entry(item.product_id).or_default() += item.quantity;
+        }
+        for item in after {
+            *deltas.entry(item.product_id).or_default() -= item.quantity;
+        }
+        
+        for (product_id, delta) in deltas {
+            let reason = match delta.signum() {
+                0 => continue,
+                1 => released,
+                _ => MovementReason::Sale,
+            };
+            InventoryService::record(conn, product_id, delta, reason, MovementSource::Order(order_id)).await?;
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, Order,
+    QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait
+};
+use sea_orm::sea_query::Expr;
+use chrono::Utc;
+use crate::config::OutboxConfig;
+use crate::db::{Outbox, outbox};
+use crate::db::outbox::EventType;
+use crate::error::AppError;
+use crate::services::WebhookService;
+
+pub struct OutboxService;
+
+impl OutboxService {
+    /// Records an event on `conn`, which should be the transaction making the change it
+    /// describes, so the event is stored if and only if the change is committed.
+    pub async fn publish<C: ConnectionTrait>(
+        conn: &C,
+        event_type: EventType,
+        aggregate_type: &str,
+        aggregate_id: i32,
+        payload: serde_json::Value,
+    ) -> Result<(), AppError> {
+        let event = outbox::ActiveModel {
+            event_type: Set(event_type),
+            aggregate_type: Set(aggregate_type.to_string()),
+            aggregate_id: Set(aggregate_id),
+            payload: Set(payload.to_string()),
+            created_at: Set(Utc::now()),
+            published_at: Set(None),
+            ..Default::default()
+        };
+        event.insert(conn).await?;
+        Ok(())
+    }
+
+    /// Hands the oldest unpublished events to every consumer and marks them published,
+    /// all in one transaction. The rows are locked without skipping, so concurrent relays
+    /// take turns. If anything fails the batch is rolled back and relayed again on the
+    /// next pass.
+    ///
+    /// Ids are drawn when events are written, not when they commit, so an event can become
+    /// visible after one with a higher id. Events are therefore relayed in the order of the
+    /// transactions that wrote them, and only once every older transaction has ended: no
+    /// event can still turn up ahead of one already relayed. A transaction left open holds
+    /// back the events of every transaction that began writing after it.
+    pub async fn relay(db: &DatabaseConnection, batch_size: u64) -> Result<Vec<outbox::Model>, AppError> {
+        let txn = db.begin().await?;
+        
+        let events = Outbox::find()
+            .filter(outbox::Column::PublishedAt.is_null())
+            .filter(Expr::cust("xact_id < pg_snapshot_xmin(pg_current_snapshot())"))
+            .order_by(Expr::cust("xact_id"), Order::Asc)
+            .order_by_asc(outbox::Column::OutboxId)
+            .limit(batch_size)
+            .lock_exclusive()
+            .all(&txn)
+            .await?;
+        
+        let now = Utc::now();
+        let mut published = Vec::with_capacity(events.len());
+        for event in events {
+            WebhookService::enqueue(&txn, &event).await?;
+            
+            let mut event: outbox::ActiveModel = event.into();
+            event.published_at = Set(Some(now));
+            published.push(event.update(&txn).await?);
+        }
+        
+        txn.commit().await?;
+        Ok(published)
+    }
+
+    /// Relays events every `poll_interval_secs`; meant to be spawned once at startup.
+    pub async fn run_relay(db: DatabaseConnection, config: OutboxConfig) {
+        let mut ticker = actix_web::rt::time::interval(Duration::from_secs(config.poll_interval_secs));
+        loop {
+            ticker.tick().await;
+            // Keep going while full batches come back, so a backlog drains quickly
+            loop {
+                match Self::relay(&db, config.batch_size).await {
+                    Ok(events) if events.len() as u64 == config.batch_size => continue,
+                    Ok(_) => break,
+                    Err(err) => {
+                        eprintln!("Outbox relay failed: {err}");
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
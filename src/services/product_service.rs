@@ -1,45 +1,265 @@
 use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use sea_orm::sea_query::{Expr, LockType, SimpleExpr};
 use sea_orm::{
-    ActiveModelTrait, DatabaseConnection, EntityTrait, QueryOrder, Set
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, FromQueryResult, LoaderTrait,
+    Order, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, Select, Set, TransactionTrait
 };
-use crate::db::{Products, products};
+use crate::db::{Categories, Products, Suppliers, categories, products, suppliers};
+use crate::db::inventory_movements::MovementReason;
+use crate::db::audit_log::AuditEntity;
 use crate::dtos::*;
-use crate::error::AppError;
+use crate::concurrency::IfMatch;
+use crate::error::{AppError, FieldError};
+use crate::pagination::PageParams;
+use crate::services::{AuditService, ChangeService, InventoryService};
+use crate::services::inventory_service::MovementSource;
+
+/// Upper ends of the price ranges that search results are counted in.
+const PRICE_BREAKS: [i64; 6] = [25, 50, 100, 250, 500, 1000];
 
 pub struct ProductService;
 
+/// A product found by `ProductService::search`.
+#[derive(Debug, FromQueryResult)]
+pub struct ProductMatch {
+    pub product_id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub price: Decimal,
+    pub stock_quantity: i32,
+    pub category_id: i32,
+    pub supplier_id: i32,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub category_name: Option<String>,
+    pub supplier_name: Option<String>,
+    pub rank: f32,
+    pub name_highlight: String,
+    pub snippet: Option<String>,
+}
+
+#[derive(FromQueryResult)]
+struct FacetRow {
+    id: i32,
+    name: String,
+    count: i64,
+}
+
+#[derive(FromQueryResult)]
+struct BucketRow {
+    bucket: i32,
+    count: i64,
+}
+
 impl ProductService {
-    pub async fn find_all(db: &DatabaseConnection) -> Result<Vec<products::Model>, AppError> {
-        let products = Products::find()
-            .order_by_asc(products::Column::Name)
+    pub async fn find_all(
+        db: &DatabaseConnection,
+        filter: &ProductFilter,
+        page: &PageParams,
+        include_deleted: bool,
+    ) -> Result<(Vec<products::Model>, u64), AppError> {
+        let mut query = Self::filtered(Products::find(), filter);
+        if !include_deleted {
+            query = query.filter(products::Column::DeletedAt.is_null());
+        }
+        
+        let sortable = [
+            ("name", products::Column::Name),
+            ("price", products::Column::Price),
+            ("stock_quantity", products::Column::StockQuantity),
+        ];
+        query = match page.sort_by(&sortable)? {
+            Some((column, order)) => query.order_by(column, order),
+            None => query.order_by_asc(products::Column::Name),
+        };
+        page.fetch(db, query.order_by_asc(products::Column::ProductId)).await
+    }
+
+    fn filtered(mut query: Select<Products>, filter: &ProductFilter) -> Select<Products> {
+        if let Some(category_id) = filter.category_id {
+            query = query.filter(products::Column::CategoryId.eq(category_id));
+        }
+        if let Some(supplier_id) = filter.supplier_id {
+            query = query.filter(products::Column::SupplierId.eq(supplier_id));
+        }
+        if let Some(price_min) = filter.price_min {
+            query = query.filter(products::Column::Price.gte(price_min));
+        }
+        if let Some(price_max) = filter.price_max {
+            query = query.filter(products::Column::Price.lte(price_max));
+        }
+        query
+    }
+
+    /// Products matching every word of `q`, best matches first unless `page` sorts otherwise.
+    pub async fn search(
+        db: &DatabaseConnection,
+        q: &str,
+        filter: &ProductFilter,
+        page: &PageParams,
+    ) -> Result<(Vec<ProductMatch>, u64), AppError> {
+        let tsquery = prefix_query(q)?;
+        let query = Self::matching(&tsquery, filter)
+            .left_join(Categories)
+            .left_join(Suppliers)
+            .column_as(categories::Column::Name, "category_name")
+            .column_as(suppliers::Column::CompanyName, "supplier_name")
+            .column_as(with_tsquery("ts_rank_cd(products.search_vector, to_tsquery('english', $1))", &tsquery), "rank")
+            .column_as(with_tsquery(&format!("ts_headline('english', {}, to_tsquery('english', $1), 'StartSel=<mark>, StopSel=</mark>, HighlightAll=true')", html_escaped("products.name")), &tsquery), "name_highlight")
+            .column_as(with_tsquery(&format!("ts_headline('english', {}, to_tsquery('english', $1), 'StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MinWords=8, MaxWords=20')", html_escaped("products.description")), &tsquery), "snippet");
+        
+        let sortable = [
+            ("name", products::Column::Name),
+            ("price", products::Column::Price),
+        ];
+        let query = match page.sort_by(&sortable)? {
+            Some((column, order)) => query.order_by(column, order),
+            None => query.order_by(Expr::cust("rank"), Order::Desc),
+        };
+        
+        let paginator = query
+            .order_by_asc(products::Column::ProductId)
+            .into_model::<ProductMatch>()
+            .paginate(db, page.per_page());
+        let total = paginator.num_items().await?;
+        let matches = paginator.fetch_page(page.page() - 1).await?;
+        Ok((matches, total))
+    }
+
+    /// Counts the matches of `q` per category, supplier and price range. Each facet is
+    /// counted without its own filter, so that it shows what choosing another value would give.
+    pub async fn search_facets(db: &DatabaseConnection, q: &str, filter: &ProductFilter) -> Result<ProductFacets, AppError> {
+        let tsquery = prefix_query(q)?;
+        
+        let categories = Self::matching(&tsquery, &ProductFilter { category_id: None, ..filter.clone() })
+            .select_only()
+            .inner_join(Categories)
+            .column_as(products::Column::CategoryId, "id")
+            .column_as(categories::Column::Name, "name")
+            .column_as(products::Column::ProductId.count(), "count")
+            .group_by(products::Column::CategoryId)
+            .group_by(categories::Column::Name)
+            .order_by(Expr::cust("count"), Order::Desc)
+            .order_by_asc(categories::Column::Name)
+            .into_model::<FacetRow>()
+            .all(db)
+            .await?;
+        
+        let suppliers = Self::matching(&tsquery, &ProductFilter { supplier_id: None, ..filter.clone() })
+            .select_only()
+            .inner_join(Suppliers)
+            .column_as(products::Column::SupplierId, "id")
+            .column_as(suppliers::Column::CompanyName, "name")
+            .column_as(products::Column::ProductId.count(), "count")
+            .group_by(products::Column::SupplierId)
+            .group_by(suppliers::Column::CompanyName)
+            .order_by(Expr::cust("count"), Order::Desc)
+            .order_by_asc(suppliers::Column::CompanyName)
+            .into_model::<FacetRow>()
             .all(db)
             .await?;
-        Ok(products)
+        
+        let breaks = PRICE_BREAKS.map(|limit| limit.to_string()).join(", ");
+        let bucket = Expr::cust(format!("width_bucket(products.price, ARRAY[{}]::numeric[])", breaks));
+        let prices = Self::matching(&tsquery, &ProductFilter { price_min: None, price_max: None, ..filter.clone() })
+            .select_only()
+            .column_as(bucket.clone(), "bucket")
+            .column_as(products::Column::ProductId.count(), "count")
+            .group_by(bucket)
+            .order_by(Expr::cust("bucket"), Order::Asc)
+            .into_model::<BucketRow>()
+            .all(db)
+            .await?;
+        
+        let facet = |row: FacetRow| FacetCount { id: row.id, name: row.name, count: row.count };
+        Ok(ProductFacets {
+            categories: categories.into_iter().map(facet).collect(),
+            suppliers: suppliers.into_iter().map(facet).collect(),
+            prices: prices
+                .into_iter()
+                .map(|row| {
+                    let bucket = row.bucket as usize;
+                    PriceFacet {
+                        min: bucket.checked_sub(1).map_or(Decimal::ZERO, |i| Decimal::from(PRICE_BREAKS[i])),
+                        max: PRICE_BREAKS.get(bucket).map(|&limit| Decimal::from(limit)),
+                        count: row.count,
+                    }
+                })
+                .collect(),
+        })
     }
 
-    pub async fn find_by_id(db: &DatabaseConnection, id: i32) -> Result<products::Model, AppError> {
+    fn matching(tsquery: &str, filter: &ProductFilter) -> Select<Products> {
+        let query = Products::find()
+            .filter(products::Column::DeletedAt.is_null())
+            .filter(with_tsquery("products.search_vector @@ to_tsquery('english', $1)", tsquery));
+        Self::filtered(query, filter)
+    }
+
+    /// Loads the category and supplier of each product with one query per relation.
+    pub async fn load_related(
+        db: &DatabaseConnection,
+        products: &[products::Model],
+    ) -> Result<Vec<(Option<categories::Model>, Option<suppliers::Model>)>, AppError> {
+        let categories = products.load_one(Categories, db).await?;
+        let suppliers = products.load_one(Suppliers, db).await?;
+        Ok(categories.into_iter().zip(suppliers).collect())
+    }
+
+    pub async fn find_by_id(db: &DatabaseConnection, id: i32, include_deleted: bool) -> Result<products::Model, AppError> {
         Products::find_by_id(id)
             .one(db)
             .await?
+            .filter(|product| include_deleted || product.deleted_at.is_none())
             .ok_or(AppError::NotFound)
     }
 
-    pub async fn create(db: &DatabaseConnection, dto: ProductCreate) -> Result<products::Model, AppError> {
+    pub async fn create<C: ConnectionTrait + TransactionTrait>(db: &C, dto: ProductCreate) -> Result<products::Model, AppError> {
+        let txn = db.begin().await?;
+        Self::check_references(&txn, Some(dto.category_id), Some(dto.supplier_id)).await?;
+        
+        // Stock starts at zero and the initial quantity goes through the ledger
         let product = products::ActiveModel {
             name: Set(dto.name),
             description: Set(dto.description),
             price: Set(dto.price),
-            stock_quantity: Set(dto.stock_quantity),
+            stock_quantity: Set(0),
             category_id: Set(dto.category_id),
             supplier_id: Set(dto.supplier_id),
             ..Default::default()
         };
+        let mut product = product.insert(&txn).await?;
+        ChangeService::notify(&txn, EntityKind::Product, product.product_id, ChangeAction::Created).await?;
+        
+        if dto.stock_quantity != 0 {
+            product = InventoryService::record(
+                &txn,
+                product.product_id,
+                dto.stock_quantity,
+                MovementReason::Adjustment,
+                MovementSource::Manual,
+            ).await?;
+        }
+        AuditService::record(&txn, AuditEntity::Product, product.product_id, None, Some(&product)).await?;
         
-        Ok(product.insert(db).await?)
+        txn.commit().await?;
+        Ok(product)
     }
 
-    pub async fn update(db: &DatabaseConnection, id: i32, dto: ProductUpdate) -> Result<products::Model, AppError> {
-        let product = Self::find_by_id(db, id).await?;
+    pub async fn update<C: ConnectionTrait + TransactionTrait>(db: &C, id: i32, if_match: &IfMatch, dto: ProductUpdate) -> Result<products::Model, AppError> {
+        let txn = db.begin().await?;
+        
+        let product = Products::find_by_id(id)
+            .filter(products::Column::DeletedAt.is_null())
+            .lock(LockType::NoKeyUpdate)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if_match.check(product.version)?;
+        Self::check_references(&txn, dto.category_id, dto.supplier_id).await?;
+        let before = product.clone();
+        let previous_stock = product.stock_quantity;
         
         let mut product: products::ActiveModel = product.into();
         
@@ -52,9 +272,6 @@ impl ProductService {
         if let Some(price) = dto.price {
             product.price = Set(price);
         }
-        if let Some(stock_quantity) = dto.stock_quantity {
-            product.stock_quantity = Set(stock_quantity);
-        }
         if let Some(category_id) = dto.category_id {
             product.category_id = Set(category_id);
         }
@@ -62,20 +279,191 @@ impl ProductService {
             product.supplier_id = Set(supplier_id);
         }
         
-        Ok(product.update(db).await?)
+        let mut product = product.update(&txn).await?;
+        
+        if let Some(stock_quantity) = dto.stock_quantity
+            && stock_quantity != previous_stock
+        {
+            product = InventoryService::record(
+                &txn,
+                id,
+                stock_quantity - previous_stock,
+                MovementReason::Adjustment,
+                MovementSource::Manual,
+            ).await?;
+        }
+        ChangeService::notify(&txn, EntityKind::Product, id, ChangeAction::Updated).await?;
+        AuditService::record(&txn, AuditEntity::Product, id, Some(&before), Some(&product)).await?;
+        
+        txn.commit().await?;
+        Ok(product)
     }
 
-    pub async fn delete(db: &DatabaseConnection, id: i32) -> Result<(), AppError> {
-        let product = Self::find_by_id(db, id).await?;
-        let product: products::ActiveModel = product.into();
-        product.delete(db).await?;
+    /// Soft-deletes the product; it stays in the table until restored.
+    pub async fn delete<C: ConnectionTrait + TransactionTrait>(db: &C, id: i32, if_match: &IfMatch) -> Result<(), AppError> {
+        let txn = db.begin().await?;
+        
+        let product = Products::find_by_id(id)
+            .filter(products::Column::DeletedAt.is_null())
+            .lock(LockType::NoKeyUpdate)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if_match.check(product.version)?;
+        AuditService::record(&txn, AuditEntity::Product, id, Some(&product), None).await?;
+        
+        let mut product: products::ActiveModel = product.into();
+        product.deleted_at = Set(Some(Utc::now()));
+        product.update(&txn).await?;
+        ChangeService::notify(&txn, EntityKind::Product, id, ChangeAction::Deleted).await?;
+        
+        txn.commit().await?;
         Ok(())
     }
 
-    pub async fn update_stock(db: &DatabaseConnection, id: i32, new_quantity: i32) -> Result<products::Model, AppError> {
-        let product = Self::find_by_id(db, id).await?;
+    pub async fn restore(db: &DatabaseConnection, id: i32, if_match: &IfMatch) -> Result<products::Model, AppError> {
+        let txn = db.begin().await?;
+        
+        let product = Products::find_by_id(id)
+            .lock(LockType::NoKeyUpdate)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if_match.check(product.version)?;
+        if product.deleted_at.is_none() {
+            return Err(AppError::Conflict(format!("Product {} is not deleted", id)));
+        }
+        let before = product.clone();
+        
         let mut product: products::ActiveModel = product.into();
-        product.stock_quantity = Set(new_quantity);
-        Ok(product.update(db).await?)
+        product.deleted_at = Set(None);
+        let product = product.update(&txn).await?;
+        ChangeService::notify(&txn, EntityKind::Product, id, ChangeAction::Created).await?;
+        AuditService::record(&txn, AuditEntity::Product, id, Some(&before), Some(&product)).await?;
+        
+        txn.commit().await?;
+        Ok(product)
+    }
+
+    /// Rejects a soft-deleted category or supplier. Ones that do not exist at all are left
+    /// to the foreign keys.
+    async fn check_references<C: ConnectionTrait>(
+        conn: &C,
+        category_id: Option<i32>,
+        supplier_id: Option<i32>,
+    ) -> Result<(), AppError> {
+        let mut details = Vec::new();
+        if let Some(category_id) = category_id
+            && let Some(category) = Categories::find_by_id(category_id).one(conn).await?
+            && category.deleted_at.is_some()
+        {
+            details.push(FieldError {
+                field: "category_id".to_string(),
+                message: format!("category {} has been deleted", category_id),
+            });
+        }
+        if let Some(supplier_id) = supplier_id
+            && let Some(supplier) = Suppliers::find_by_id(supplier_id).one(conn).await?
+            && supplier.deleted_at.is_some()
+        {
+            details.push(FieldError {
+                field: "supplier_id".to_string(),
+                message: format!("supplier {} has been deleted", supplier_id),
+            });
+        }
+        if details.is_empty() {
+            return Ok(());
+        }
+        Err(AppError::Validation {
+            message: format!("{} field(s) failed validation", details.len()),
+            details,
+        })
+    }
+
+    /// Sets the stock to `new_quantity`, booking the difference as an adjustment. The row
+    /// stays locked until then, so a sale or receipt cannot slip in between.
+    pub async fn update_stock(db: &DatabaseConnection, id: i32, new_quantity: i32) -> Result<products::Model, AppError> {
+        let txn = db.begin().await?;
+        
+        let product = Products::find_by_id(id)
+            .lock(LockType::NoKeyUpdate)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        
+        let product = if new_quantity != product.stock_quantity {
+            let updated = InventoryService::record(
+                &txn,
+                id,
+                new_quantity - product.stock_quantity,
+                MovementReason::Adjustment,
+                MovementSource::Manual,
+            ).await?;
+            AuditService::record(&txn, AuditEntity::Product, id, Some(&product), Some(&updated)).await?;
+            updated
+        } else {
+            product
+        };
+        
+        txn.commit().await?;
+        Ok(product)
+    }
+}
+
+/// Requires every word of `q`, each matching as a prefix. Anything but letters and digits
+/// separates words, which keeps tsquery operators in the input from taking effect.
+fn prefix_query(q: &str) -> Result<String, AppError> {
+    let terms: Vec<String> = q
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("{}:*", word))
+        .collect();
+    if terms.is_empty() {
+        return Err(AppError::Validation {
+            message: "1 field(s) failed validation".to_string(),
+            details: vec![FieldError { field: "q".to_string(), message: "must contain a word to search for".to_string() }],
+        });
+    }
+    Ok(terms.join(" & "))
+}
+
+/// SQL escaping the HTML special characters in `column`. `ts_headline` passes markup in the
+/// text through untouched, so it must only ever see escaped text for its output to be safe.
+fn html_escaped(column: &str) -> String {
+    format!(
+        r#"replace(replace(replace(replace(replace({column}, '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), '"', '&quot;'), '''', '&#39;')"#
+    )
+}
+
+/// The SQL expression `sql` with `tsquery` bound to its `$1`.
+fn with_tsquery(sql: &str, tsquery: &str) -> SimpleExpr {
+    Expr::cust_with_values(sql, [tsquery.to_string()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::prefix_query;
+    use crate::error::AppError;
+
+    #[test]
+    fn every_word_becomes_a_required_prefix() {
+        assert_eq!(prefix_query("wire head").unwrap(), "wire:* & head:*");
+        assert_eq!(prefix_query("  Laptop  ").unwrap(), "Laptop:*");
+    }
+
+    #[test]
+    fn tsquery_operators_are_dropped() {
+        assert_eq!(prefix_query("a&b | !c:* (d)").unwrap(), "a:* & b:* & c:* & d:*");
+        assert_eq!(prefix_query("o'reilly").unwrap(), "o:* & reilly:*");
+    }
+
+    #[test]
+    fn a_query_without_words_is_rejected() {
+        for q in ["", "   ", "&|!()", ":*"] {
+            match prefix_query(q) {
+                Err(AppError::Validation { details, .. }) => assert_eq!(details[0].field, "q"),
+                other => panic!("{q:?} gave {other:?}"),
+            }
+        }
     }
 }
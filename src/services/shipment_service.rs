@@ -1,23 +1,61 @@
 use anyhow::Result;
-use sea_orm::{ModelTrait, QueryOrder, TransactionTrait};
+use sea_orm::sea_query::LockType;
+use sea_orm::sea_query::Expr;
+use sea_orm::{ConnectionTrait, LoaderTrait, ModelTrait, QueryOrder, QuerySelect, TransactionTrait};
 use sea_orm::{
     ActiveModelTrait, DatabaseConnection, EntityTrait, QueryFilter, 
     ColumnTrait, Set
 };
-use crate::db::{ShipmentItems, Shipments, products, shipments_items, shipments};
+use crate::db::{Products, ShipmentItems, Shipments, Suppliers, products, shipments_items, shipments, suppliers};
+use crate::db::inventory_movements::MovementReason;
+use crate::db::shipments::ShipmentStatus;
+use crate::db::audit_log::AuditEntity;
+use serde_json::json;
 use crate::dtos::*;
+use crate::concurrency::IfMatch;
 use crate::error::AppError;
-use crate::services::ProductService;
+use crate::pagination::PageParams;
+use crate::db::outbox::EventType;
+use crate::services::{AuditService, ChangeService, InventoryService, OutboxService};
+use crate::services::inventory_service::MovementSource;
 
 pub struct ShipmentService;
 
 impl ShipmentService {
-    pub async fn find_all(db: &DatabaseConnection) -> Result<Vec<shipments::Model>, AppError> {
-        let shipments = Shipments::find()
-            .order_by_desc(shipments::Column::ShipmentDate)
-            .all(db)
-            .await?;
-        Ok(shipments)
+    pub async fn find_all(db: &DatabaseConnection, filter: &ShipmentFilter, page: &PageParams) -> Result<(Vec<shipments::Model>, u64), AppError> {
+        let mut query = Shipments::find();
+        if let Some(status) = filter.status {
+            query = query.filter(shipments::Column::Status.eq(status));
+        }
+        if let Some(supplier_id) = filter.supplier_id {
+            query = query.filter(shipments::Column::SupplierId.eq(supplier_id));
+        }
+        if let Some(from) = filter.from {
+            query = query.filter(shipments::Column::ShipmentDate.gte(from));
+        }
+        if let Some(to) = filter.to {
+            query = query.filter(shipments::Column::ShipmentDate.lte(to));
+        }
+        
+        let sortable = [
+            ("shipment_date", shipments::Column::ShipmentDate),
+            ("expected_delivery_date", shipments::Column::ExpectedDeliveryDate),
+            ("status", shipments::Column::Status),
+            ("total_cost", shipments::Column::TotalCost),
+        ];
+        query = match page.sort_by(&sortable)? {
+            Some((column, order)) => query.order_by(column, order),
+            None => query.order_by_desc(shipments::Column::ShipmentDate),
+        };
+        page.fetch(db, query.order_by_asc(shipments::Column::ShipmentId)).await
+    }
+
+    /// Loads the supplier of each shipment in a single query.
+    pub async fn load_suppliers(
+        db: &DatabaseConnection,
+        shipments: &[shipments::Model],
+    ) -> Result<Vec<Option<suppliers::Model>>, AppError> {
+        Ok(shipments.load_one(Suppliers, db).await?)
     }
 
     pub async fn find_by_id(db: &DatabaseConnection, id: i32) -> Result<shipments::Model, AppError> {
@@ -28,6 +66,14 @@ impl ShipmentService {
     }
 
     pub async fn create(db: &DatabaseConnection, dto: ShipmentCreate) -> Result<shipments::Model, AppError> {
+        let status = dto.status.unwrap_or_default();
+        if matches!(status, ShipmentStatus::PartiallyReceived | ShipmentStatus::Cancelled) {
+            return Err(AppError::validation(format!(
+                "New shipments must be in transit or delivered, not {}",
+                status.as_str()
+            )));
+        }
+        
         let txn = db.begin().await?;
         
         // Create the shipment
@@ -35,55 +81,71 @@ impl ShipmentService {
             supplier_id: Set(dto.supplier_id),
             shipment_date: Set(dto.shipment_date),
             expected_delivery_date: Set(dto.expected_delivery_date),
-            status: Set(dto.status.unwrap_or_else(|| "in_transit".to_string())),
+            status: Set(ShipmentStatus::InTransit),
             total_cost: Set(dto.total_cost),
             ..Default::default()
         };
         
-        let shipment = shipment.insert(&txn).await?;
+        let mut shipment = shipment.insert(&txn).await?;
         
         // Create shipment items
+        let mut items = Vec::with_capacity(dto.items.len());
         for item in dto.items {
             let shipment_item = shipments_items::ActiveModel {
                 shipment_id: Set(shipment.shipment_id),
                 product_id: Set(item.product_id),
                 quantity: Set(item.quantity),
                 unit_cost: Set(item.unit_cost),
-                ..Default::default()
+                received_quantity: Set(0),
             };
-            shipment_item.insert(&txn).await?;
-            
-            // Update product stock
-            let product = ProductService::find_by_id(&db, item.product_id).await?;
-            let new_stock = product.stock_quantity + item.quantity;
-            
-            let mut product: products::ActiveModel = product.into();
-            product.stock_quantity = Set(new_stock);
-            product.update(&txn).await?;
+            items.push(shipment_item.insert(&txn).await?);
+        }
+        
+        OutboxService::publish(&txn, EventType::ShipmentCreated, "shipment", shipment.shipment_id, json!({
+            "shipment_id": shipment.shipment_id,
+            "supplier_id": shipment.supplier_id,
+            "shipment_date": shipment.shipment_date,
+            "expected_delivery_date": shipment.expected_delivery_date,
+            "status": shipment.status,
+            "total_cost": shipment.total_cost,
+            "items": items.iter().map(|item| json!({
+                "product_id": item.product_id,
+                "quantity": item.quantity,
+                "unit_cost": item.unit_cost,
+            })).collect::<Vec<_>>(),
+        })).await?;
+        
+        // Stock only arrives with the goods
+        if status == ShipmentStatus::Delivered {
+            shipment = Self::change_status(&txn, shipment, ShipmentStatus::Delivered).await?;
         }
+        ChangeService::notify(&txn, EntityKind::Shipment, shipment.shipment_id, ChangeAction::Created).await?;
+        AuditService::record(&txn, AuditEntity::Shipment, shipment.shipment_id, None, Some(&shipment)).await?;
         
         txn.commit().await?;
         Ok(shipment)
     }
 
-    pub async fn update(db: &DatabaseConnection, id: i32, dto: ShipmentUpdate) -> Result<shipments::Model, AppError> {
-        let shipment = Self::find_by_id(db, id).await?;
-        
-        let mut shipment: shipments::ActiveModel = shipment.into();
+    pub async fn update(db: &DatabaseConnection, id: i32, if_match: &IfMatch, dto: ShipmentUpdate) -> Result<shipments::Model, AppError> {
+        let txn = db.begin().await?;
         
-        if let Some(status) = dto.status {
-            shipment.status = Set(status);
-        }
-        if let Some(expected_delivery_date) = dto.expected_delivery_date {
-            shipment.expected_delivery_date = Set(expected_delivery_date);
-        }
-        if let Some(total_cost) = dto.total_cost {
-            shipment.total_cost = Set(total_cost);
-        }
+        let shipment = Shipments::find_by_id(id)
+            .lock(LockType::NoKeyUpdate)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if_match.check(shipment.version)?;
+        dto.validate_against(shipment.shipment_date)?;
+        let before = shipment.clone();
         
         // Handle items update if provided
         if let Some(items) = dto.items {
-            let txn = db.begin().await?;
+            if shipment.status != ShipmentStatus::InTransit {
+                return Err(AppError::Conflict(format!(
+                    "Items of a {} shipment cannot be changed",
+                    shipment.status.as_str()
+                )));
+            }
             
             // Delete existing items
             ShipmentItems::delete_many()
@@ -98,27 +160,243 @@ impl ShipmentService {
                     product_id: Set(item.product_id),
                     quantity: Set(item.quantity),
                     unit_cost: Set(item.unit_cost),
-                    ..Default::default()
+                    received_quantity: Set(0),
                 };
                 shipment_item.insert(&txn).await?;
             }
+        }
+        
+        let mut shipment: shipments::ActiveModel = shipment.into();
+        
+        if let Some(expected_delivery_date) = dto.expected_delivery_date {
+            shipment.expected_delivery_date = Set(expected_delivery_date);
+        }
+        if let Some(total_cost) = dto.total_cost {
+            shipment.total_cost = Set(total_cost);
+        }
+        
+        let mut shipment = shipment.update(&txn).await?;
+        
+        if let Some(status) = dto.status
+            && status != shipment.status
+        {
+            shipment = Self::change_status(&txn, shipment, status).await?;
+        }
+        ChangeService::notify(&txn, EntityKind::Shipment, id, ChangeAction::Updated).await?;
+        AuditService::record(&txn, AuditEntity::Shipment, id, Some(&before), Some(&shipment)).await?;
+        
+        txn.commit().await?;
+        Ok(shipment)
+    }
+
+    /// Receives the given quantities, or everything still outstanding when `dto.items` is
+    /// empty and the shipment is not being closed.
+    pub async fn receive(db: &DatabaseConnection, id: i32, dto: ShipmentReceive) -> Result<shipments::Model, AppError> {
+        let txn = db.begin().await?;
+        
+        let shipment = Shipments::find_by_id(id)
+            .lock(LockType::NoKeyUpdate)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        let before = shipment.clone();
+        let shipment = Self::receive_items(&txn, shipment, dto.items, dto.close).await?;
+        ChangeService::notify(&txn, EntityKind::Shipment, id, ChangeAction::Updated).await?;
+        AuditService::record(&txn, AuditEntity::Shipment, id, Some(&before), Some(&shipment)).await?;
+        
+        txn.commit().await?;
+        Ok(shipment)
+    }
+
+    /// Adds the received `lines` to stock. More than was ordered is accepted, as the goods
+    /// are on the shelf either way, and shows up as a discrepancy. With `close` the shipment
+    /// is delivered however much is still missing; otherwise only once every line is complete.
+    async fn receive_items<C: ConnectionTrait>(
+        conn: &C,
+        shipment: shipments::Model,
+        lines: Vec<ShipmentItemReceive>,
+        close: bool,
+    ) -> Result<shipments::Model, AppError> {
+        if !matches!(shipment.status, ShipmentStatus::InTransit | ShipmentStatus::PartiallyReceived) {
+            return Err(AppError::Conflict(format!(
+                "Shipment {} is {} and cannot be received",
+                shipment.shipment_id,
+                shipment.status.as_str()
+            )));
+        }
+        
+        let mut items = shipment.find_related(ShipmentItems).all(conn).await?;
+        let mut lines = if lines.is_empty() && !close {
+            items
+                .iter()
+                .filter(|item| item.received_quantity < item.quantity)
+                .map(|item| ShipmentItemReceive {
+                    product_id: item.product_id,
+                    quantity: item.quantity - item.received_quantity,
+                })
+                .collect()
+        } else {
+            lines
+        };
+        // Products are locked in id order, like everywhere stock changes, so that concurrent
+        // receipts and orders cannot deadlock
+        lines.sort_by_key(|line| line.product_id);
+        
+        let mut received = Vec::with_capacity(lines.len());
+        for line in lines {
+            let item = items
+                .iter_mut()
+                .find(|item| item.product_id == line.product_id)
+                .ok_or_else(|| AppError::validation(format!(
+                    "Product {} is not part of shipment {}",
+                    line.product_id, shipment.shipment_id
+                )))?;
+            
+            let mut active: shipments_items::ActiveModel = item.clone().into();
+            active.received_quantity = Set(item.received_quantity + line.quantity);
+            *item = active.update(conn).await?;
             
-            txn.commit().await?;
+            InventoryService::record(
+                conn,
+                line.product_id,
+                line.quantity,
+                MovementReason::Receipt,
+                MovementSource::Shipment(shipment.shipment_id),
+            ).await?;
+            received.push(json!({ "product_id": line.product_id, "quantity": line.quantity }));
         }
         
-        Ok(shipment.update(db).await?)
+        let status = if close || items.iter().all(|item| item.received_quantity >= item.quantity) {
+            ShipmentStatus::Delivered
+        } else {
+            ShipmentStatus::PartiallyReceived
+        };
+        
+        let previous_status = shipment.status;
+        let mut shipment: shipments::ActiveModel = shipment.into();
+        shipment.status = Set(status);
+        let shipment = shipment.update(conn).await?;
+        
+        OutboxService::publish(conn, EventType::ShipmentReceived, "shipment", shipment.shipment_id, json!({
+            "shipment_id": shipment.shipment_id,
+            "supplier_id": shipment.supplier_id,
+            "status": shipment.status,
+            "items": received,
+        })).await?;
+        Self::notify_status_change(conn, &shipment, previous_status).await?;
+        Ok(shipment)
     }
 
-    pub async fn delete(db: &DatabaseConnection, id: i32) -> Result<(), AppError> {
-        let shipment = Self::find_by_id(db, id).await?;
+    /// Moves a shipment to `status`, receiving whatever is outstanding on delivery
+    /// and taking received goods back out of stock if the shipment is cancelled.
+    async fn change_status<C: ConnectionTrait>(
+        conn: &C,
+        shipment: shipments::Model,
+        status: ShipmentStatus,
+    ) -> Result<shipments::Model, AppError> {
+        if !shipment.status.can_transition_to(status) {
+            return Err(AppError::Conflict(format!(
+                "Shipment {} cannot move from {} to {}",
+                shipment.shipment_id,
+                shipment.status.as_str(),
+                status.as_str()
+            )));
+        }
+        if status == ShipmentStatus::Delivered {
+            return Self::receive_items(conn, shipment, Vec::new(), false).await;
+        }
+        
+        let items = shipment
+            .find_related(ShipmentItems)
+            .order_by_asc(shipments_items::Column::ProductId)
+            .all(conn)
+            .await?;
+        for item in items.into_iter().filter(|item| item.received_quantity > 0) {
+            InventoryService::record(
+                conn,
+                item.product_id,
+                -item.received_quantity,
+                MovementReason::Cancel,
+                MovementSource::Shipment(shipment.shipment_id),
+            ).await?;
+            
+            let mut item: shipments_items::ActiveModel = item.into();
+            item.received_quantity = Set(0);
+            item.update(conn).await?;
+        }
+        
+        let previous_status = shipment.status;
+        let mut shipment: shipments::ActiveModel = shipment.into();
+        shipment.status = Set(status);
+        let shipment = shipment.update(conn).await?;
+        Self::notify_status_change(conn, &shipment, previous_status).await?;
+        Ok(shipment)
+    }
+
+    async fn notify_status_change<C: ConnectionTrait>(
+        conn: &C,
+        shipment: &shipments::Model,
+        previous_status: ShipmentStatus,
+    ) -> Result<(), AppError> {
+        if shipment.status == previous_status {
+            return Ok(());
+        }
+        OutboxService::publish(conn, EventType::ShipmentStatusChanged, "shipment", shipment.shipment_id, json!({
+            "shipment_id": shipment.shipment_id,
+            "supplier_id": shipment.supplier_id,
+            "previous_status": previous_status,
+            "status": shipment.status,
+        })).await
+    }
+
+    /// Lines of the supplier's partially received or delivered shipments where the
+    /// received quantity differs from the ordered one.
+    pub async fn find_discrepancies(db: &DatabaseConnection, supplier_id: i32) -> Result<Vec<(shipments::Model, shipments_items::Model)>, AppError> {
+        let rows = ShipmentItems::find()
+            .find_also_related(Shipments)
+            .filter(shipments::Column::SupplierId.eq(supplier_id))
+            .filter(shipments::Column::Status.is_in([ShipmentStatus::PartiallyReceived, ShipmentStatus::Delivered]))
+            .filter(
+                Expr::col((ShipmentItems, shipments_items::Column::ReceivedQuantity))
+                    .ne(Expr::col((ShipmentItems, shipments_items::Column::Quantity))),
+            )
+            .order_by_asc(shipments::Column::ShipmentDate)
+            .order_by_asc(shipments_items::Column::ProductId)
+            .all(db)
+            .await?;
+        
+        Ok(rows
+            .into_iter()
+            .filter_map(|(item, shipment)| shipment.map(|shipment| (shipment, item)))
+            .collect())
+    }
+
+    pub async fn delete(db: &DatabaseConnection, id: i32, if_match: &IfMatch) -> Result<(), AppError> {
+        let txn = db.begin().await?;
+        
+        let shipment = Shipments::find_by_id(id)
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if_match.check(shipment.version)?;
+        AuditService::record(&txn, AuditEntity::Shipment, id, Some(&shipment), None).await?;
+        
         let shipment: shipments::ActiveModel = shipment.into();
-        shipment.delete(db).await?;
+        shipment.delete(&txn).await?;
+        ChangeService::notify(&txn, EntityKind::Shipment, id, ChangeAction::Deleted).await?;
+        
+        txn.commit().await?;
         Ok(())
     }
 
-    pub async fn find_with_details(db: &DatabaseConnection, id: i32) -> Result<(shipments::Model, Vec<shipments_items::Model>), AppError> {
+    pub async fn find_with_details(
+        db: &DatabaseConnection,
+        id: i32,
+    ) -> Result<(shipments::Model, Vec<(shipments_items::Model, Option<products::Model>)>), AppError> {
         let shipment = Self::find_by_id(db, id).await?;
         let items = shipment.find_related(ShipmentItems).all(db).await?;
-        Ok((shipment, items))
+        let products = items.load_one(Products, db).await?;
+        Ok((shipment, items.into_iter().zip(products).collect()))
     }
 }
@@ -1,32 +1,44 @@
 use anyhow::Result;
+use sea_orm::sea_query::LockType;
 use sea_orm::ActiveValue::Set;
 use sea_orm::{
-    ActiveModelTrait, DatabaseConnection, EntityTrait, ModelTrait, QueryOrder 
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter, QuerySelect,
+    TransactionTrait, ModelTrait, QueryOrder
 };
-use crate::db::{Products, Suppliers, products, suppliers};
+use chrono::Utc;
+use crate::db::{Products, Shipments, Suppliers, products, shipments, suppliers};
+use crate::db::audit_log::AuditEntity;
 use crate::dtos::*;
-use crate::error::AppError;
+use crate::concurrency::IfMatch;
+use crate::error::{AppError, FieldError};
+use crate::pagination::PageParams;
+use crate::services::{AuditService, ChangeService};
 
 
 pub struct SupplierService;
 
 impl SupplierService {
-    pub async fn find_all(db: &DatabaseConnection) -> Result<Vec<suppliers::Model>, AppError> {
-        let suppliers = Suppliers::find()
-            .order_by_asc(suppliers::Column::CompanyName)
-            .all(db)
-            .await?;
-        Ok(suppliers)
+    pub async fn find_all(db: &DatabaseConnection, page: &PageParams, include_deleted: bool) -> Result<(Vec<suppliers::Model>, u64), AppError> {
+        let mut query = Suppliers::find();
+        if !include_deleted {
+            query = query.filter(suppliers::Column::DeletedAt.is_null());
+        }
+        let query = match page.sort_by(&[("company_name", suppliers::Column::CompanyName)])? {
+            Some((column, order)) => query.order_by(column, order),
+            None => query.order_by_asc(suppliers::Column::CompanyName),
+        };
+        page.fetch(db, query.order_by_asc(suppliers::Column::SupplierId)).await
     }
 
-    pub async fn find_by_id(db: &DatabaseConnection, id: i32) -> Result<suppliers::Model, AppError> {
+    pub async fn find_by_id(db: &DatabaseConnection, id: i32, include_deleted: bool) -> Result<suppliers::Model, AppError> {
         Suppliers::find_by_id(id)
             .one(db)
             .await?
+            .filter(|supplier| include_deleted || supplier.deleted_at.is_none())
             .ok_or(AppError::NotFound)
     }
 
-    pub async fn create(db: &DatabaseConnection, dto: SupplierCreate) -> Result<suppliers::Model, AppError> {
+    pub async fn create<C: ConnectionTrait + TransactionTrait>(db: &C, dto: SupplierCreate) -> Result<suppliers::Model, AppError> {
         let supplier = suppliers::ActiveModel {
             company_name: Set(dto.company_name),
             contact_name: Set(dto.contact_name),
@@ -36,11 +48,25 @@ impl SupplierService {
             ..Default::default()
         };
         
-        Ok(supplier.insert(db).await?)
+        let txn = db.begin().await?;
+        let supplier = supplier.insert(&txn).await?;
+        ChangeService::notify(&txn, EntityKind::Supplier, supplier.supplier_id, ChangeAction::Created).await?;
+        AuditService::record(&txn, AuditEntity::Supplier, supplier.supplier_id, None, Some(&supplier)).await?;
+        txn.commit().await?;
+        Ok(supplier)
     }
 
-    pub async fn update(db: &DatabaseConnection, id: i32, dto: SupplierUpdate) -> Result<suppliers::Model, AppError> {
-        let supplier = Self::find_by_id(db, id).await?;
+    pub async fn update<C: ConnectionTrait + TransactionTrait>(db: &C, id: i32, if_match: &IfMatch, dto: SupplierUpdate) -> Result<suppliers::Model, AppError> {
+        let txn = db.begin().await?;
+        
+        let supplier = Suppliers::find_by_id(id)
+            .filter(suppliers::Column::DeletedAt.is_null())
+            .lock(LockType::NoKeyUpdate)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if_match.check(supplier.version)?;
+        let before = supplier.clone();
         
         let mut supplier: suppliers::ActiveModel = supplier.into();
         
@@ -60,19 +86,104 @@ impl SupplierService {
             supplier.address = Set(Some(address));
         }
         
-        Ok(supplier.update(db).await?)
+        let supplier = supplier.update(&txn).await?;
+        ChangeService::notify(&txn, EntityKind::Supplier, id, ChangeAction::Updated).await?;
+        AuditService::record(&txn, AuditEntity::Supplier, id, Some(&before), Some(&supplier)).await?;
+        
+        txn.commit().await?;
+        Ok(supplier)
     }
 
-    pub async fn delete(db: &DatabaseConnection, id: i32) -> Result<(), AppError> {
-        let supplier = Self::find_by_id(db, id).await?;
-        let supplier: suppliers::ActiveModel = supplier.into();
-        supplier.delete(db).await?;
+    /// Soft-deletes the supplier; it stays in the table until restored. A supplier with
+    /// shipments or products is only deleted when `archive` is set, and they are kept.
+    pub async fn delete(db: &DatabaseConnection, id: i32, if_match: &IfMatch, archive: bool) -> Result<(), AppError> {
+        let txn = db.begin().await?;
+        
+        // FOR UPDATE also holds off new shipments and products, whose foreign key checks
+        // need a key share lock
+        let supplier = Suppliers::find_by_id(id)
+            .filter(suppliers::Column::DeletedAt.is_null())
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if_match.check(supplier.version)?;
+        
+        if !archive {
+            let shipments: Vec<i32> = Shipments::find()
+                .select_only()
+                .column(shipments::Column::ShipmentId)
+                .filter(shipments::Column::SupplierId.eq(id))
+                .order_by_asc(shipments::Column::ShipmentId)
+                .into_tuple()
+                .all(&txn)
+                .await?;
+            let products: Vec<i32> = Products::find()
+                .select_only()
+                .column(products::Column::ProductId)
+                .filter(products::Column::SupplierId.eq(id))
+                .filter(products::Column::DeletedAt.is_null())
+                .order_by_asc(products::Column::ProductId)
+                .into_tuple()
+                .all(&txn)
+                .await?;
+            
+            let mut dependents = Vec::new();
+            if !shipments.is_empty() {
+                dependents.push(FieldError::dependents("shipments", &shipments));
+            }
+            if !products.is_empty() {
+                dependents.push(FieldError::dependents("products", &products));
+            }
+            if !dependents.is_empty() {
+                return Err(AppError::StillReferenced {
+                    message: format!("Supplier {} still has shipments or products, archive it to keep them", id),
+                    dependents,
+                });
+            }
+        }
+        AuditService::record(&txn, AuditEntity::Supplier, id, Some(&supplier), None).await?;
+        
+        let mut supplier: suppliers::ActiveModel = supplier.into();
+        supplier.deleted_at = Set(Some(Utc::now()));
+        supplier.update(&txn).await?;
+        ChangeService::notify(&txn, EntityKind::Supplier, id, ChangeAction::Deleted).await?;
+        
+        txn.commit().await?;
         Ok(())
     }
 
+    pub async fn restore(db: &DatabaseConnection, id: i32, if_match: &IfMatch) -> Result<suppliers::Model, AppError> {
+        let txn = db.begin().await?;
+        
+        let supplier = Suppliers::find_by_id(id)
+            .lock(LockType::NoKeyUpdate)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if_match.check(supplier.version)?;
+        if supplier.deleted_at.is_none() {
+            return Err(AppError::Conflict(format!("Supplier {} is not deleted", id)));
+        }
+        let before = supplier.clone();
+        
+        let mut supplier: suppliers::ActiveModel = supplier.into();
+        supplier.deleted_at = Set(None);
+        let supplier = supplier.update(&txn).await?;
+        ChangeService::notify(&txn, EntityKind::Supplier, id, ChangeAction::Created).await?;
+        AuditService::record(&txn, AuditEntity::Supplier, id, Some(&before), Some(&supplier)).await?;
+        
+        txn.commit().await?;
+        Ok(supplier)
+    }
+
     pub async fn find_with_products(db: &DatabaseConnection, id: i32) -> Result<(suppliers::Model, Vec<products::Model>), AppError> {
-        let supplier = Self::find_by_id(db, id).await?;
-        let products = supplier.find_related(Products).all(db).await?;
+        let supplier = Self::find_by_id(db, id, false).await?;
+        let products = supplier
+            .find_related(Products)
+            .filter(products::Column::DeletedAt.is_null())
+            .all(db)
+            .await?;
         Ok((supplier, products))
     }
 }
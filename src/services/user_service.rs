@@ -0,0 +1,96 @@
+use anyhow::Result;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, Set, TransactionTrait
+};
+use chrono::Utc;
+use crate::db::{Users, users};
+use crate::db::audit_log::AuditEntity;
+use crate::dtos::*;
+use crate::error::AppError;
+use crate::pagination::PageParams;
+use crate::services::{AuditService, AuthService};
+
+pub struct UserService;
+
+impl UserService {
+    pub async fn find_all(db: &DatabaseConnection, page: &PageParams) -> Result<(Vec<users::Model>, u64), AppError> {
+        let sortable = [
+            ("username", users::Column::Username),
+            ("role", users::Column::Role),
+            ("created_at", users::Column::CreatedAt),
+        ];
+        let query = match page.sort_by(&sortable)? {
+            Some((column, order)) => Users::find().order_by(column, order),
+            None => Users::find().order_by_asc(users::Column::Username),
+        };
+        page.fetch(db, query.order_by_asc(users::Column::UserId)).await
+    }
+
+    pub async fn find_by_id(db: &DatabaseConnection, id: i32) -> Result<users::Model, AppError> {
+        Users::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(AppError::NotFound)
+    }
+
+    pub async fn create(db: &DatabaseConnection, dto: UserCreate) -> Result<users::Model, AppError> {
+        let taken = Users::find()
+            .filter(users::Column::Username.eq(&dto.username))
+            .count(db)
+            .await?;
+        if taken > 0 {
+            return Err(AppError::Conflict(format!("Username {} is already taken", dto.username)));
+        }
+        
+        let user = users::ActiveModel {
+            username: Set(dto.username),
+            password_hash: Set(AuthService::hash_password(&dto.password)?),
+            role: Set(dto.role),
+            created_at: Set(Utc::now()),
+            ..Default::default()
+        };
+        
+        let txn = db.begin().await?;
+        let user = user.insert(&txn).await?;
+        AuditService::record(&txn, AuditEntity::User, user.user_id, None, Some(&user)).await?;
+        txn.commit().await?;
+        Ok(user)
+    }
+
+    pub async fn update(db: &DatabaseConnection, id: i32, dto: UserUpdate) -> Result<users::Model, AppError> {
+        let txn = db.begin().await?;
+        let before = Users::find_by_id(id)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        
+        let mut user: users::ActiveModel = before.clone().into();
+        
+        if let Some(password) = dto.password {
+            user.password_hash = Set(AuthService::hash_password(&password)?);
+        }
+        if let Some(role) = dto.role {
+            user.role = Set(role);
+        }
+        
+        let user = user.update(&txn).await?;
+        AuditService::record(&txn, AuditEntity::User, id, Some(&before), Some(&user)).await?;
+        txn.commit().await?;
+        Ok(user)
+    }
+
+    pub async fn delete(db: &DatabaseConnection, id: i32) -> Result<(), AppError> {
+        let txn = db.begin().await?;
+        let user = Users::find_by_id(id)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        AuditService::record(&txn, AuditEntity::User, id, Some(&user), None).await?;
+        
+        let user: users::ActiveModel = user.into();
+        user.delete(&txn).await?;
+        txn.commit().await?;
+        Ok(())
+    }
+}
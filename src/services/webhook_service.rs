@@ -0,0 +1,330 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use hmac::{Hmac, Mac};
+use sea_orm::sea_query::{LockBehavior, LockType};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, LoaderTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set, TransactionTrait
+};
+use chrono::Utc;
+use serde_json::json;
+use sha2::Sha256;
+use crate::config::WebhookConfig;
+use crate::db::{WebhookDeliveries, Webhooks, outbox, webhook_deliveries, webhooks};
+use crate::db::webhook_deliveries::DeliveryStatus;
+use crate::db::audit_log::AuditEntity;
+use crate::dtos::*;
+use crate::error::AppError;
+use crate::pagination::PageParams;
+use crate::services::AuditService;
+
+/// How many due deliveries one dispatcher pass sends.
+const DISPATCH_BATCH: u64 = 50;
+
+pub struct WebhookService;
+
+impl WebhookService {
+    pub async fn find_all(db: &DatabaseConnection, page: &PageParams) -> Result<(Vec<webhooks::Model>, u64), AppError> {
+        let sortable = [
+            ("event_type", webhooks::Column::EventType),
+            ("url", webhooks::Column::Url),
+            ("created_at", webhooks::Column::CreatedAt),
+        ];
+        let query = match page.sort_by(&sortable)? {
+            Some((column, order)) => Webhooks::find().order_by(column, order),
+            None => Webhooks::find().order_by_asc(webhooks::Column::CreatedAt),
+        };
+        page.fetch(db, query.order_by_asc(webhooks::Column::WebhookId)).await
+    }
+
+    pub async fn find_by_id(db: &DatabaseConnection, id: i32) -> Result<webhooks::Model, AppError> {
+        Webhooks::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(AppError::NotFound)
+    }
+
+    pub async fn create(db: &DatabaseConnection, dto: WebhookCreate) -> Result<webhooks::Model, AppError> {
+        let mut secret = [0u8; 32];
+        OsRng.fill_bytes(&mut secret);
+        
+        let webhook = webhooks::ActiveModel {
+            url: Set(dto.url),
+            event_type: Set(dto.event_type),
+            secret: Set(hex::encode(secret)),
+            active: Set(dto.active.unwrap_or(true)),
+            created_at: Set(Utc::now()),
+            ..Default::default()
+        };
+        
+        let txn = db.begin().await?;
+        let webhook = webhook.insert(&txn).await?;
+        AuditService::record(&txn, AuditEntity::Webhook, webhook.webhook_id, None, Some(&webhook)).await?;
+        txn.commit().await?;
+        Ok(webhook)
+    }
+
+    pub async fn update(db: &DatabaseConnection, id: i32, dto: WebhookUpdate) -> Result<webhooks::Model, AppError> {
+        let txn = db.begin().await?;
+        let before = Webhooks::find_by_id(id)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        
+        let mut webhook: webhooks::ActiveModel = before.clone().into();
+        
+        if let Some(url) = dto.url {
+            webhook.url = Set(url);
+        }
+        if let Some(event_type) = dto.event_type {
+            webhook.event_type = Set(event_type);
+        }
+        if let Some(active) = dto.active {
+            webhook.active = Set(active);
+        }
+        
+        let webhook = webhook.update(&txn).await?;
+        AuditService::record(&txn, AuditEntity::Webhook, id, Some(&before), Some(&webhook)).await?;
+        txn.commit().await?;
+        Ok(webhook)
+    }
+
+    pub async fn delete(db: &DatabaseConnection, id: i32) -> Result<(), AppError> {
+        let txn = db.begin().await?;
+        let webhook = Webhooks::find_by_id(id)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        AuditService::record(&txn, AuditEntity::Webhook, id, Some(&webhook), None).await?;
+        
+        let webhook: webhooks::ActiveModel = webhook.into();
+        webhook.delete(&txn).await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// The delivery log of one webhook, newest first.
+    pub async fn find_deliveries(
+        db: &DatabaseConnection,
+        webhook_id: i32,
+        filter: &DeliveryFilter,
+        page: &PageParams,
+    ) -> Result<(Vec<webhook_deliveries::Model>, u64), AppError> {
+        Self::find_by_id(db, webhook_id).await?;
+        
+        let mut query = WebhookDeliveries::find()
+            .filter(webhook_deliveries::Column::WebhookId.eq(webhook_id));
+        if let Some(status) = filter.status {
+            query = query.filter(webhook_deliveries::Column::Status.eq(status));
+        }
+        
+        let sortable = [
+            ("created_at", webhook_deliveries::Column::CreatedAt),
+            ("next_attempt_at", webhook_deliveries::Column::NextAttemptAt),
+            ("attempts", webhook_deliveries::Column::Attempts),
+        ];
+        query = match page.sort_by(&sortable)? {
+            Some((column, order)) => query.order_by(column, order),
+            None => query.order_by_desc(webhook_deliveries::Column::CreatedAt),
+        };
+        page.fetch(db, query.order_by_desc(webhook_deliveries::Column::DeliveryId)).await
+    }
+
+    /// Queues a delivery that succeeded or gave up to be sent again right away.
+    pub async fn redeliver(db: &DatabaseConnection, delivery_id: i32) -> Result<webhook_deliveries::Model, AppError> {
+        let delivery = WebhookDeliveries::find_by_id(delivery_id)
+            .one(db)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if delivery.status == DeliveryStatus::Pending {
+            return Err(AppError::Conflict(format!(
+                "Delivery {} is already waiting to be sent",
+                delivery_id
+            )));
+        }
+        
+        let mut delivery: webhook_deliveries::ActiveModel = delivery.into();
+        delivery.status = Set(DeliveryStatus::Pending);
+        delivery.next_attempt_at = Set(Utc::now());
+        Ok(delivery.update(db).await?)
+    }
+
+    /// Queues `event` for every active webhook subscribed to its type. Called by the outbox
+    /// relay on its transaction, so an event is queued exactly when it is marked published.
+    pub async fn enqueue<C: ConnectionTrait>(conn: &C, event: &outbox::Model) -> Result<(), AppError> {
+        let subscribers = Webhooks::find()
+            .filter(webhooks::Column::EventType.eq(event.event_type))
+            .filter(webhooks::Column::Active.eq(true))
+            .all(conn)
+            .await?;
+        if subscribers.is_empty() {
+            return Ok(());
+        }
+        
+        // `id` lets receivers drop an event they have already seen
+        let data: serde_json::Value = serde_json::from_str(&event.payload).unwrap_or_default();
+        let payload = json!({
+            "id": event.outbox_id,
+            "event": event.event_type.as_str(),
+            "occurred_at": event.created_at,
+            "data": data,
+        })
+        .to_string();
+        let now = Utc::now();
+        let deliveries = subscribers.into_iter().map(|webhook| webhook_deliveries::ActiveModel {
+            webhook_id: Set(webhook.webhook_id),
+            event_type: Set(event.event_type),
+            payload: Set(payload.clone()),
+            status: Set(DeliveryStatus::Pending),
+            attempts: Set(0),
+            next_attempt_at: Set(now),
+            created_at: Set(now),
+            ..Default::default()
+        });
+        WebhookDeliveries::insert_many(deliveries).exec(conn).await?;
+        Ok(())
+    }
+
+    /// Sends the deliveries that are due. Each is leased for the length of an attempt first,
+    /// so several dispatchers never send the same delivery at once.
+    pub async fn dispatch_due(
+        db: &DatabaseConnection,
+        client: &reqwest::Client,
+        config: &WebhookConfig,
+    ) -> Result<usize, AppError> {
+        let now = Utc::now();
+        let lease = chrono::Duration::seconds(config.timeout_secs as i64 * 2);
+        
+        let txn = db.begin().await?;
+        let due = WebhookDeliveries::find()
+            .filter(webhook_deliveries::Column::Status.eq(DeliveryStatus::Pending))
+            .filter(webhook_deliveries::Column::NextAttemptAt.lte(now))
+            .order_by_asc(webhook_deliveries::Column::NextAttemptAt)
+            .order_by_asc(webhook_deliveries::Column::DeliveryId)
+            .limit(DISPATCH_BATCH)
+            .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+            .all(&txn)
+            .await?;
+        let hooks = due.load_one(Webhooks, &txn).await?;
+        for delivery in &due {
+            let mut leased: webhook_deliveries::ActiveModel = delivery.clone().into();
+            leased.next_attempt_at = Set(now + lease);
+            leased.update(&txn).await?;
+        }
+        txn.commit().await?;
+        
+        let sent = due.len();
+        for (delivery, webhook) in due.into_iter().zip(hooks) {
+            let Some(webhook) = webhook else { continue };
+            let result = Self::send(client, &webhook, &delivery).await;
+            Self::record_attempt(db, delivery, result, config).await?;
+        }
+        Ok(sent)
+    }
+
+    /// Posts one delivery, returning the response status or why there was none.
+    async fn send(
+        client: &reqwest::Client,
+        webhook: &webhooks::Model,
+        delivery: &webhook_deliveries::Model,
+    ) -> Result<u16, String> {
+        let timestamp = Utc::now().timestamp();
+        let signature = Self::sign(&webhook.secret, timestamp, &delivery.payload);
+        
+        let response = client
+            .post(&webhook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Webhook-Id", webhook.webhook_id.to_string())
+            .header("X-Webhook-Delivery", delivery.delivery_id.to_string())
+            .header("X-Webhook-Event", delivery.event_type.as_str())
+            .header("X-Webhook-Signature", format!("t={},v1={}", timestamp, signature))
+            .body(delivery.payload.clone())
+            .send()
+            .await
+            .map_err(|err| err.to_string())?;
+        Ok(response.status().as_u16())
+    }
+
+    /// Hex HMAC-SHA256 of `"{timestamp}.{payload}"` keyed with the webhook's secret.
+    /// Receivers recompute it to check that a delivery is authentic and recent.
+    pub fn sign(secret: &str, timestamp: i64, payload: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(timestamp.to_string().as_bytes());
+        mac.update(b".");
+        mac.update(payload.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    /// Marks a 2xx as delivered; anything else is retried with exponential backoff
+    /// until `max_attempts` is reached.
+    async fn record_attempt(
+        db: &DatabaseConnection,
+        delivery: webhook_deliveries::Model,
+        result: Result<u16, String>,
+        config: &WebhookConfig,
+    ) -> Result<(), AppError> {
+        let now = Utc::now();
+        let attempts = delivery.attempts + 1;
+        let mut delivery: webhook_deliveries::ActiveModel = delivery.into();
+        delivery.attempts = Set(attempts);
+        
+        let succeeded = match result {
+            Ok(status) => {
+                delivery.last_status_code = Set(Some(i32::from(status)));
+                delivery.last_error = Set(None);
+                (200..300).contains(&status)
+            }
+            Err(error) => {
+                delivery.last_status_code = Set(None);
+                delivery.last_error = Set(Some(error));
+                false
+            }
+        };
+        
+        if succeeded {
+            delivery.status = Set(DeliveryStatus::Succeeded);
+            delivery.delivered_at = Set(Some(now));
+        } else if attempts >= config.max_attempts {
+            delivery.status = Set(DeliveryStatus::Failed);
+        } else {
+            let backoff = config.backoff_base_secs.saturating_mul(1 << (attempts - 1).min(20));
+            delivery.next_attempt_at = Set(now + chrono::Duration::seconds(backoff));
+        }
+        
+        delivery.update(db).await?;
+        Ok(())
+    }
+
+    /// Sends due deliveries every `poll_interval_secs`; meant to be spawned once at startup.
+    pub async fn run_dispatcher(db: DatabaseConnection, config: WebhookConfig) {
+        let client = match reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+        {
+            Ok(client) => client,
+            Err(err) => {
+                eprintln!("Webhook dispatcher could not start: {err}");
+                return;
+            }
+        };
+        
+        let mut ticker = actix_web::rt::time::interval(Duration::from_secs(config.poll_interval_secs));
+        loop {
+            ticker.tick().await;
+            // Keep going while full batches come back, so a backlog drains quickly
+            loop {
+                match Self::dispatch_due(&db, &client, &config).await {
+                    Ok(sent) if sent as u64 == DISPATCH_BATCH => continue,
+                    Ok(_) => break,
+                    Err(err) => {
+                        eprintln!("Webhook dispatch failed: {err}");
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,12 +1,23 @@
 use sea_orm::DatabaseConnection;
+use tokio::sync::broadcast;
+
+use crate::config::{AuthConfig, IdempotencyConfig};
+use crate::dtos::EntityChange;
+
+/// How many changes a slow `/events` subscriber may fall behind before it is told to resync.
+const CHANGE_BUFFER: usize = 256;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: DatabaseConnection,
+    pub auth: AuthConfig,
+    pub idempotency: IdempotencyConfig,
+    pub changes: broadcast::Sender<EntityChange>,
 }
 
 impl AppState {
-    pub fn new(db: DatabaseConnection) -> Self {
-        Self { db }
+    pub fn new(db: DatabaseConnection, auth: AuthConfig, idempotency: IdempotencyConfig) -> Self {
+        let (changes, _) = broadcast::channel(CHANGE_BUFFER);
+        Self { db, auth, idempotency, changes }
     }
 }
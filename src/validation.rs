@@ -0,0 +1,135 @@
+use std::{future::Future, pin::Pin};
+
+use actix_web::{FromRequest, HttpRequest, dev::Payload, web};
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use validator::{Validate, ValidationError, ValidationErrors, ValidationErrorsKind};
+
+use crate::error::{AppError, FieldError};
+
+/// A JSON body that has passed the DTO's `Validate` rules. Use in place of `web::Json`.
+pub struct ValidJson<T>(pub T);
+
+impl<T> ValidJson<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for ValidJson<T> {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let json = web::Json::<T>::from_request(req, payload);
+        Box::pin(async move {
+            let dto = json.await?.into_inner();
+            dto.validate().map_err(AppError::from)?;
+            Ok(ValidJson(dto))
+        })
+    }
+}
+
+impl From<ValidationErrors> for AppError {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut details = Vec::new();
+        collect(&errors, "", &mut details);
+        details.sort_by(|a, b| a.field.cmp(&b.field));
+        
+        AppError::Validation {
+            message: format!("{} field(s) failed validation", details.len()),
+            details,
+        }
+    }
+}
+
+/// Flattens nested errors into `items[0].quantity` style field paths.
+fn collect(errors: &ValidationErrors, prefix: &str, details: &mut Vec<FieldError>) {
+    for (field, kind) in errors.errors() {
+        let path = match field.as_ref() {
+            // Struct-level errors name their field through a `field` param.
+            "__all__" => prefix.to_string(),
+            field if prefix.is_empty() => field.to_string(),
+            field => format!("{}.{}", prefix, field),
+        };
+        match kind {
+            ValidationErrorsKind::Field(field_errors) => {
+                for error in field_errors {
+                    let field = match error.params.get("field").and_then(Value::as_str) {
+                        Some(field) if path.is_empty() => field.to_string(),
+                        Some(field) => format!("{}.{}", path, field),
+                        None => path.clone(),
+                    };
+                    details.push(FieldError { field, message: describe(error) });
+                }
+            }
+            ValidationErrorsKind::Struct(nested) => collect(nested, &path, details),
+            ValidationErrorsKind::List(items) => {
+                for (index, nested) in items {
+                    collect(nested, &format!("{}[{}]", path, index), details);
+                }
+            }
+        }
+    }
+}
+
+fn describe(error: &ValidationError) -> String {
+    if let Some(message) = &error.message {
+        return message.to_string();
+    }
+    let min = error.params.get("min");
+    let max = error.params.get("max");
+    
+    match (error.code.as_ref(), min, max) {
+        ("length", Some(min), Some(max)) => format!("must be between {} and {} characters", min, max),
+        ("length", Some(min), None) => format!("must be at least {} characters", min),
+        ("length", None, Some(max)) => format!("must be at most {} characters", max),
+        ("range", Some(min), Some(max)) => format!("must be between {} and {}", min, max),
+        ("range", Some(min), None) => format!("must be at least {}", min),
+        ("range", None, Some(max)) => format!("must be at most {}", max),
+        ("email", _, _) => "must be a valid email address".to_string(),
+        ("url", _, _) => "must be a valid URL".to_string(),
+        (code, _, _) => format!("is invalid ({})", code),
+    }
+}
+
+pub fn non_negative(value: &Decimal) -> Result<(), ValidationError> {
+    if value.is_sign_negative() && !value.is_zero() {
+        return Err(ValidationError::new("range").with_message("must not be negative".into()));
+    }
+    Ok(())
+}
+
+/// An ISO 3166-1 alpha-2 country code: two capital letters.
+pub fn country_code(value: &str) -> Result<(), ValidationError> {
+    if value.len() != 2 || !value.chars().all(|c| c.is_ascii_uppercase()) {
+        return Err(ValidationError::new("country").with_message("must be a two-letter ISO country code such as US".into()));
+    }
+    Ok(())
+}
+
+/// A line on an order or shipment, which may name each product only once.
+pub trait ProductLine {
+    fn product_id(&self) -> i32;
+}
+
+pub fn unique_products<T: ProductLine>(items: &[T]) -> Result<(), ValidationError> {
+    let mut seen = std::collections::HashSet::new();
+    match items.iter().find(|item| !seen.insert(item.product_id())) {
+        Some(item) => {
+            let mut error = ValidationError::new("unique")
+                .with_message(format!("lists product {} more than once", item.product_id()).into());
+            error.add_param("field".into(), &"items");
+            Err(error)
+        }
+        None => Ok(()),
+    }
+}
+
+/// Builds a struct-level error attributed to `field`.
+pub fn field_error(field: &'static str, code: &'static str, message: &'static str) -> ValidationError {
+    let mut error = ValidationError::new(code).with_message(message.into());
+    error.add_param("field".into(), &field);
+    error
+}
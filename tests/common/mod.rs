@@ -0,0 +1,107 @@
+//! Helpers shared by the integration tests. They need the PostgreSQL server from
+//! `configuration/`, on which each test creates and later drops its own database.
+
+// Each test crate compiles this module but uses only part of it
+#![allow(dead_code)]
+
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crud_shop_slop::config::{ServerConfig, read_config};
+use crud_shop_slop::db::{connect_database, connection_string, migrate};
+use crud_shop_slop::services::AuthService;
+use crud_shop_slop::start_server;
+use crud_shop_slop::state::AppState;
+use sea_orm::{ConnectionTrait, DatabaseConnection};
+use serde_json::{Value, json};
+
+/// A freshly migrated database holding only the mock data of the migrations.
+pub struct TestDatabase {
+    pub db: DatabaseConnection,
+    pub config: ServerConfig,
+    server: DatabaseConnection,
+    name: String,
+}
+
+impl TestDatabase {
+    /// Creates a database under a name no other test run uses and migrates it.
+    /// `configure` runs before the pool is handed out, so every clone shares its setup.
+    pub async fn create(configure: impl FnOnce(&mut DatabaseConnection)) -> Self {
+        static CREATED: AtomicUsize = AtomicUsize::new(0);
+        
+        let config = read_config().expect("Failed to read server config");
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().subsec_nanos();
+        let name = format!(
+            "slopshop_test_{}_{}_{}",
+            std::process::id(),
+            CREATED.fetch_add(1, Ordering::Relaxed),
+            nanos
+        );
+        
+        let url = |name: Option<&str>| {
+            connection_string(&config.db.user, &config.db.password, &config.db.host, config.db.port, name)
+        };
+        let server = connect_database(url(None)).await.expect("Failed to connect to database server");
+        server
+            .execute_unprepared(&format!("CREATE DATABASE {name}"))
+            .await
+            .expect("Failed to create test database");
+        
+        let mut db = connect_database(url(Some(&name))).await.expect("Failed to connect to test database");
+        migrate(&db).await.expect("Failed to run migrations on test database");
+        configure(&mut db);
+        
+        Self { db, config, server, name }
+    }
+    
+    /// Closes the pool and drops the database.
+    pub async fn drop(self) {
+        let _ = self.db.close().await;
+        self.server
+            .execute_unprepared(&format!("DROP DATABASE {} WITH (FORCE)", self.name))
+            .await
+            .expect("Failed to drop test database");
+    }
+}
+
+/// The API served from a test database on a free port, with an admin logged in.
+pub struct TestApp {
+    client: reqwest::Client,
+    base: String,
+    token: String,
+}
+
+impl TestApp {
+    pub async fn spawn(database: &TestDatabase) -> Self {
+        let (username, password) = ("admin".to_string(), "integration".to_string());
+        AuthService::ensure_admin(&database.db, Some((username.clone(), password.clone())))
+            .await
+            .expect("Failed to create admin user");
+        
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind test port");
+        let base = format!("http://{}/api", listener.local_addr().unwrap());
+        let config = &database.config;
+        let state = AppState::new(database.db.clone(), config.auth.clone(), config.idempotency.clone());
+        actix_web::rt::spawn(start_server(listener, state).expect("Failed to start server"));
+        
+        let client = reqwest::Client::new();
+        let login: Value = client
+            .post(format!("{base}/auth/login"))
+            .json(&json!({ "username": username, "password": password }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .expect("Failed to log in")
+            .json()
+            .await
+            .unwrap();
+        let token = login["token"].as_str().expect("login returned no token").to_string();
+        Self { client, base, token }
+    }
+    
+    /// A request to `path` under `/api`, made as the admin.
+    pub fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.client.request(method, format!("{}{path}", self.base)).bearer_auth(&self.token)
+    }
+}
@@ -0,0 +1,68 @@
+//! Edits must name the version they were based on in `If-Match`.
+
+mod common;
+
+use reqwest::{Method, StatusCode, header};
+use serde_json::{Value, json};
+
+use common::{TestApp, TestDatabase};
+
+#[actix_web::test]
+async fn edits_need_the_current_etag() {
+    let database = TestDatabase::create(|_| {}).await;
+    let app = TestApp::spawn(&database).await;
+    
+    let read = app.request(Method::GET, "/categories/1").send().await.unwrap();
+    assert_eq!(read.status(), StatusCode::OK);
+    let etag = read.headers()[header::ETAG].to_str().unwrap().to_string();
+    let rename = |name: &str| app.request(Method::PUT, "/categories/1").json(&json!({ "name": name }));
+    
+    let missing = rename("Without If-Match").send().await.unwrap();
+    assert_eq!(missing.status(), StatusCode::PRECONDITION_REQUIRED);
+    let error: Value = missing.json().await.unwrap();
+    assert_eq!(error["code"], "precondition_required");
+    
+    let saved = rename("First edit").header(header::IF_MATCH, &etag).send().await.unwrap();
+    assert_eq!(saved.status(), StatusCode::OK);
+    assert_ne!(saved.headers()[header::ETAG], etag.as_str());
+    
+    // A second edit based on the same read would overwrite the first
+    let stale = rename("Lost update").header(header::IF_MATCH, &etag).send().await.unwrap();
+    assert_eq!(stale.status(), StatusCode::PRECONDITION_FAILED);
+    let error: Value = stale.json().await.unwrap();
+    assert_eq!(error["code"], "precondition_failed");
+    
+    let delete = app.request(Method::DELETE, "/categories/1").header(header::IF_MATCH, &etag).send().await.unwrap();
+    assert_eq!(delete.status(), StatusCode::PRECONDITION_FAILED);
+    
+    let current: Value = app.request(Method::GET, "/categories/1").send().await.unwrap().json().await.unwrap();
+    assert_eq!(current["name"], "First edit");
+    
+    database.drop().await;
+}
+
+#[actix_web::test]
+async fn batch_edits_need_the_current_version() {
+    let database = TestDatabase::create(|_| {}).await;
+    let app = TestApp::spawn(&database).await;
+    
+    let read = app.request(Method::GET, "/categories/2").send().await.unwrap();
+    let version: i32 = read.headers()[header::ETAG].to_str().unwrap().trim_matches('"').parse().unwrap();
+    let batch = json!({
+        "mode": "per_item",
+        "operations": [
+            { "op": "update", "id": 2, "data": { "name": "Without version" } },
+            { "op": "delete", "id": 2 },
+            { "op": "update", "id": 2, "version": version - 1, "data": { "name": "Stale version" } },
+            { "op": "update", "id": 2, "version": version, "data": { "name": "Current version" } },
+        ],
+    });
+    let response: Value = app.request(Method::POST, "/categories/batch").json(&batch).send().await.unwrap().json().await.unwrap();
+    let statuses: Vec<u64> = response["results"].as_array().unwrap().iter().map(|result| result["status"].as_u64().unwrap()).collect();
+    assert_eq!(statuses, [428, 428, 412, 200]);
+    
+    let current: Value = app.request(Method::GET, "/categories/2").send().await.unwrap().json().await.unwrap();
+    assert_eq!(current["name"], "Current version");
+    
+    database.drop().await;
+}
@@ -0,0 +1,58 @@
+//! An import only updates records that have not changed since they were exported.
+
+mod common;
+
+use reqwest::{Method, StatusCode};
+use serde_json::Value;
+
+use common::{TestApp, TestDatabase};
+
+/// `csv` with the named column of its first data row replaced.
+fn edit(csv: &str, column: &str, value: &str) -> String {
+    let mut reader = csv::Reader::from_reader(csv.as_bytes());
+    let headers = reader.headers().unwrap().clone();
+    let index = headers.iter().position(|header| header == column).unwrap();
+    let row: Vec<String> = reader.records().next().unwrap().unwrap().iter().map(str::to_string).collect();
+    let row: Vec<&str> = row.iter().enumerate().map(|(i, cell)| if i == index { value } else { cell }).collect();
+    
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(&headers).unwrap();
+    writer.write_record(row).unwrap();
+    String::from_utf8(writer.into_inner().unwrap()).unwrap()
+}
+
+async fn import(app: &TestApp, csv: String) -> (StatusCode, Value) {
+    let response = app
+        .request(Method::POST, "/customers/import")
+        .header("Content-Type", "text/csv")
+        .body(csv)
+        .send()
+        .await
+        .unwrap();
+    (response.status(), response.json().await.unwrap())
+}
+
+#[actix_web::test]
+async fn updates_need_the_exported_version() {
+    let database = TestDatabase::create(|_| {}).await;
+    let app = TestApp::spawn(&database).await;
+    
+    let export = app.request(Method::GET, "/customers/export.csv").send().await.unwrap().text().await.unwrap();
+    let edited = edit(&export, "phone", "555-0100");
+    
+    let (status, report) = import(&app, edited.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["updated"], 1);
+    
+    // The same file again would overwrite the change it just made
+    let (status, error) = import(&app, edited.clone()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["details"][0]["field"], "rows[2].version");
+    
+    let (status, error) = import(&app, edit(&edited, "version", "")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["details"][0]["field"], "rows[2].version");
+    assert_eq!(error["details"][0]["message"], "is required to update an existing record");
+    
+    database.drop().await;
+}
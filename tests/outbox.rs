@@ -0,0 +1,34 @@
+//! Events are relayed in the order their transactions wrote them, even when a later
+//! event commits first.
+
+mod common;
+
+use crud_shop_slop::db::outbox::EventType;
+use crud_shop_slop::services::OutboxService;
+use sea_orm::TransactionTrait;
+use serde_json::json;
+
+use common::TestDatabase;
+
+#[actix_web::test]
+async fn waits_for_earlier_transactions_to_end() {
+    let database = TestDatabase::create(|_| {}).await;
+    let db = &database.db;
+    OutboxService::relay(db, 100).await.unwrap();
+    
+    let first = db.begin().await.unwrap();
+    OutboxService::publish(&first, EventType::CustomerCreated, "customer", 1, json!({})).await.unwrap();
+    
+    // Publishers do not wait for each other, so the later event commits first
+    let second = db.begin().await.unwrap();
+    OutboxService::publish(&second, EventType::CustomerCreated, "customer", 2, json!({})).await.unwrap();
+    second.commit().await.unwrap();
+    assert!(OutboxService::relay(db, 100).await.unwrap().is_empty());
+    
+    first.commit().await.unwrap();
+    let relayed = OutboxService::relay(db, 100).await.unwrap();
+    let aggregates: Vec<i32> = relayed.iter().map(|event| event.aggregate_id).collect();
+    assert_eq!(aggregates, [1, 2]);
+    
+    database.drop().await;
+}
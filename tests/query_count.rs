@@ -0,0 +1,104 @@
+//! The list and detail endpoints load related rows in batches, so the number of
+//! statements a request runs must not grow with the rows it returns.
+
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use reqwest::Method;
+use serde_json::{Value, json};
+
+use common::{TestApp, TestDatabase};
+
+struct Api {
+    app: TestApp,
+    statements: Arc<AtomicUsize>,
+}
+
+impl Api {
+    async fn send(&self, request: reqwest::RequestBuilder) -> Value {
+        let response = request.send().await.expect("request failed");
+        assert!(response.status().is_success(), "{} from {}", response.status(), response.url());
+        response.json().await.expect("response is not JSON")
+    }
+
+    async fn post(&self, path: &str, body: Value) -> Value {
+        self.send(self.app.request(Method::POST, path).json(&body)).await
+    }
+
+    /// GETs `path`, returning the body and how many statements the request ran.
+    async fn count(&self, path: &str) -> (Value, usize) {
+        self.statements.store(0, Ordering::SeqCst);
+        let body = self.send(self.app.request(Method::GET, path)).await;
+        (body, self.statements.load(Ordering::SeqCst))
+    }
+}
+
+#[actix_web::test]
+async fn statements_do_not_grow_with_rows() {
+    let statements = Arc::new(AtomicUsize::new(0));
+    let counter = statements.clone();
+    let database = TestDatabase::create(|db| {
+        db.set_metric_callback(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        })
+    })
+    .await;
+    let api = Api { app: TestApp::spawn(&database).await, statements };
+
+    for collection in ["products", "orders"] {
+        let (one, one_count) = api.count(&format!("/{collection}?per_page=1")).await;
+        let (many, many_count) = api.count(&format!("/{collection}?per_page=1000")).await;
+        assert!(one_count > 0, "no statements were counted");
+        assert_eq!(one["items"].as_array().unwrap().len(), 1);
+        assert!(many["items"].as_array().unwrap().len() > 1, "too few {collection} to compare");
+        assert_eq!(one_count, many_count, "statements for one page of {collection} vs many");
+    }
+
+    let (products, _) = api.count("/products?supplier_id=1&per_page=1000").await;
+    let products: Vec<i64> = products["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|product| product["product_id"].as_i64().unwrap())
+        .collect();
+    assert!(products.len() >= 3, "supplier 1 needs several products");
+
+    let order = |items: &[i64]| {
+        json!({
+            "customer_id": 1,
+            "shipping_address": "1 Test Street",
+            "items": items.iter().map(|id| json!({ "product_id": id, "quantity": 1 })).collect::<Vec<_>>(),
+        })
+    };
+    let single = api.post("/orders", order(&products[..1])).await;
+    let several = api.post("/orders", order(&products)).await;
+    let (one, one_count) = api.count(&format!("/orders/{}/details", single["order_id"])).await;
+    let (many, many_count) = api.count(&format!("/orders/{}/details", several["order_id"])).await;
+    assert_eq!(one["items"].as_array().unwrap().len(), 1);
+    assert_eq!(many["items"].as_array().unwrap().len(), products.len());
+    assert_eq!(one_count, many_count, "statements for order details with one item vs many");
+
+    let shipment = |items: &[i64]| {
+        json!({
+            "supplier_id": 1,
+            "shipment_date": "2026-01-01",
+            "expected_delivery_date": "2026-01-08",
+            "total_cost": "10.00",
+            "items": items
+                .iter()
+                .map(|id| json!({ "product_id": id, "quantity": 2, "unit_cost": "1.00" }))
+                .collect::<Vec<_>>(),
+        })
+    };
+    let single = api.post("/shipments", shipment(&products[..1])).await;
+    let several = api.post("/shipments", shipment(&products)).await;
+    let (one, one_count) = api.count(&format!("/shipments/{}/details", single["shipment_id"])).await;
+    let (many, many_count) = api.count(&format!("/shipments/{}/details", several["shipment_id"])).await;
+    assert_eq!(one["items"].as_array().unwrap().len(), 1);
+    assert_eq!(many["items"].as_array().unwrap().len(), products.len());
+    assert_eq!(one_count, many_count, "statements for shipment details with one item vs many");
+
+    database.drop().await;
+}
@@ -0,0 +1,133 @@
+//! Delivers an event to a local stand-in for a subscriber that fails the first attempt.
+
+mod common;
+
+use std::net::TcpListener;
+use std::sync::Mutex;
+
+use actix_web::{App, HttpRequest, HttpResponse, HttpServer, web};
+use chrono::Utc;
+use crud_shop_slop::config::WebhookConfig;
+use crud_shop_slop::db::outbox::EventType;
+use crud_shop_slop::db::webhook_deliveries::{self, DeliveryStatus};
+use crud_shop_slop::db::WebhookDeliveries;
+use crud_shop_slop::dtos::WebhookCreate;
+use crud_shop_slop::services::{OutboxService, WebhookService};
+use hmac::{Hmac, Mac};
+use sea_orm::{ActiveModelTrait, EntityTrait, Set, TransactionTrait};
+use serde_json::{Value, json};
+use sha2::Sha256;
+
+use common::TestDatabase;
+
+/// What the subscriber was sent.
+struct Received {
+    signature: String,
+    delivery: String,
+    body: String,
+}
+
+/// Answers the first request with a 500 and every later one with a 200.
+async fn subscriber(req: HttpRequest, body: String, received: web::Data<Mutex<Vec<Received>>>) -> HttpResponse {
+    let header = |name: &str| {
+        req.headers().get(name).and_then(|value| value.to_str().ok()).unwrap_or_default().to_string()
+    };
+    let mut received = received.lock().unwrap();
+    received.push(Received {
+        signature: header("X-Webhook-Signature"),
+        delivery: header("X-Webhook-Delivery"),
+        body,
+    });
+    if received.len() == 1 {
+        HttpResponse::InternalServerError().finish()
+    } else {
+        HttpResponse::Ok().finish()
+    }
+}
+
+/// Checks a `t=..,v1=..` header against the HMAC-SHA256 of `"{t}.{body}"`.
+fn assert_signed(received: &Received, secret: &str) {
+    let (timestamp, signature) = received
+        .signature
+        .strip_prefix("t=")
+        .and_then(|rest| rest.split_once(",v1="))
+        .unwrap_or_else(|| panic!("malformed signature header {:?}", received.signature));
+    let timestamp: i64 = timestamp.parse().unwrap();
+    assert!((Utc::now().timestamp() - timestamp).abs() < 60, "stale signature timestamp");
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("{timestamp}.{}", received.body).as_bytes());
+    mac.verify_slice(&hex::decode(signature).unwrap()).expect("signature does not match the body");
+}
+
+async fn delivery(database: &TestDatabase, id: i32) -> webhook_deliveries::Model {
+    WebhookDeliveries::find_by_id(id).one(&database.db).await.unwrap().unwrap()
+}
+
+#[actix_web::test]
+async fn retries_a_failed_delivery_with_backoff() {
+    let database = TestDatabase::create(|_| {}).await;
+    let db = &database.db;
+
+    let received = web::Data::new(Mutex::new(Vec::<Received>::new()));
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let server_data = received.clone();
+    let server = HttpServer::new(move || App::new().app_data(server_data.clone()).default_service(web::to(subscriber)))
+        .workers(1)
+        .listen(listener)
+        .unwrap()
+        .run();
+    actix_web::rt::spawn(server);
+
+    // Events published by the migrations' mock data are not for this subscriber
+    OutboxService::relay(db, 100).await.unwrap();
+    let webhook = WebhookService::create(db, WebhookCreate { url, event_type: EventType::OrderCreated, active: None })
+        .await
+        .unwrap();
+    let txn = db.begin().await.unwrap();
+    OutboxService::publish(&txn, EventType::OrderCreated, "order", 42, json!({ "order_id": 42 })).await.unwrap();
+    txn.commit().await.unwrap();
+    OutboxService::relay(db, 100).await.unwrap();
+
+    let config = WebhookConfig { max_attempts: 3, ..database.config.webhooks.clone() };
+    let client = reqwest::Client::new();
+
+    // The first attempt gets a 500 and is put off for backoff_base_secs
+    let attempted = Utc::now();
+    assert_eq!(WebhookService::dispatch_due(db, &client, &config).await.unwrap(), 1);
+    let id: i32 = received.lock().unwrap()[0].delivery.parse().unwrap();
+    let failed = delivery(&database, id).await;
+    assert_eq!(failed.webhook_id, webhook.webhook_id);
+    assert_eq!(failed.status, DeliveryStatus::Pending);
+    assert_eq!(failed.attempts, 1);
+    assert_eq!(failed.last_status_code, Some(500));
+    assert_eq!(failed.delivered_at, None);
+    let backoff = (failed.next_attempt_at - attempted).num_seconds() - config.backoff_base_secs;
+    assert!((-1..=5).contains(&backoff), "retry is {backoff}s off the configured backoff");
+
+    // Nothing is due until the backoff has passed
+    assert_eq!(WebhookService::dispatch_due(db, &client, &config).await.unwrap(), 0);
+    let mut due: webhook_deliveries::ActiveModel = failed.into();
+    due.next_attempt_at = Set(Utc::now());
+    due.update(db).await.unwrap();
+
+    assert_eq!(WebhookService::dispatch_due(db, &client, &config).await.unwrap(), 1);
+    let delivered = delivery(&database, id).await;
+    assert_eq!(delivered.status, DeliveryStatus::Succeeded);
+    assert_eq!(delivered.attempts, 2);
+    assert_eq!(delivered.last_status_code, Some(200));
+    assert!(delivered.delivered_at.is_some());
+
+    let received = std::mem::take(&mut *received.lock().unwrap());
+    assert_eq!(received.len(), 2);
+    for request in received.iter() {
+        assert_eq!(request.body, delivered.payload);
+        assert_signed(request, &webhook.secret);
+    }
+    let body: Value = serde_json::from_str(&received[0].body).unwrap();
+    assert_eq!(body["event"], "order.created");
+    assert_eq!(body["data"]["order_id"], 42);
+
+    database.drop().await;
+}